@@ -0,0 +1,87 @@
+use std::{collections::HashMap, str::FromStr};
+
+use anyhow::{anyhow, Context};
+
+/// Args is a minimal command line parser. Options are written as `--name=value` (or `--name`
+/// with no value), and everything else is positional.
+pub struct Args {
+    pub positional: Vec<String>,
+    options: HashMap<String, Option<String>>,
+}
+
+impl Args {
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Self {
+        let mut positional = Vec::new();
+        let mut options = HashMap::new();
+        for arg in args {
+            match arg.strip_prefix("--") {
+                Some(option) => match option.split_once('=') {
+                    Some((name, value)) => {
+                        options.insert(name.to_string(), Some(value.to_string()))
+                    }
+                    None => options.insert(option.to_string(), None),
+                },
+                None => {
+                    positional.push(arg);
+                    None
+                }
+            };
+        }
+        Self {
+            positional,
+            options,
+        }
+    }
+
+    /// Parses the process's arguments, skipping the program name.
+    pub fn from_env() -> Self {
+        Self::parse(std::env::args().skip(1))
+    }
+
+    /// Removes and returns the first positional argument if it is one of `subcommands`.
+    pub fn subcommand(&mut self, subcommands: &[&str]) -> Option<String> {
+        let first = self.positional.first()?;
+        if subcommands.contains(&first.as_str()) {
+            Some(self.positional.remove(0))
+        } else {
+            None
+        }
+    }
+
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.options.get(name).and_then(|value| value.as_deref())
+    }
+
+    /// Whether `--name` was given, with or without a value.
+    pub fn flag(&self, name: &str) -> bool {
+        self.options.contains_key(name)
+    }
+
+    /// Parses the value of `--name=value`, or returns `None` if the option wasn't given.
+    pub fn parse_value<T>(&self, name: &str) -> anyhow::Result<Option<T>>
+    where
+        T: FromStr,
+        T::Err: std::fmt::Display,
+    {
+        self.value(name)
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|e| anyhow!("{}", e))
+                    .with_context(|| format!("invalid value for --{}: {:?}", name, value))
+            })
+            .transpose()
+    }
+
+    /// Parses a `WIDTHxHEIGHT` option, eg. `--size=1920x1080`.
+    pub fn size(&self, name: &str) -> anyhow::Result<Option<(u32, u32)>> {
+        self.value(name)
+            .map(|value| {
+                value
+                    .split_once('x')
+                    .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+                    .ok_or_else(|| anyhow!("invalid value for --{}: expected WIDTHxHEIGHT", name))
+            })
+            .transpose()
+    }
+}
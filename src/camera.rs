@@ -1,5 +1,7 @@
 use wgpu::util::DeviceExt;
-use winit::event::{ElementState, KeyboardInput, VirtualKeyCode, WindowEvent};
+use winit::event::{
+    ElementState, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+};
 
 pub(crate) struct CameraController {
     pub(crate) speed: f32,
@@ -7,6 +9,11 @@ pub(crate) struct CameraController {
     pub(crate) is_backward_pressed: bool,
     pub(crate) is_left_pressed: bool,
     pub(crate) is_right_pressed: bool,
+    pub(crate) is_dragging: bool,
+    pub(crate) cursor: Option<(f64, f64)>,
+    // Mouse movement and scrolling accumulated since the last update_camera, in pixels and lines.
+    pub(crate) drag_delta: (f32, f32),
+    pub(crate) scroll_delta: f32,
 }
 
 impl CameraController {
@@ -17,6 +24,10 @@ impl CameraController {
             is_backward_pressed: false,
             is_left_pressed: false,
             is_right_pressed: false,
+            is_dragging: false,
+            cursor: None,
+            drag_delta: (0.0, 0.0),
+            scroll_delta: 0.0,
         }
     }
 
@@ -52,11 +63,34 @@ impl CameraController {
                     _ => false,
                 }
             }
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => {
+                self.is_dragging = *state == ElementState::Pressed;
+                true
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                if let (true, Some((x, y))) = (self.is_dragging, self.cursor) {
+                    self.drag_delta.0 += (position.x - x) as f32;
+                    self.drag_delta.1 += (position.y - y) as f32;
+                }
+                self.cursor = Some((position.x, position.y));
+                self.is_dragging
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.scroll_delta += match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / 20.0,
+                };
+                true
+            }
             _ => false,
         }
     }
 
-    pub(crate) fn update_camera(&self, camera: &mut Camera) {
+    pub(crate) fn update_camera(&mut self, camera: &mut Camera) {
         use cgmath::InnerSpace;
         let forward = camera.target - camera.eye;
         let forward_norm = forward.normalize();
@@ -86,6 +120,35 @@ impl CameraController {
         if self.is_left_pressed {
             camera.eye = camera.target - (forward - right * self.speed).normalize() * forward_mag;
         }
+
+        self.orbit_camera(camera);
+    }
+
+    /// Applies mouse drags as yaw/pitch around the target and scrolling as zoom.
+    fn orbit_camera(&mut self, camera: &mut Camera) {
+        use cgmath::{InnerSpace, Rotation, Rotation3};
+        const RADIANS_PER_PIXEL: f32 = 0.005;
+
+        let (dx, dy) = std::mem::take(&mut self.drag_delta);
+        let scroll = std::mem::take(&mut self.scroll_delta);
+
+        let offset = camera.eye - camera.target;
+        let right = offset.cross(camera.up).normalize();
+        let yaw =
+            cgmath::Quaternion::from_axis_angle(camera.up, cgmath::Rad(-dx * RADIANS_PER_PIXEL));
+        let pitch = cgmath::Quaternion::from_axis_angle(right, cgmath::Rad(dy * RADIANS_PER_PIXEL));
+        let offset = (yaw * pitch).rotate_vector(offset);
+
+        // Keep the eye from flipping over the poles, where the view matrix degenerates.
+        let offset = if offset.normalize().dot(camera.up).abs() < 0.99 {
+            offset
+        } else {
+            camera.eye - camera.target
+        };
+
+        let zoom = (1.0 - scroll * self.speed).max(0.1);
+        let distance = (offset.magnitude() * zoom).max(self.speed);
+        camera.eye = camera.target + offset.normalize() * distance;
     }
 }
 
@@ -0,0 +1,480 @@
+//! Signal processing between a source and whatever consumes its frames, whether that's a trace
+//! on screen or a WAV being written.
+
+use std::{collections::VecDeque, f32::consts::TAU, fmt, str::FromStr};
+
+use anyhow::{anyhow, bail, Context};
+
+use crate::sound::Frame;
+
+/// Axis is one channel of a frame.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl FromStr for Axis {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "x" => Ok(Axis::X),
+            "y" => Ok(Axis::Y),
+            "z" => Ok(Axis::Z),
+            _ => bail!("unknown axis {:?}, expected x, y or z", s),
+        }
+    }
+}
+
+impl fmt::Display for Axis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Axis::X => "x",
+            Axis::Y => "y",
+            Axis::Z => "z",
+        })
+    }
+}
+
+/// Sample is a frame with every channel scaled to -1.0..=1.0, which is what nodes work on.
+#[derive(Copy, Clone, Debug)]
+struct Sample {
+    x: f32,
+    y: f32,
+    z: Option<f32>,
+}
+
+impl Sample {
+    fn from_frame((x, y, z): Frame) -> Self {
+        let scale = |sample: i32| sample as f32 / i16::MAX as f32;
+        Self {
+            x: scale(x),
+            y: scale(y),
+            z: z.map(scale),
+        }
+    }
+
+    fn to_frame(self) -> Frame {
+        let scale = |sample: f32| {
+            ((sample * i16::MAX as f32).round() as i32).clamp(i16::MIN as i32, i16::MAX as i32)
+        };
+        (scale(self.x), scale(self.y), self.z.map(scale))
+    }
+
+    fn axis_mut(&mut self, axis: Axis) -> Option<&mut f32> {
+        match axis {
+            Axis::X => Some(&mut self.x),
+            Axis::Y => Some(&mut self.y),
+            Axis::Z => self.z.as_mut(),
+        }
+    }
+}
+
+/// Filter is a one-pole filter on X and Y, gentle enough not to smear shapes with ringing.
+#[derive(Clone, Debug)]
+pub struct Filter {
+    /// The frequency where the filter starts to cut, in Hz.
+    pub cutoff: f32,
+    // The last output on X and Y
+    low: [f32; 2],
+}
+
+impl Filter {
+    fn new(cutoff: f32) -> Self {
+        Self {
+            cutoff,
+            low: [0.0; 2],
+        }
+    }
+
+    /// Filters `sample` in place, keeping only what's below the cutoff, or only what's above it
+    /// for a high-pass.
+    fn process(&mut self, sample: &mut Sample, sample_rate: u32, high_pass: bool) {
+        let alpha = 1.0 - (-TAU * self.cutoff.max(0.0) / sample_rate as f32).exp();
+        for (axis, low) in [&mut sample.x, &mut sample.y]
+            .into_iter()
+            .zip(&mut self.low)
+        {
+            *low += alpha * (*axis - *low);
+            *axis = if high_pass { *axis - *low } else { *low };
+        }
+    }
+}
+
+/// Node is one step of a Chain.
+#[derive(Clone, Debug)]
+pub enum Node {
+    /// Scales X and Y.
+    Gain(f32),
+    /// Removes any constant offset from X and Y, which would push the drawing off center.
+    DcBlock(Filter),
+    LowPass(Filter),
+    HighPass(Filter),
+    /// Turns the drawing anticlockwise, by degrees.
+    Rotate(f32),
+    /// Flips X, Y or both.
+    Mirror {
+        x: bool,
+        y: bool,
+    },
+    /// Swaps X and Y.
+    Swap,
+    /// Turns left/right stereo into mid/side, so mono content is drawn as a vertical line like on
+    /// a goniometer.
+    MidSide,
+    /// Squashes X and Y smoothly into -1.0..=1.0 with tanh, harder the higher the drive.
+    SoftClip(f32),
+    /// Delays one channel behind the others by some samples.
+    Delay {
+        axis: Axis,
+        samples: usize,
+        buffer: VecDeque<f32>,
+    },
+}
+
+impl Node {
+    fn process(&mut self, sample: &mut Sample, sample_rate: u32) {
+        match self {
+            Node::Gain(gain) => {
+                sample.x *= *gain;
+                sample.y *= *gain;
+            }
+            Node::DcBlock(filter) | Node::HighPass(filter) => {
+                filter.process(sample, sample_rate, true)
+            }
+            Node::LowPass(filter) => filter.process(sample, sample_rate, false),
+            Node::Rotate(degrees) => {
+                let (sin, cos) = degrees.to_radians().sin_cos();
+                let (x, y) = (sample.x, sample.y);
+                sample.x = x * cos - y * sin;
+                sample.y = x * sin + y * cos;
+            }
+            Node::Mirror { x, y } => {
+                if *x {
+                    sample.x = -sample.x;
+                }
+                if *y {
+                    sample.y = -sample.y;
+                }
+            }
+            Node::Swap => std::mem::swap(&mut sample.x, &mut sample.y),
+            Node::MidSide => {
+                let (left, right) = (sample.x, sample.y);
+                sample.x = (right - left) * std::f32::consts::FRAC_1_SQRT_2;
+                sample.y = (left + right) * std::f32::consts::FRAC_1_SQRT_2;
+            }
+            Node::SoftClip(drive) => {
+                if *drive > 0.0 {
+                    let clip = |axis: f32| (axis * *drive).tanh() / drive.tanh();
+                    sample.x = clip(sample.x);
+                    sample.y = clip(sample.y);
+                }
+            }
+            Node::Delay {
+                axis,
+                samples,
+                buffer,
+            } => {
+                if let Some(value) = sample.axis_mut(*axis) {
+                    buffer.push_back(*value);
+                    // Starts out silent until the delay has filled up
+                    *value = if buffer.len() > *samples {
+                        buffer.pop_front().unwrap_or_default()
+                    } else {
+                        0.0
+                    };
+                    while buffer.len() > *samples {
+                        buffer.pop_front();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Changes one of the node's parameters while it's running, eg. `cutoff` of a low-pass.
+    pub fn set(&mut self, param: &str, value: f32) -> anyhow::Result<()> {
+        // Once in a filter's state, NaN would never leave it
+        if !value.is_finite() {
+            bail!("{} needs a finite {}, not {}", self.name(), param, value);
+        }
+        match (self, param) {
+            (Node::Gain(gain), "gain") => *gain = value,
+            (Node::DcBlock(filter) | Node::LowPass(filter) | Node::HighPass(filter), "cutoff") => {
+                filter.cutoff = value
+            }
+            (Node::Rotate(degrees), "degrees") => *degrees = value,
+            (Node::Mirror { x, .. }, "x") => *x = value != 0.0,
+            (Node::Mirror { y, .. }, "y") => *y = value != 0.0,
+            (Node::SoftClip(drive), "drive") => *drive = value,
+            (Node::Delay { samples, .. }, "samples") => *samples = value.max(0.0) as usize,
+            (node, _) => bail!("{} has no parameter {:?}", node.name(), param),
+        }
+        Ok(())
+    }
+
+    /// Returns the node's main parameter moved one step up or down, for nudging it from the
+    /// keyboard, or `None` if it has nothing to nudge.
+    pub fn nudged(&self, up: bool) -> Option<(&'static str, f32)> {
+        let sign = if up { 1.0 } else { -1.0 };
+        match self {
+            Node::Gain(gain) => Some(("gain", gain + sign * 0.1)),
+            // Cutoffs move in even steps of pitch rather than Hz
+            Node::DcBlock(filter) | Node::LowPass(filter) | Node::HighPass(filter) => {
+                Some(("cutoff", filter.cutoff * 1.25f32.powf(sign)))
+            }
+            Node::Rotate(degrees) => Some(("degrees", degrees + sign * 5.0)),
+            Node::SoftClip(drive) => Some(("drive", (drive + sign * 0.25).max(0.0))),
+            Node::Delay { samples, .. } => Some(("samples", *samples as f32 + sign)),
+            Node::Mirror { .. } | Node::Swap | Node::MidSide => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Node::Gain(_) => "gain",
+            Node::DcBlock(_) => "dc",
+            Node::LowPass(_) => "lowpass",
+            Node::HighPass(_) => "highpass",
+            Node::Rotate(_) => "rotate",
+            Node::Mirror { .. } => "mirror",
+            Node::Swap => "swap",
+            Node::MidSide => "ms",
+            Node::SoftClip(_) => "clip",
+            Node::Delay { .. } => "delay",
+        }
+    }
+}
+
+/// Parses a node written as its name followed by any arguments after colons, eg. `gain:2`,
+/// `lowpass:4000`, `mirror:xy` or `delay:y:10`.
+impl FromStr for Node {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let name = parts.next().unwrap_or_default();
+        let arguments: Vec<&str> = parts.collect();
+        let number = |i: usize, default: Option<f32>| -> anyhow::Result<f32> {
+            match (arguments.get(i), default) {
+                (Some(argument), _) => {
+                    let value: f32 = argument
+                        .parse()
+                        .with_context(|| format!("invalid argument {:?} for {}", argument, name))?;
+                    if !value.is_finite() {
+                        bail!(
+                            "invalid argument {:?} for {}, it has to be finite",
+                            argument,
+                            name
+                        );
+                    }
+                    Ok(value)
+                }
+                (None, Some(default)) => Ok(default),
+                (None, None) => Err(anyhow!("{} needs an argument", name)),
+            }
+        };
+
+        let node = match name {
+            "gain" => Node::Gain(number(0, None)?),
+            "dc" => Node::DcBlock(Filter::new(number(0, Some(5.0))?)),
+            "lowpass" => Node::LowPass(Filter::new(number(0, None)?)),
+            "highpass" => Node::HighPass(Filter::new(number(0, None)?)),
+            "rotate" => Node::Rotate(number(0, None)?),
+            "mirror" => {
+                let axes = arguments.first().copied().unwrap_or("x");
+                // none is only written out for a mirror with both axes switched off at runtime
+                let axes = if axes == "none" { "" } else { axes };
+                if !axes.chars().all(|axis| axis == 'x' || axis == 'y') {
+                    bail!(
+                        "invalid argument {:?} for mirror, expected x, y or xy",
+                        axes
+                    );
+                }
+                Node::Mirror {
+                    x: axes.contains('x'),
+                    y: axes.contains('y'),
+                }
+            }
+            "swap" => Node::Swap,
+            "ms" => Node::MidSide,
+            "clip" => Node::SoftClip(number(0, Some(1.0))?),
+            "delay" => {
+                let axis = arguments
+                    .first()
+                    .ok_or_else(|| anyhow!("delay needs an axis and a number of samples"))?
+                    .parse()?;
+                Node::Delay {
+                    axis,
+                    samples: number(1, None)?.max(0.0) as usize,
+                    buffer: VecDeque::new(),
+                }
+            }
+            _ => bail!(
+                "unknown node {:?}, expected gain, dc, lowpass, highpass, rotate, mirror, swap, \
+                 ms, clip or delay",
+                name
+            ),
+        };
+        Ok(node)
+    }
+}
+
+/// Writes a node the way it's parsed, with its current parameters.
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Node::Gain(gain) => write!(f, "gain:{}", gain),
+            Node::DcBlock(filter) | Node::LowPass(filter) | Node::HighPass(filter) => {
+                write!(f, "{}:{}", self.name(), filter.cutoff)
+            }
+            Node::Rotate(degrees) => write!(f, "rotate:{}", degrees),
+            Node::Mirror { x: false, y: false } => write!(f, "mirror:none"),
+            Node::Mirror { x, y } => {
+                let axes = [(*x, "x"), (*y, "y")]
+                    .into_iter()
+                    .filter_map(|(on, axis)| on.then_some(axis))
+                    .collect::<String>();
+                write!(f, "mirror:{}", axes)
+            }
+            Node::Swap | Node::MidSide => f.write_str(self.name()),
+            Node::SoftClip(drive) => write!(f, "clip:{}", drive),
+            Node::Delay { axis, samples, .. } => write!(f, "delay:{}:{}", axis, samples),
+        }
+    }
+}
+
+/// Chain is a list of nodes that frames go through in order.
+#[derive(Clone, Debug, Default)]
+pub struct Chain {
+    pub nodes: Vec<Node>,
+}
+
+impl Chain {
+    /// Runs a frame from a source at `sample_rate` through every node.
+    pub fn process(&mut self, frame: Frame, sample_rate: u32) -> Frame {
+        // Frames pass through an empty chain untouched, rather than being rounded
+        if self.nodes.is_empty() {
+            return frame;
+        }
+        let mut sample = Sample::from_frame(frame);
+        for node in &mut self.nodes {
+            node.process(&mut sample, sample_rate);
+        }
+        sample.to_frame()
+    }
+
+    /// Runs frames from a source at `sample_rate` through the chain as they're read.
+    pub fn apply<I>(mut self, frames: I, sample_rate: u32) -> impl Iterator<Item = Frame>
+    where
+        I: IntoIterator<Item = Frame>,
+    {
+        frames
+            .into_iter()
+            .map(move |frame| self.process(frame, sample_rate))
+    }
+
+    /// Changes a parameter of the node at `index`.
+    pub fn set(&mut self, index: usize, param: &str, value: f32) -> anyhow::Result<()> {
+        let count = self.nodes.len();
+        self.nodes
+            .get_mut(index)
+            .ok_or_else(|| anyhow!("no node {} in a chain of {}", index, count))?
+            .set(param, value)
+    }
+}
+
+impl fmt::Display for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, node) in self.nodes.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{}", node)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses a comma separated list of nodes, eg. `dc,gain:1.5,rotate:45`.
+impl FromStr for Chain {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let nodes = s
+            .split(',')
+            .map(str::trim)
+            .filter(|node| !node.is_empty())
+            .map(str::parse)
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self { nodes })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chains_read_back_what_they_write() {
+        let text =
+            "dc:5,gain:1.5,lowpass:4000,highpass:20,rotate:-45,mirror:xy,mirror:none,swap,ms,\
+                    clip:2,delay:y:10";
+        let chain: Chain = text.parse().unwrap();
+        assert_eq!(chain.nodes.len(), 11);
+        assert_eq!(chain.to_string(), text);
+        assert_eq!(
+            chain.to_string().parse::<Chain>().unwrap().to_string(),
+            text
+        );
+    }
+
+    #[test]
+    fn defaults_are_written_out() {
+        let chain: Chain = " dc, clip ,mirror,".parse().unwrap();
+        assert_eq!(chain.to_string(), "dc:5,clip:1,mirror:x");
+        assert_eq!("".parse::<Chain>().unwrap().to_string(), "");
+    }
+
+    #[test]
+    fn runtime_changes_are_written_out() {
+        let mut chain: Chain = "lowpass:4000,mirror:x".parse().unwrap();
+        chain.set(0, "cutoff", 2500.0).unwrap();
+        chain.set(1, "x", 0.0).unwrap();
+        assert_eq!(chain.to_string(), "lowpass:2500,mirror:none");
+        assert_eq!(
+            chain.to_string().parse::<Chain>().unwrap().to_string(),
+            "lowpass:2500,mirror:none"
+        );
+    }
+
+    #[test]
+    fn bad_nodes_are_rejected() {
+        for text in [
+            "gain",
+            "gain:loud",
+            "gain:NaN",
+            "lowpass:inf",
+            "rotate:-inf",
+            "mirror:z",
+            "delay:10",
+            "delay:w:10",
+            "delay:y",
+            "wobble",
+            "dc,wobble",
+        ] {
+            assert!(text.parse::<Chain>().is_err(), "{:?} parsed", text);
+        }
+    }
+
+    #[test]
+    fn bad_parameters_are_rejected() {
+        let mut chain: Chain = "gain:2,lowpass:4000".parse().unwrap();
+        assert!(chain.set(0, "gain", f32::NAN).is_err());
+        assert!(chain.set(1, "cutoff", f32::INFINITY).is_err());
+        assert!(chain.set(0, "cutoff", 100.0).is_err());
+        assert!(chain.set(2, "gain", 1.0).is_err());
+        assert_eq!(chain.to_string(), "gain:2,lowpass:4000");
+    }
+}
@@ -0,0 +1,136 @@
+//! A single-stroke vector font in the spirit of the Hershey fonts, drawn on a small grid so every
+//! glyph is a handful of strokes the beam can trace without lifting.
+
+use super::Polyline;
+
+/// Glyphs are drawn in a cell this many units wide, and advance by this much plus a gap.
+pub const GLYPH_WIDTH: f32 = 4.0;
+pub const ADVANCE: f32 = 6.0;
+/// Capitals and ascenders reach this far above the baseline, and descenders this far below.
+pub const CAP_HEIGHT: f32 = 6.0;
+pub const DESCENT: f32 = 2.0;
+
+/// Strokes for each printable ASCII character from ' ' to '~'. Strokes are separated by spaces,
+/// and each is a run of points written as two digits, x then y. The baseline is at y = 2, so
+/// descenders reach down to 0 and capitals up to 8.
+#[rustfmt::skip]
+const GLYPHS: [&str; 95] = [
+    "",                                 // ' '
+    "2824 2322",                        // !
+    "1817 3837",                        // "
+    "1713 3733 0646 0444",              // #
+    "4616051434433202 2820",            // $
+    "0248 0818170708 3343423233",       // %
+    "4216172837360403122244",           // &
+    "2827",                             // '
+    "38262331",                         // (
+    "18262311",                         // )
+    "2723 0644 4604",                   // *
+    "2723 0545",                        // +
+    "232211",                           // ,
+    "0545",                             // -
+    "2322",                             // .
+    "0248",                             // /
+    "120307183847433212 0347",          // 0
+    "172822 1232",                      // 1
+    "07183847460242",                   // 2
+    "07183847463525 354443321203",      // 3
+    "32380444",                         // 4
+    "480805354443321203",               // 5
+    "4738180703123243443505",           // 6
+    "084812",                           // 7
+    "15060718384746351504031232434435", // 8
+    "4515060718384743321203",           // 9
+    "2625 2322",                        // :
+    "2625 232211",                      // ;
+    "470543",                           // <
+    "0646 0444",                        // =
+    "074503",                           // >
+    "07183847462524 2322",              // ?
+    "34141636344447381807031242",       // @
+    "022842 1535",                      // A
+    "02083847463505 3544433202",        // B
+    "4738180703123243",                 // C
+    "02082846442202",                   // D
+    "48080242 0535",                    // E
+    "480802 0535",                      // F
+    "47381807031232434525",             // G
+    "0208 4248 0545",                   // H
+    "1838 2822 1232",                   // I
+    "4843321203",                       // J
+    "0208 4804 1542",                   // K
+    "080242",                           // L
+    "0208254842",                       // M
+    "02084248",                         // N
+    "120307183847433212",               // O
+    "02083847463505",                   // P
+    "120307183847433212 2442",          // Q
+    "02083847463505 2542",              // R
+    "473818070615354443321203",         // S
+    "0848 2822",                        // T
+    "080312324348",                     // U
+    "082248",                           // V
+    "0812263248",                       // W
+    "0842 4802",                        // X
+    "082548 2522",                      // Y
+    "08480242",                         // Z
+    "38181131",                         // [
+    "0842",                             // \
+    "18383111",                         // ]
+    "162836",                           // ^
+    "0040",                             // _
+    "1827",                             // `
+    "4642 4536160503123243",            // a
+    "0802 0516364543321203",            // b
+    "4536160503123243",                 // c
+    "4842 4536160503123243",            // d
+    "04444536160503123243",             // e
+    "4738281712 0636",                  // f
+    "4641301001 4536160503123243",      // g
+    "0802 0516364542",                  // h
+    "2622 2827",                        // i
+    "3631201001 3837",                  // j
+    "0802 4603 1442",                   // k
+    "18132232",                         // l
+    "0602 05162522 25364542",           // m
+    "0602 0516364542",                  // n
+    "120305163645433212",               // o
+    "0600 0516364543321203",            // p
+    "4640 4536160503123243",            // q
+    "0602 042646",                      // r
+    "45361605143443321203",             // s
+    "1813223243 0636",                  // t
+    "0603123243 4642",                  // u
+    "062246",                           // v
+    "0612253246",                       // w
+    "0642 4602",                        // x
+    "0622 4610",                        // y
+    "06460242",                         // z
+    "38272615242231",                   // {
+    "2820",                             // |
+    "18272635242211",                   // }
+    "05163445",                         // ~
+];
+
+/// Returns the strokes of `c` in glyph units, with the origin at the left of the baseline.
+/// Characters the font doesn't cover are drawn as '?'.
+pub fn glyph(c: char) -> Vec<Polyline> {
+    let index = match c {
+        ' '..='~' => c as usize - ' ' as usize,
+        _ => '?' as usize - ' ' as usize,
+    };
+    GLYPHS[index]
+        .split_whitespace()
+        .map(|stroke| {
+            stroke
+                .as_bytes()
+                .chunks(2)
+                .map(|digits| {
+                    let x = (digits[0] - b'0') as f32;
+                    let y = (digits[1] - b'0') as f32 - DESCENT;
+                    [x, y]
+                })
+                .collect()
+        })
+        .collect()
+}
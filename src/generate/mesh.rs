@@ -0,0 +1,328 @@
+use std::collections::HashSet;
+
+use anyhow::{bail, Context};
+use cgmath::{Deg, InnerSpace, Quaternion, Rad, Rotation3, Vector3};
+
+use crate::{
+    args::Args,
+    dsp::Chain,
+    onepass::{self, ViewMode},
+};
+
+use super::{Generator, Polyline, Sketch};
+
+/// Mesh is the edges of a Wavefront OBJ, as pairs of indices into its vertices.
+pub struct Mesh {
+    pub vertices: Vec<Vector3<f32>>,
+    pub edges: Vec<(usize, usize)>,
+}
+
+/// Wireframe spins a mesh's edges in 3D. Its edges are chained into as few continuous paths as
+/// possible, so the beam rarely has to jump.
+pub struct Wireframe {
+    paths: Vec<Vec<Vector3<f32>>>,
+    /// Revolutions per second about the vertical axis.
+    pub spin: f32,
+    /// How far the spinning mesh is tipped towards the viewer.
+    pub tilt: Deg<f32>,
+}
+
+impl Wireframe {
+    /// Chains the edges of a mesh into paths, after centering it and scaling it to fit in the
+    /// unit sphere (so it stays on screen however it's turned).
+    pub fn new(mesh: &Mesh) -> Self {
+        let Mesh { vertices, edges } = mesh;
+        let (mut min, mut max) = (
+            Vector3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            Vector3::new(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+        );
+        for vertex in vertices {
+            min = Vector3::new(
+                min.x.min(vertex.x),
+                min.y.min(vertex.y),
+                min.z.min(vertex.z),
+            );
+            max = Vector3::new(
+                max.x.max(vertex.x),
+                max.y.max(vertex.y),
+                max.z.max(vertex.z),
+            );
+        }
+        let center = (min + max) / 2.0;
+        let radius = vertices
+            .iter()
+            .map(|vertex| (vertex - center).magnitude())
+            .fold(0.0, f32::max)
+            .max(f32::EPSILON);
+
+        let paths = trails(vertices.len(), edges)
+            .into_iter()
+            .map(|trail| {
+                trail
+                    .into_iter()
+                    .map(|i| (vertices[i] - center) / radius)
+                    .collect()
+            })
+            .collect::<Vec<Vec<_>>>();
+
+        // Order the paths as they'd be seen head on, which is about as good as any angle
+        let paths = super::order_polylines(
+            paths
+                .into_iter()
+                .map(|path| path.into_iter().map(|v| [v.x, v.y, v.z]).collect())
+                .collect(),
+        )
+        .into_iter()
+        .map(|path: Polyline<3>| path.into_iter().map(Vector3::from).collect())
+        .collect();
+
+        Self {
+            paths,
+            spin: 0.25,
+            tilt: Deg(20.0),
+        }
+    }
+}
+
+impl Sketch<3> for Wireframe {
+    fn draw(&mut self, time: f32) -> Vec<Polyline<3>> {
+        let rotation = Quaternion::from_angle_x(self.tilt)
+            * Quaternion::from_angle_y(Rad(std::f32::consts::TAU * self.spin * time));
+        self.paths
+            .iter()
+            .map(|path| {
+                path.iter()
+                    .map(|&vertex| (rotation * vertex).into())
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// Perspective projects a 3D sketch onto the screen, for scopes without a Z input.
+pub struct Perspective<S> {
+    sketch: S,
+    /// How far the eye is from the center of the scene, where the scene fits in the unit
+    /// sphere. Nearer eyes exaggerate perspective more.
+    distance: f32,
+}
+
+impl<S: Sketch<3>> Perspective<S> {
+    pub fn new(sketch: S, distance: f32) -> Self {
+        Self {
+            sketch,
+            distance: distance.max(1.0 + f32::EPSILON),
+        }
+    }
+}
+
+impl<S: Sketch<3>> Sketch<2> for Perspective<S> {
+    fn draw(&mut self, time: f32) -> Vec<Polyline> {
+        // Scaled so the nearest the scene gets to the eye is drawn at its actual size
+        let scale = self.distance - 1.0;
+        self.sketch
+            .draw(time)
+            .into_iter()
+            .map(|polyline| {
+                polyline
+                    .into_iter()
+                    .map(|[x, y, z]| {
+                        let depth = scale / (self.distance - z);
+                        [x * depth, y * depth]
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// Reads the vertices and edges of a Wavefront OBJ: every side of every face, plus any lines.
+/// Everything besides positions, faces and lines is ignored.
+pub fn load_obj(text: &str) -> anyhow::Result<Mesh> {
+    let mut vertices = Vec::new();
+    let mut edges = HashSet::new();
+    for (number, line) in text.lines().enumerate() {
+        let mut words = line.split_whitespace();
+        let context = || format!("line {}: {:?}", number + 1, line);
+        match words.next() {
+            Some("v") => {
+                let coordinates = words
+                    .take(3)
+                    .map(|word| word.parse::<f32>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .with_context(context)?;
+                match coordinates[..] {
+                    [x, y, z] => vertices.push(Vector3::new(x, y, z)),
+                    _ => bail!("vertex needs x, y and z ({})", context()),
+                }
+            }
+            Some(kind @ ("f" | "l")) => {
+                // Indices are 1-based, or negative to count back from the latest vertex, and
+                // faces may have texture and normal indices after slashes
+                let indices = words
+                    .map(|word| {
+                        let index = word.split('/').next().unwrap_or_default().parse::<i64>()?;
+                        let index = if index < 0 {
+                            vertices.len() as i64 + index
+                        } else {
+                            index - 1
+                        };
+                        match usize::try_from(index) {
+                            Ok(index) if index < vertices.len() => Ok(index),
+                            _ => bail!("vertex {} doesn't exist", word),
+                        }
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()
+                    .with_context(context)?;
+                let mut sides: Vec<_> = indices.windows(2).map(|side| (side[0], side[1])).collect();
+                if kind == "f" && indices.len() > 2 {
+                    sides.push((indices[indices.len() - 1], indices[0]));
+                }
+                // Faces that share a side share an edge
+                edges.extend(
+                    sides
+                        .into_iter()
+                        .filter(|(a, b)| a != b)
+                        .map(|(a, b)| (a.min(b), a.max(b))),
+                );
+            }
+            _ => {}
+        }
+    }
+    let mut edges: Vec<_> = edges.into_iter().collect();
+    edges.sort_unstable();
+    Ok(Mesh { vertices, edges })
+}
+
+/// Covers every edge of a graph exactly once with trails (walks that never reuse an edge), using
+/// Hierholzer's algorithm. A connected graph where no more than two vertices have an odd number
+/// of edges is covered by a single trail.
+fn trails(vertex_count: usize, edges: &[(usize, usize)]) -> Vec<Vec<usize>> {
+    let mut adjacent: Vec<Vec<(usize, usize)>> = vec![Vec::new(); vertex_count];
+    for (edge, &(a, b)) in edges.iter().enumerate() {
+        adjacent[a].push((b, edge));
+        adjacent[b].push((a, edge));
+    }
+    let mut used = vec![false; edges.len()];
+
+    // Trails have to start and end at odd vertices, so starting from them avoids splitting
+    let mut starts: Vec<usize> = (0..vertex_count).collect();
+    starts.sort_by_key(|&vertex| adjacent[vertex].len() % 2 == 0);
+
+    let mut trails = Vec::new();
+    for start in starts {
+        // Vertices on the way, with the vertex they were reached from
+        let mut stack: Vec<(usize, Option<usize>)> = vec![(start, None)];
+        let mut trail: Vec<usize> = Vec::new();
+        // The vertex the last vertex added to `trail` was reached from
+        let mut expected = None;
+        while let Some(&(vertex, from)) = stack.last() {
+            match adjacent[vertex].pop() {
+                Some((next, edge)) if !used[edge] => {
+                    used[edge] = true;
+                    stack.push((next, Some(vertex)));
+                }
+                Some(_) => {}
+                None => {
+                    stack.pop();
+                    // Vertices come off the stack as a trail, unless the walk got stuck
+                    // somewhere other than where it started. Then the trail so far ends with
+                    // the edge back to where its last vertex was reached from, and another
+                    // trail starts.
+                    if let Some(previous) = expected.filter(|&previous| previous != vertex) {
+                        trail.push(previous);
+                        trails.push(std::mem::take(&mut trail));
+                    }
+                    trail.push(vertex);
+                    expected = from;
+                }
+            }
+        }
+        if trail.len() > 1 {
+            trails.push(trail);
+        }
+    }
+    trails
+}
+
+/// Spins the wireframe of a Wavefront OBJ, either live or into a WAV, eg.
+///
+/// ```sh
+/// oscilloscope mesh teapot.obj --spin=0.1
+/// oscilloscope mesh cube.obj --view=xyz --output=cube.wav --duration=30
+/// ```
+///
+/// By default the mesh is projected onto X and Y. With `--view=xyz` its depth goes to a third Z
+/// channel instead, for the XYZ view.
+///
+/// Options:
+/// - `--output=PATH`: write a WAV instead of showing the mesh live
+/// - `--view=xy|xyz` (default xy)
+/// - `--spin=N`: revolutions per second (default 0.25)
+/// - `--tilt=DEGREES`: how far the mesh is tipped towards the viewer (default 20)
+/// - `--distance=D`: how far the eye is from the mesh, for the XY projection (default 3)
+/// - `--sample-rate=N` (default 48000)
+/// - `--dsp=CHAIN`: signal processing for the output, eg. `lowpass:8000,clip`
+/// - `--fps=N`: how many times a second the mesh is traced (default 50)
+/// - `--duration=SECONDS`: how much to write with `--output` (default 10)
+pub fn main(args: Args) -> anyhow::Result<()> {
+    let view_mode = args.parse_value("view")?.unwrap_or(ViewMode::Xy);
+    let distance = args.parse_value::<f32>("distance")?.unwrap_or(3.0);
+    let (sample_rate, fps) = super::parse_rates(&args)?;
+    let chain = args.parse_value::<Chain>("dsp")?.unwrap_or_default();
+    let duration = args.parse_value::<f32>("duration")?.unwrap_or(10.0);
+    let input = match args.positional.as_slice() {
+        [input] => input,
+        _ => bail!("mesh needs exactly one OBJ file"),
+    };
+
+    let text = std::fs::read_to_string(input).with_context(|| format!("reading {}", input))?;
+    let mesh = load_obj(&text).with_context(|| format!("parsing {}", input))?;
+    if mesh.edges.is_empty() {
+        bail!("{} has no edges to draw", input);
+    }
+    let mut wireframe = Wireframe::new(&mesh);
+    if let Some(spin) = args.parse_value("spin")? {
+        wireframe.spin = spin;
+    }
+    if let Some(tilt) = args.parse_value("tilt")? {
+        wireframe.tilt = Deg(tilt);
+    }
+    log::info!(
+        "drawing {} edges in {} paths",
+        mesh.edges.len(),
+        wireframe.paths.len()
+    );
+
+    match (view_mode, args.value("output")) {
+        (ViewMode::Xy, output) => {
+            let generator =
+                Generator::new(Perspective::new(wireframe, distance), sample_rate, fps)?;
+            match output {
+                Some(output) => super::write_wav(
+                    output,
+                    sample_rate,
+                    2,
+                    chain.apply(generator.take_duration(duration), sample_rate),
+                )?,
+                None => onepass::preview(&args, vec![Box::new(generator)])?,
+            }
+        }
+        (ViewMode::Xyz, output) => {
+            let generator = Generator::new(wireframe, sample_rate, fps)?;
+            match output {
+                Some(output) => super::write_wav(
+                    output,
+                    sample_rate,
+                    3,
+                    chain.apply(generator.take_duration(duration), sample_rate),
+                )?,
+                None => onepass::preview(&args, vec![Box::new(generator)])?,
+            }
+        }
+    }
+    if let Some(output) = args.value("output") {
+        log::info!("wrote {}s to {}", duration, output);
+    }
+    Ok(())
+}
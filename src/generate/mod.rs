@@ -0,0 +1,340 @@
+//! Generators go the other way from the renderer: they turn drawings into XY (or XYZ) sample
+//! streams that draw them on a scope.
+
+pub mod font;
+pub mod mesh;
+pub mod raster;
+pub mod svg;
+pub mod synth;
+pub mod text;
+
+use anyhow::{bail, Context};
+
+use crate::{
+    args::Args,
+    sound::{Frame, Source, WavFormat, WavRecorder},
+};
+
+/// Point is a beam position, with every axis in -1.0..=1.0. Drawings are flat unless they say
+/// otherwise, but can have a third axis for the XYZ view.
+pub type Point<const N: usize = 2> = [f32; N];
+
+/// Polyline is one continuous stroke of a drawing.
+pub type Polyline<const N: usize = 2> = Vec<Point<N>>;
+
+/// Jumps between polylines are traced this many times faster than the polylines themselves, so
+/// they take up little of each frame and leave only a faint line behind.
+const JUMP_SPEEDUP: f32 = 8.0;
+
+fn distance<const N: usize>(a: Point<N>, b: Point<N>) -> f32 {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| (a - b).powi(2))
+        .sum::<f32>()
+        .sqrt()
+}
+
+fn lerp<const N: usize>(a: Point<N>, b: Point<N>, t: f32) -> Point<N> {
+    let mut point = a;
+    for axis in 0..N {
+        point[axis] += (b[axis] - a[axis]) * t;
+    }
+    point
+}
+
+/// Reorders (and reverses, where it helps) polylines to keep the jumps between them short, by
+/// greedily visiting whichever remaining polyline has an end nearest the beam.
+pub fn order_polylines<const N: usize>(mut polylines: Vec<Polyline<N>>) -> Vec<Polyline<N>> {
+    polylines.retain(|polyline| !polyline.is_empty());
+
+    let mut ordered = Vec::with_capacity(polylines.len());
+    let mut beam = [0.0; N];
+    while !polylines.is_empty() {
+        let (i, reverse, _) = polylines
+            .iter()
+            .enumerate()
+            .flat_map(|(i, polyline)| {
+                [
+                    (i, false, distance(beam, polyline[0])),
+                    (i, true, distance(beam, polyline[polyline.len() - 1])),
+                ]
+            })
+            .fold((0, false, f32::INFINITY), |nearest, candidate| {
+                if candidate.2 < nearest.2 {
+                    candidate
+                } else {
+                    nearest
+                }
+            });
+
+        let mut polyline = polylines.swap_remove(i);
+        if reverse {
+            polyline.reverse();
+        }
+        beam = polyline[polyline.len() - 1];
+        ordered.push(polyline);
+    }
+    ordered
+}
+
+/// Scales and centers polylines to fill -extent..=extent along their longer axis, keeping their
+/// aspect ratio.
+pub fn fit(polylines: &mut [Polyline], extent: f32) {
+    let (mut min, mut max) = ([f32::INFINITY; 2], [f32::NEG_INFINITY; 2]);
+    for point in polylines.iter().flatten() {
+        for axis in 0..2 {
+            min[axis] = min[axis].min(point[axis]);
+            max[axis] = max[axis].max(point[axis]);
+        }
+    }
+    let size = (max[0] - min[0]).max(max[1] - min[1]);
+    if !size.is_finite() || size <= 0.0 {
+        return;
+    }
+
+    let center = lerp(min, max, 0.5);
+    let scale = 2.0 * extent / size;
+    for point in polylines.iter_mut().flatten() {
+        *point = [
+            (point[0] - center[0]) * scale,
+            (point[1] - center[1]) * scale,
+        ];
+    }
+}
+
+/// Traces polylines in order with `count` points spaced evenly along them, so every part of the
+/// drawing is equally bright. The trace ends by jumping back to its start, so it can be repeated
+/// frame after frame.
+pub fn trace<const N: usize>(polylines: &[Polyline<N>], count: usize) -> Vec<Point<N>> {
+    // Every point along the way, with the distance travelled to reach it
+    let mut path: Vec<(Point<N>, f32)> = Vec::new();
+    let mut travelled = 0.0;
+    for polyline in polylines {
+        for (i, &point) in polyline.iter().enumerate() {
+            if let Some(&(previous, _)) = path.last() {
+                let step = distance(previous, point);
+                travelled += if i == 0 { step / JUMP_SPEEDUP } else { step };
+            }
+            path.push((point, travelled));
+        }
+    }
+    let (start, end) = match (path.first(), path.last()) {
+        (Some(&(start, _)), Some(&(end, _))) => (start, end),
+        _ => return vec![[0.0; N]; count],
+    };
+    travelled += distance(end, start) / JUMP_SPEEDUP;
+    path.push((start, travelled));
+    if travelled <= 0.0 {
+        return vec![start; count];
+    }
+
+    let mut points = Vec::with_capacity(count);
+    let mut segment = 0;
+    for i in 0..count {
+        let target = travelled * i as f32 / count as f32;
+        while path[segment + 1].1 < target {
+            segment += 1;
+        }
+        let ((a, a_travelled), (b, b_travelled)) = (path[segment], path[segment + 1]);
+        let t = if b_travelled > a_travelled {
+            (target - a_travelled) / (b_travelled - a_travelled)
+        } else {
+            0.0
+        };
+        points.push(lerp(a, b, t));
+    }
+    points
+}
+
+/// Checks that a sketch can be traced `fps` times a second at `sample_rate`, which needs every
+/// frame to be at least a sample long on average.
+pub fn check_rates(sample_rate: u32, fps: f32) -> anyhow::Result<()> {
+    if sample_rate == 0 {
+        bail!("the sample rate must be more than 0");
+    }
+    // Written this way round so NaN fails too
+    if !(fps > 0.0 && fps <= sample_rate as f32) {
+        bail!(
+            "the frame rate must be more than 0 and at most the sample rate, not {}",
+            fps
+        );
+    }
+    Ok(())
+}
+
+/// Parses `--sample-rate` (default 48000), which has to be more than 0.
+pub fn parse_sample_rate(args: &Args) -> anyhow::Result<u32> {
+    let sample_rate = args.parse_value::<u32>("sample-rate")?.unwrap_or(48000);
+    if sample_rate == 0 {
+        bail!("--sample-rate must be more than 0");
+    }
+    Ok(sample_rate)
+}
+
+/// Parses `--sample-rate` (default 48000) and `--fps` (default 50), checking a sketch can be
+/// traced at them.
+pub fn parse_rates(args: &Args) -> anyhow::Result<(u32, f32)> {
+    let sample_rate = parse_sample_rate(args)?;
+    let fps = args.parse_value::<f32>("fps")?.unwrap_or(50.0);
+    check_rates(sample_rate, fps).with_context(|| format!("invalid value for --fps: {}", fps))?;
+    Ok((sample_rate, fps))
+}
+
+/// Returns how many samples long `frame` is, when frames are `sample_rate / fps` samples long on
+/// average. Frames round to whole samples without drifting from the frame rate.
+pub fn frame_len(frame: u64, sample_rate: u32, fps: f32) -> usize {
+    let frame_start =
+        |frame: u64| (frame as f64 * sample_rate as f64 / fps as f64).round() as usize;
+    frame_start(frame + 1) - frame_start(frame)
+}
+
+/// Splits polylines where they cross the left or right edge of the screen, dropping the parts
+/// outside it.
+pub fn clip_x(polylines: Vec<Polyline>) -> Vec<Polyline> {
+    let inside = |point: Point| (-1.0..=1.0).contains(&point[0]);
+    // Where the segment from `a` to `b` crosses whichever edge `b` is beyond (or `a`, if `b` is
+    // inside)
+    let crossing = |a: Point, b: Point| {
+        let edge = if inside(b) { a[0] } else { b[0] }.clamp(-1.0, 1.0);
+        lerp(a, b, (edge - a[0]) / (b[0] - a[0]))
+    };
+
+    let mut clipped = Vec::new();
+    for polyline in polylines {
+        let mut current: Polyline = Vec::new();
+        let mut previous: Option<Point> = None;
+        for point in polyline {
+            match (previous, inside(point)) {
+                (Some(previous), true) if !inside(previous) => {
+                    current.push(crossing(previous, point));
+                    current.push(point);
+                }
+                (Some(previous), false) if inside(previous) => {
+                    current.push(crossing(previous, point));
+                    clipped.push(std::mem::take(&mut current));
+                }
+                (_, true) => current.push(point),
+                (_, false) => {}
+            }
+            previous = Some(point);
+        }
+        if !current.is_empty() {
+            clipped.push(current);
+        }
+    }
+    clipped
+}
+
+/// Sketch is a drawing that can change over time, in N dimensions.
+pub trait Sketch<const N: usize = 2> {
+    /// Returns the polylines to trace for the frame at `time` seconds, in the order to trace
+    /// them.
+    fn draw(&mut self, time: f32) -> Vec<Polyline<N>>;
+}
+
+/// A still drawing.
+impl<const N: usize> Sketch<N> for Vec<Polyline<N>> {
+    fn draw(&mut self, _time: f32) -> Vec<Polyline<N>> {
+        self.clone()
+    }
+}
+
+/// Generator is a never-ending source that traces a sketch `fps` times a second. The third axis
+/// of 3D sketches goes to the Z channel.
+pub struct Generator<S: Sketch<N>, const N: usize = 2> {
+    sketch: S,
+    sample_rate: u32,
+    fps: f32,
+    frame: u64,
+    points: std::vec::IntoIter<Point<N>>,
+}
+
+impl<S: Sketch<N>, const N: usize> Generator<S, N> {
+    pub fn new(sketch: S, sample_rate: u32, fps: f32) -> anyhow::Result<Self> {
+        check_rates(sample_rate, fps)?;
+        Ok(Self {
+            sketch,
+            sample_rate,
+            fps,
+            frame: 0,
+            points: Vec::new().into_iter(),
+        })
+    }
+
+    /// Returns this many seconds of frames, for writing to a WAV.
+    pub fn take_duration(self, duration: f32) -> impl Iterator<Item = Frame> {
+        let samples = (duration * self.sample_rate as f32).round() as usize;
+        self.take(samples)
+    }
+}
+
+impl<S: Sketch<N>, const N: usize> Iterator for Generator<S, N> {
+    type Item = Frame;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(point) = self.points.next() {
+                let sample = |axis: f32| (axis.clamp(-1.0, 1.0) * i16::MAX as f32) as i32;
+                return Some((
+                    sample(point[0]),
+                    sample(point[1]),
+                    point.get(2).copied().map(sample),
+                ));
+            }
+            let polylines = self.sketch.draw(self.frame as f32 / self.fps);
+            let count = frame_len(self.frame, self.sample_rate, self.fps);
+            self.points = trace(&polylines, count).into_iter();
+            self.frame += 1;
+        }
+    }
+}
+
+impl<S: Sketch<N>, const N: usize> Source for Generator<S, N> {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn channels(&self) -> u16 {
+        N as u16
+    }
+}
+
+/// Writes frames to a 16-bit WAV, with Z on a third channel if `channels` is 3.
+pub fn write_wav<I>(path: &str, sample_rate: u32, channels: u16, frames: I) -> anyhow::Result<()>
+where
+    I: IntoIterator<Item = Frame>,
+{
+    let mut recorder = WavRecorder::create(path, sample_rate, channels, WavFormat::Int16)?;
+    for frame in frames {
+        recorder.write(frame)?;
+    }
+    recorder.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rates_that_cant_be_traced_at_are_rejected() {
+        assert!(check_rates(48000, 50.0).is_ok());
+        assert!(check_rates(48000, 48000.0).is_ok());
+        for fps in [0.0, -5.0, f32::NAN, f32::INFINITY, 48001.0] {
+            assert!(check_rates(48000, fps).is_err(), "{} fps", fps);
+        }
+        assert!(check_rates(0, 50.0).is_err());
+        assert!(Generator::new(Vec::<Polyline>::new(), 48000, 0.0).is_err());
+
+        let args = |args: &[&str]| Args::parse(args.iter().map(|arg| arg.to_string()));
+        assert_eq!(parse_rates(&args(&[])).unwrap(), (48000, 50.0));
+        assert!(parse_rates(&args(&["--fps=0"])).is_err());
+        assert!(parse_rates(&args(&["--sample-rate=0"])).is_err());
+    }
+
+    #[test]
+    fn frames_add_up_to_the_sample_rate() {
+        let lengths: Vec<usize> = (0..3).map(|frame| frame_len(frame, 100, 3.0)).collect();
+        assert_eq!(lengths, [33, 34, 33]);
+    }
+}
@@ -0,0 +1,227 @@
+use anyhow::{bail, Context};
+use image::{imageops::FilterType, GrayImage};
+
+use crate::{args::Args, dsp::Chain, onepass};
+
+use super::{Generator, Polyline};
+
+/// Traces the edges of a raster image into a drawing, either live or into a WAV, eg.
+///
+/// ```sh
+/// oscilloscope image logo.png --detail=300 --points=2000 --output=logo.wav
+/// ```
+///
+/// Options:
+/// - `--output=PATH`: write a WAV instead of showing the drawing live
+/// - `--detail=N`: the image is scaled to N pixels along its longer side before tracing (default
+///   200)
+/// - `--threshold=T`: how strong an edge has to be to be traced, from 0 to 1 (default 0.2)
+/// - `--min-length=N`: edges shorter than N pixels are dropped (default 4)
+/// - `--points=N`: the points in each frame, which sets the frame rate (default 1000)
+/// - `--sample-rate=N` (default 48000)
+/// - `--dsp=CHAIN`: signal processing for the output, eg. `lowpass:8000,clip`
+/// - `--duration=SECONDS`: how much to write with `--output` (default 10)
+pub fn main(args: Args) -> anyhow::Result<()> {
+    let detail = args.parse_value::<u32>("detail")?.unwrap_or(200);
+    let threshold = args.parse_value::<f32>("threshold")?.unwrap_or(0.2);
+    let min_length = args.parse_value::<usize>("min-length")?.unwrap_or(4);
+    let points = args.parse_value::<u32>("points")?.unwrap_or(1000).max(1);
+    let sample_rate = super::parse_sample_rate(&args)?;
+    let chain = args.parse_value::<Chain>("dsp")?.unwrap_or_default();
+    let duration = args.parse_value::<f32>("duration")?.unwrap_or(10.0);
+    let input = match args.positional.as_slice() {
+        [input] => input,
+        _ => bail!("image needs exactly one image file"),
+    };
+
+    let image = image::open(input)
+        .with_context(|| format!("reading {}", input))?
+        .to_luma8();
+    let (width, height) = image.dimensions();
+    let scale = detail as f32 / width.max(height) as f32;
+    let image = image::imageops::resize(
+        &image,
+        ((width as f32 * scale).round() as u32).max(1),
+        ((height as f32 * scale).round() as u32).max(1),
+        FilterType::Triangle,
+    );
+
+    let edges = edges(&image, threshold);
+    let mut polylines: Vec<Polyline> = contours(&edges, image.width(), image.height())
+        .into_iter()
+        .filter(|contour| contour.len() >= min_length)
+        .map(|contour| {
+            smooth(
+                contour
+                    .into_iter()
+                    .map(|(x, y)| [x as f32, -(y as f32)])
+                    .collect(),
+            )
+        })
+        .collect();
+    if polylines.is_empty() {
+        bail!("found no edges in {}, try a lower --threshold", input);
+    }
+    super::fit(&mut polylines, 0.9);
+    let polylines = super::order_polylines(polylines);
+    log::info!("traced {} edges", polylines.len());
+
+    // Neither is 0, so this is more than 0 and at most the sample rate
+    let fps = sample_rate as f32 / points as f32;
+    let generator = Generator::new(polylines, sample_rate, fps)?;
+    match args.value("output") {
+        Some(output) => {
+            super::write_wav(
+                output,
+                sample_rate,
+                2,
+                chain.apply(generator.take_duration(duration), sample_rate),
+            )?;
+            log::info!("wrote {}s to {}", duration, output);
+        }
+        None => onepass::preview(&args, vec![Box::new(generator)])?,
+    }
+    Ok(())
+}
+
+/// Finds edges one pixel wide: pixels whose Sobel gradient is at least `threshold` (relative to
+/// the strongest in the image), and stronger than their neighbours across the edge.
+fn edges(image: &GrayImage, threshold: f32) -> Vec<bool> {
+    let (width, height) = (image.width() as i64, image.height() as i64);
+    let pixel = |x: i64, y: i64| {
+        image.get_pixel(x.clamp(0, width - 1) as u32, y.clamp(0, height - 1) as u32)[0] as f32
+    };
+
+    let mut gradients = Vec::with_capacity((width * height) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let gx = pixel(x + 1, y - 1) + 2.0 * pixel(x + 1, y) + pixel(x + 1, y + 1)
+                - pixel(x - 1, y - 1)
+                - 2.0 * pixel(x - 1, y)
+                - pixel(x - 1, y + 1);
+            let gy = pixel(x - 1, y + 1) + 2.0 * pixel(x, y + 1) + pixel(x + 1, y + 1)
+                - pixel(x - 1, y - 1)
+                - 2.0 * pixel(x, y - 1)
+                - pixel(x + 1, y - 1);
+            gradients.push((gx, gy));
+        }
+    }
+
+    let magnitude = |x: i64, y: i64| {
+        if x < 0 || y < 0 || x >= width || y >= height {
+            return 0.0;
+        }
+        let (gx, gy): (f32, f32) = gradients[(y * width + x) as usize];
+        gx.hypot(gy)
+    };
+    let strongest = gradients
+        .iter()
+        .map(|(gx, gy)| gx.hypot(*gy))
+        .fold(0.0, f32::max);
+    if strongest <= 0.0 {
+        return vec![false; gradients.len()];
+    }
+
+    let mut edges = Vec::with_capacity(gradients.len());
+    for y in 0..height {
+        for x in 0..width {
+            let (gx, gy) = gradients[(y * width + x) as usize];
+            let here = gx.hypot(gy);
+            // Step to the neighbours across the edge, with the gradient rounded to 45 degrees
+            let angle = gy.atan2(gx).to_degrees().rem_euclid(180.0);
+            let (dx, dy) = match angle {
+                a if !(22.5..157.5).contains(&a) => (1, 0),
+                a if a < 67.5 => (1, 1),
+                a if a < 112.5 => (0, 1),
+                _ => (-1, 1),
+            };
+            edges.push(
+                here >= threshold * strongest
+                    && here >= magnitude(x + dx, y + dy)
+                    && here > magnitude(x - dx, y - dy),
+            );
+        }
+    }
+    edges
+}
+
+/// Follows edge pixels into chains of neighbouring pixels. Chains start from loose ends where
+/// there are any, so open edges are traced end to end rather than from the middle.
+fn contours(edges: &[bool], width: u32, height: u32) -> Vec<Vec<(u32, u32)>> {
+    let (width, height) = (width as i64, height as i64);
+    let mut remaining = edges.to_vec();
+    // Straight neighbours come first, so chains prefer them over diagonals
+    const NEIGHBOURS: [(i64, i64); 8] = [
+        (1, 0),
+        (0, 1),
+        (-1, 0),
+        (0, -1),
+        (1, 1),
+        (-1, 1),
+        (-1, -1),
+        (1, -1),
+    ];
+    let index = |x: i64, y: i64| {
+        if x < 0 || y < 0 || x >= width || y >= height {
+            None
+        } else {
+            Some((y * width + x) as usize)
+        }
+    };
+    let neighbours = |remaining: &[bool], x: i64, y: i64| {
+        NEIGHBOURS
+            .iter()
+            .filter(|(dx, dy)| index(x + dx, y + dy).is_some_and(|i| remaining[i]))
+            .count()
+    };
+
+    let mut starts: Vec<(i64, i64)> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .filter(|&(x, y)| edges[(y * width + x) as usize])
+        .collect();
+    starts.sort_by_key(|&(x, y)| neighbours(edges, x, y) != 1);
+
+    let mut contours = Vec::new();
+    for (x, y) in starts {
+        let i = (y * width + x) as usize;
+        if !remaining[i] {
+            continue;
+        }
+        remaining[i] = false;
+        let mut contour = vec![(x as u32, y as u32)];
+        let (mut x, mut y) = (x, y);
+        while let Some(&(dx, dy)) = NEIGHBOURS
+            .iter()
+            .find(|(dx, dy)| index(x + dx, y + dy).is_some_and(|i| remaining[i]))
+        {
+            x += dx;
+            y += dy;
+            remaining[(y * width + x) as usize] = false;
+            contour.push((x as u32, y as u32));
+        }
+        // Close loops that came back around to where they started
+        let (first, last) = (contour[0], contour[contour.len() - 1]);
+        if contour.len() > 2 && first.0.abs_diff(last.0) <= 1 && first.1.abs_diff(last.1) <= 1 {
+            contour.push(first);
+        }
+        contours.push(contour);
+    }
+    contours
+}
+
+/// Averages each point of a chain of pixels with its neighbours along the chain, to round off
+/// the staircase of pixel steps along slopes and curves. The ends stay put.
+fn smooth(polyline: Polyline) -> Polyline {
+    const RADIUS: usize = 2;
+    let last = polyline.len().saturating_sub(1);
+    (0..polyline.len())
+        .map(|i| {
+            let radius = RADIUS.min(i).min(last - i);
+            let window = &polyline[i - radius..=i + radius];
+            let sum = window.iter().fold([0.0, 0.0], |sum, point| {
+                [sum[0] + point[0], sum[1] + point[1]]
+            });
+            [sum[0] / window.len() as f32, sum[1] / window.len() as f32]
+        })
+        .collect()
+}
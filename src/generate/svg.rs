@@ -0,0 +1,483 @@
+use std::str::FromStr;
+
+use anyhow::{bail, Context};
+use cgmath::{Matrix3, SquareMatrix, Vector3};
+use svgtypes::{PathParser, PathSegment, PointsParser};
+
+use crate::{args::Args, dsp::Chain};
+
+use super::{Generator, Polyline};
+
+/// Curves and arcs are flattened into this many line segments. They're resampled evenly
+/// afterwards, so this only needs to be fine enough to look smooth.
+const CURVE_SEGMENTS: usize = 32;
+
+type SvgPoint = [f64; 2];
+
+/// Converts an SVG into a stereo WAV that draws it, eg.
+///
+/// ```sh
+/// oscilloscope svg logo.svg --output=logo.wav --duration=10 && oscilloscope logo.wav
+/// ```
+///
+/// Paths, lines, polylines, polygons, rectangles, circles and ellipses are drawn, along with the
+/// transforms on them and their groups. Fills, strokes and styles are ignored.
+///
+/// Options:
+/// - `--output=PATH` (required)
+/// - `--sample-rate=N` (default 48000)
+/// - `--dsp=CHAIN`: signal processing for the output, eg. `lowpass:8000,clip`
+/// - `--fps=N`: how many times a second the whole drawing is traced (default 50)
+/// - `--duration=SECONDS` (default 10)
+pub fn main(args: Args) -> anyhow::Result<()> {
+    let output = args.value("output").context("svg needs --output=PATH")?;
+    let (sample_rate, fps) = super::parse_rates(&args)?;
+    let chain = args.parse_value::<Chain>("dsp")?.unwrap_or_default();
+    let duration = args.parse_value::<f32>("duration")?.unwrap_or(10.0);
+    let input = match args.positional.as_slice() {
+        [input] => input,
+        _ => bail!("svg needs exactly one SVG file"),
+    };
+
+    let text = std::fs::read_to_string(input).with_context(|| format!("reading {}", input))?;
+    let mut polylines = polylines(&text).with_context(|| format!("parsing {}", input))?;
+    if polylines.is_empty() {
+        bail!("{} has nothing to draw", input);
+    }
+    super::fit(&mut polylines, 0.9);
+    let polylines = super::order_polylines(polylines);
+
+    let generator = Generator::new(polylines, sample_rate, fps)?;
+    super::write_wav(
+        output,
+        sample_rate,
+        2,
+        chain.apply(generator.take_duration(duration), sample_rate),
+    )?;
+
+    log::info!("wrote {}s to {}", duration, output);
+    Ok(())
+}
+
+/// Flattens every drawable element of an SVG document into polylines, with Y pointing up.
+pub fn polylines(text: &str) -> anyhow::Result<Vec<Polyline>> {
+    let document = roxmltree::Document::parse(text)?;
+    let mut polylines = Vec::new();
+    collect(document.root_element(), Matrix3::identity(), &mut polylines)?;
+    Ok(polylines
+        .into_iter()
+        .map(|polyline| {
+            polyline
+                .into_iter()
+                .map(|[x, y]| [x as f32, -y as f32])
+                .collect()
+        })
+        .collect())
+}
+
+fn collect(
+    node: roxmltree::Node,
+    parent_transform: Matrix3<f64>,
+    polylines: &mut Vec<Vec<SvgPoint>>,
+) -> anyhow::Result<()> {
+    let transform = match node.attribute("transform") {
+        Some(transform) => {
+            let svgtypes::Transform { a, b, c, d, e, f } = svgtypes::Transform::from_str(transform)
+                .map_err(|e| anyhow::anyhow!("invalid transform {:?}: {}", transform, e))?;
+            parent_transform * Matrix3::new(a, b, 0.0, c, d, 0.0, e, f, 1.0)
+        }
+        None => parent_transform,
+    };
+
+    let number = |name| -> anyhow::Result<f64> {
+        match node.attribute(name) {
+            Some(value) => svgtypes::Length::from_str(value)
+                .map(|length| length.number)
+                .map_err(|e| anyhow::anyhow!("invalid {} {:?}: {}", name, value, e)),
+            None => Ok(0.0),
+        }
+    };
+
+    let element_polylines = match node.tag_name().name() {
+        // Nothing inside these is drawn directly
+        "defs" | "clipPath" | "mask" | "marker" | "pattern" | "symbol" | "style" | "title"
+        | "desc" | "metadata" => return Ok(()),
+        "path" => path(node.attribute("d").unwrap_or_default())?,
+        "line" => vec![vec![
+            [number("x1")?, number("y1")?],
+            [number("x2")?, number("y2")?],
+        ]],
+        "polyline" | "polygon" => {
+            let mut polyline: Vec<SvgPoint> =
+                PointsParser::from(node.attribute("points").unwrap_or_default())
+                    .map(|(x, y)| [x, y])
+                    .collect();
+            if node.has_tag_name("polygon") {
+                if let Some(&first) = polyline.first() {
+                    polyline.push(first);
+                }
+            }
+            vec![polyline]
+        }
+        "rect" => {
+            let (x, y) = (number("x")?, number("y")?);
+            let (width, height) = (number("width")?, number("height")?);
+            vec![vec![
+                [x, y],
+                [x + width, y],
+                [x + width, y + height],
+                [x, y + height],
+                [x, y],
+            ]]
+        }
+        "circle" => {
+            let r = number("r")?;
+            vec![ellipse([number("cx")?, number("cy")?], r, r)]
+        }
+        "ellipse" => vec![ellipse(
+            [number("cx")?, number("cy")?],
+            number("rx")?,
+            number("ry")?,
+        )],
+        _ => vec![],
+    };
+
+    polylines.extend(
+        element_polylines
+            .into_iter()
+            .filter(|polyline| polyline.len() > 1)
+            .map(|polyline| {
+                polyline
+                    .into_iter()
+                    .map(|[x, y]| {
+                        let point = transform * Vector3::new(x, y, 1.0);
+                        [point.x, point.y]
+                    })
+                    .collect()
+            }),
+    );
+
+    for child in node.children().filter(|child| child.is_element()) {
+        collect(child, transform, polylines)?;
+    }
+    Ok(())
+}
+
+fn ellipse(center: SvgPoint, rx: f64, ry: f64) -> Vec<SvgPoint> {
+    (0..=CURVE_SEGMENTS)
+        .map(|i| {
+            let angle = std::f64::consts::TAU * i as f64 / CURVE_SEGMENTS as f64;
+            [center[0] + rx * angle.cos(), center[1] + ry * angle.sin()]
+        })
+        .collect()
+}
+
+/// Flattens SVG path data into one polyline per subpath.
+fn path(data: &str) -> anyhow::Result<Vec<Vec<SvgPoint>>> {
+    let mut polylines = Vec::new();
+    let mut polyline: Vec<SvgPoint> = Vec::new();
+    let mut current = [0.0, 0.0];
+    let mut subpath_start = [0.0, 0.0];
+    // The last control points, which smooth curves reflect
+    let mut last_cubic: Option<SvgPoint> = None;
+    let mut last_quadratic: Option<SvgPoint> = None;
+
+    for segment in PathParser::from(data) {
+        let segment = segment.map_err(|e| anyhow::anyhow!("invalid path data: {}", e))?;
+        let absolute = move |abs: bool, x: f64, y: f64| {
+            if abs {
+                [x, y]
+            } else {
+                [current[0] + x, current[1] + y]
+            }
+        };
+        let reflect = move |control: Option<SvgPoint>| match control {
+            Some(control) => [2.0 * current[0] - control[0], 2.0 * current[1] - control[1]],
+            None => current,
+        };
+        if polyline.is_empty() {
+            polyline.push(current);
+        }
+
+        let (mut cubic, mut quadratic) = (None, None);
+        match segment {
+            PathSegment::MoveTo { abs, x, y } => {
+                polylines.push(std::mem::take(&mut polyline));
+                current = absolute(abs, x, y);
+                subpath_start = current;
+                polyline.push(current);
+            }
+            PathSegment::LineTo { abs, x, y } => {
+                current = absolute(abs, x, y);
+                polyline.push(current);
+            }
+            PathSegment::HorizontalLineTo { abs, x } => {
+                current[0] = if abs { x } else { current[0] + x };
+                polyline.push(current);
+            }
+            PathSegment::VerticalLineTo { abs, y } => {
+                current[1] = if abs { y } else { current[1] + y };
+                polyline.push(current);
+            }
+            PathSegment::CurveTo {
+                abs,
+                x1,
+                y1,
+                x2,
+                y2,
+                x,
+                y,
+            } => {
+                let (c1, c2) = (absolute(abs, x1, y1), absolute(abs, x2, y2));
+                let end = absolute(abs, x, y);
+                polyline.extend(cubic_points(current, c1, c2, end));
+                (current, cubic) = (end, Some(c2));
+            }
+            PathSegment::SmoothCurveTo { abs, x2, y2, x, y } => {
+                let (c1, c2) = (reflect(last_cubic), absolute(abs, x2, y2));
+                let end = absolute(abs, x, y);
+                polyline.extend(cubic_points(current, c1, c2, end));
+                (current, cubic) = (end, Some(c2));
+            }
+            PathSegment::Quadratic { abs, x1, y1, x, y } => {
+                let (control, end) = (absolute(abs, x1, y1), absolute(abs, x, y));
+                polyline.extend(quadratic_points(current, control, end));
+                (current, quadratic) = (end, Some(control));
+            }
+            PathSegment::SmoothQuadratic { abs, x, y } => {
+                let (control, end) = (reflect(last_quadratic), absolute(abs, x, y));
+                polyline.extend(quadratic_points(current, control, end));
+                (current, quadratic) = (end, Some(control));
+            }
+            PathSegment::EllipticalArc {
+                abs,
+                rx,
+                ry,
+                x_axis_rotation,
+                large_arc,
+                sweep,
+                x,
+                y,
+            } => {
+                let end = absolute(abs, x, y);
+                polyline.extend(arc_points(
+                    current,
+                    rx,
+                    ry,
+                    x_axis_rotation,
+                    large_arc,
+                    sweep,
+                    end,
+                ));
+                current = end;
+            }
+            PathSegment::ClosePath { .. } => {
+                current = subpath_start;
+                polyline.push(current);
+                polylines.push(std::mem::take(&mut polyline));
+            }
+        }
+        last_cubic = cubic;
+        last_quadratic = quadratic;
+    }
+    polylines.push(polyline);
+    Ok(polylines)
+}
+
+fn cubic_points(
+    start: SvgPoint,
+    c1: SvgPoint,
+    c2: SvgPoint,
+    end: SvgPoint,
+) -> impl Iterator<Item = SvgPoint> {
+    (1..=CURVE_SEGMENTS).map(move |i| {
+        let t = i as f64 / CURVE_SEGMENTS as f64;
+        let u = 1.0 - t;
+        let weights = [u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t];
+        let axis = |a: usize| {
+            weights[0] * start[a] + weights[1] * c1[a] + weights[2] * c2[a] + weights[3] * end[a]
+        };
+        [axis(0), axis(1)]
+    })
+}
+
+fn quadratic_points(
+    start: SvgPoint,
+    control: SvgPoint,
+    end: SvgPoint,
+) -> impl Iterator<Item = SvgPoint> {
+    (1..=CURVE_SEGMENTS).map(move |i| {
+        let t = i as f64 / CURVE_SEGMENTS as f64;
+        let u = 1.0 - t;
+        let axis = |a: usize| u * u * start[a] + 2.0 * u * t * control[a] + t * t * end[a];
+        [axis(0), axis(1)]
+    })
+}
+
+/// Flattens an elliptical arc, after converting it from SVG's endpoint form to a center and
+/// angles, following the SVG spec's implementation notes (F.6.5).
+fn arc_points(
+    start: SvgPoint,
+    rx: f64,
+    ry: f64,
+    x_axis_rotation: f64,
+    large_arc: bool,
+    sweep: bool,
+    end: SvgPoint,
+) -> Vec<SvgPoint> {
+    let (mut rx, mut ry) = (rx.abs(), ry.abs());
+    if rx == 0.0 || ry == 0.0 || start == end {
+        return vec![end];
+    }
+    let (sin, cos) = x_axis_rotation.to_radians().sin_cos();
+
+    // The start point, in a frame centered between the endpoints and aligned with the ellipse
+    let dx = (start[0] - end[0]) / 2.0;
+    let dy = (start[1] - end[1]) / 2.0;
+    let x1 = cos * dx + sin * dy;
+    let y1 = -sin * dx + cos * dy;
+
+    // Radii too small to span the endpoints are scaled up until they just do
+    let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+
+    let numerator = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+    let denominator = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+    let sign = if large_arc == sweep { -1.0 } else { 1.0 };
+    let coefficient = sign * (numerator / denominator).max(0.0).sqrt();
+    let cx1 = coefficient * rx * y1 / ry;
+    let cy1 = -coefficient * ry * x1 / rx;
+    let center = [
+        cos * cx1 - sin * cy1 + (start[0] + end[0]) / 2.0,
+        sin * cx1 + cos * cy1 + (start[1] + end[1]) / 2.0,
+    ];
+
+    let angle = |ux: f64, uy: f64, vx: f64, vy: f64| (ux * vy - uy * vx).atan2(ux * vx + uy * vy);
+    let start_angle = angle(1.0, 0.0, (x1 - cx1) / rx, (y1 - cy1) / ry);
+    let mut sweep_angle = angle(
+        (x1 - cx1) / rx,
+        (y1 - cy1) / ry,
+        (-x1 - cx1) / rx,
+        (-y1 - cy1) / ry,
+    );
+    if !sweep && sweep_angle > 0.0 {
+        sweep_angle -= std::f64::consts::TAU;
+    } else if sweep && sweep_angle < 0.0 {
+        sweep_angle += std::f64::consts::TAU;
+    }
+
+    (1..=CURVE_SEGMENTS)
+        .map(|i| {
+            let theta = start_angle + sweep_angle * i as f64 / CURVE_SEGMENTS as f64;
+            let (ellipse_x, ellipse_y) = (rx * theta.cos(), ry * theta.sin());
+            [
+                center[0] + cos * ellipse_x - sin * ellipse_y,
+                center[1] + sin * ellipse_x + cos * ellipse_y,
+            ]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn near(a: SvgPoint, b: SvgPoint) -> bool {
+        (a[0] - b[0]).abs() < 1e-9 && (a[1] - b[1]).abs() < 1e-9
+    }
+
+    fn distance(a: SvgPoint, b: SvgPoint) -> f64 {
+        (a[0] - b[0]).hypot(a[1] - b[1])
+    }
+
+    #[test]
+    fn lines_and_closed_subpaths() {
+        let polylines = polylines(
+            r#"<svg xmlns="http://www.w3.org/2000/svg">
+                <path d="M 0 0 L 10 0 L 10 10 Z m 1 1 l 2 0 h 1 v 1"/>
+            </svg>"#,
+        )
+        .unwrap();
+        // Y points up once it's out of the SVG
+        assert_eq!(
+            polylines,
+            vec![
+                vec![[0.0, 0.0], [10.0, 0.0], [10.0, -10.0], [0.0, 0.0]],
+                vec![[1.0, -1.0], [3.0, -1.0], [4.0, -1.0], [4.0, -2.0]],
+            ]
+        );
+    }
+
+    #[test]
+    fn transforms_apply_to_children() {
+        let polylines = polylines(
+            r#"<svg xmlns="http://www.w3.org/2000/svg">
+                <g transform="translate(5 0)">
+                    <line x1="0" y1="0" x2="1" y2="0" transform="scale(2)"/>
+                </g>
+            </svg>"#,
+        )
+        .unwrap();
+        assert_eq!(polylines, vec![vec![[5.0, 0.0], [7.0, 0.0]]]);
+    }
+
+    #[test]
+    fn curves_end_where_they_should() {
+        let polylines = path("M 0 0 C 0 10 10 10 10 0 Q 15 -10 20 0").unwrap();
+        let curve = polylines
+            .iter()
+            .find(|polyline| polyline.len() > 1)
+            .unwrap();
+        assert_eq!(curve.len(), 1 + 2 * CURVE_SEGMENTS);
+        // Halfway along the cubic, and the end of each
+        assert!(near(curve[CURVE_SEGMENTS / 2], [5.0, 7.5]));
+        assert!(near(curve[CURVE_SEGMENTS], [10.0, 0.0]));
+        assert!(near(curve[2 * CURVE_SEGMENTS], [20.0, 0.0]));
+    }
+
+    #[test]
+    fn arcs_stay_on_their_ellipse() {
+        let polylines = path("M 10 0 A 10 10 0 0 1 -10 0").unwrap();
+        let arc = polylines
+            .iter()
+            .find(|polyline| polyline.len() > 1)
+            .unwrap();
+        assert_eq!(arc.len(), 1 + CURVE_SEGMENTS);
+        assert!(arc
+            .iter()
+            .all(|&point| (distance(point, [0.0, 0.0]) - 10.0).abs() < 1e-9));
+        assert!(near(arc[CURVE_SEGMENTS], [-10.0, 0.0]));
+        // Sweeping clockwise on screen, with Y down, goes through the bottom
+        assert!(near(arc[CURVE_SEGMENTS / 2], [0.0, 10.0]));
+
+        // Without the sweep flag it goes the other way round
+        let polylines = path("M 10 0 A 10 10 0 0 0 -10 0").unwrap();
+        let arc = polylines
+            .iter()
+            .find(|polyline| polyline.len() > 1)
+            .unwrap();
+        assert!(near(arc[CURVE_SEGMENTS / 2], [0.0, -10.0]));
+    }
+
+    #[test]
+    fn arcs_too_small_to_reach_are_scaled_up() {
+        let polylines = path("M 0 0 A 1 1 0 0 1 10 0").unwrap();
+        let arc = polylines
+            .iter()
+            .find(|polyline| polyline.len() > 1)
+            .unwrap();
+        assert!(arc
+            .iter()
+            .all(|&point| (distance(point, [5.0, 0.0]) - 5.0).abs() < 1e-9));
+        assert!(near(arc[CURVE_SEGMENTS], [10.0, 0.0]));
+    }
+
+    #[test]
+    fn bad_path_data_is_an_error() {
+        assert!(path("M 0 0 L 10").is_err());
+        assert!(polylines("<svg").is_err());
+    }
+}
@@ -0,0 +1,347 @@
+use std::{f32::consts::TAU, str::FromStr};
+
+use anyhow::{bail, Context};
+use midly::{MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+
+use crate::{
+    args::Args,
+    dsp::Chain,
+    onepass,
+    sound::{Frame, Source},
+};
+
+use super::Point;
+
+/// Shape is what a voice draws once per cycle of its note.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Shape {
+    #[default]
+    Circle,
+    Triangle,
+    Square,
+    Star,
+    /// A 1:2 Lissajous figure, which sounds an octave up as well as the note.
+    Lissajous,
+}
+
+impl Shape {
+    /// The shape that morphing moves towards.
+    pub fn next(self) -> Self {
+        match self {
+            Shape::Circle => Shape::Triangle,
+            Shape::Triangle => Shape::Square,
+            Shape::Square => Shape::Star,
+            Shape::Star => Shape::Lissajous,
+            Shape::Lissajous => Shape::Circle,
+        }
+    }
+
+    /// Returns the point `phase` (from 0 to 1) of the way around the shape, which fits in the
+    /// unit circle.
+    pub fn point(self, phase: f32) -> Point {
+        let angle = TAU * phase;
+        match self {
+            Shape::Circle => [angle.cos(), angle.sin()],
+            Shape::Triangle => polygon_point(3, 1, phase),
+            Shape::Square => polygon_point(4, 1, phase),
+            Shape::Star => polygon_point(5, 2, phase),
+            Shape::Lissajous => [angle.sin(), (2.0 * angle).sin()],
+        }
+    }
+}
+
+impl FromStr for Shape {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "circle" => Ok(Shape::Circle),
+            "triangle" => Ok(Shape::Triangle),
+            "square" => Ok(Shape::Square),
+            "star" => Ok(Shape::Star),
+            "lissajous" => Ok(Shape::Lissajous),
+            _ => bail!(
+                "unknown shape {:?}, expected circle, triangle, square, star or lissajous",
+                s
+            ),
+        }
+    }
+}
+
+/// Walks around a regular polygon with `corners` corners inscribed in the unit circle, visiting
+/// every `step`th corner, so step 2 of 5 is a pentagram.
+fn polygon_point(corners: u32, step: u32, phase: f32) -> Point {
+    let corner = |i: u32| {
+        let angle = TAU * (i * step % corners) as f32 / corners as f32 + TAU / 4.0;
+        [angle.cos(), angle.sin()]
+    };
+    let along = phase.rem_euclid(1.0) * corners as f32;
+    let i = along as u32;
+    super::lerp(corner(i), corner(i + 1), along.fract())
+}
+
+/// Event is a MIDI message the synth responds to, at a time in seconds.
+#[derive(Copy, Clone, Debug)]
+enum Event {
+    NoteOn { key: u8, velocity: u8 },
+    NoteOff { key: u8 },
+    Controller { controller: u8, value: u8 },
+}
+
+/// Reads the note and controller events of every track of a Standard MIDI File, in order, with
+/// their times in seconds.
+fn load_midi(bytes: &[u8]) -> anyhow::Result<Vec<(f64, Event)>> {
+    let smf = Smf::parse(bytes)?;
+
+    // Every track's events against ticks from the start, so tempo changes in one track (usually
+    // the first) can apply to them all
+    let mut events = Vec::new();
+    for track in &smf.tracks {
+        let mut ticks = 0u64;
+        for event in track {
+            ticks += event.delta.as_int() as u64;
+            events.push((ticks, event.kind));
+        }
+    }
+    events.sort_by_key(|(ticks, _)| *ticks);
+
+    // Until told otherwise, MIDI files play at 120 beats per minute
+    let mut seconds_per_beat = 0.5;
+    let seconds_per_tick = |seconds_per_beat: f64| match smf.header.timing {
+        Timing::Metrical(ticks_per_beat) => seconds_per_beat / ticks_per_beat.as_int() as f64,
+        Timing::Timecode(fps, ticks_per_frame) => {
+            1.0 / (fps.as_f32() as f64 * ticks_per_frame as f64)
+        }
+    };
+
+    let mut timed = Vec::new();
+    let (mut last_ticks, mut seconds) = (0, 0.0);
+    for (ticks, kind) in events {
+        seconds += (ticks - last_ticks) as f64 * seconds_per_tick(seconds_per_beat);
+        last_ticks = ticks;
+        let event = match kind {
+            TrackEventKind::Meta(MetaMessage::Tempo(microseconds)) => {
+                seconds_per_beat = microseconds.as_int() as f64 / 1e6;
+                continue;
+            }
+            TrackEventKind::Midi { message, .. } => match message {
+                // Note on with no velocity is how many files say note off
+                MidiMessage::NoteOn { key, vel } if vel.as_int() == 0 => {
+                    Event::NoteOff { key: key.as_int() }
+                }
+                MidiMessage::NoteOn { key, vel } => Event::NoteOn {
+                    key: key.as_int(),
+                    velocity: vel.as_int(),
+                },
+                MidiMessage::NoteOff { key, .. } => Event::NoteOff { key: key.as_int() },
+                MidiMessage::Controller { controller, value } => Event::Controller {
+                    controller: controller.as_int(),
+                    value: value.as_int(),
+                },
+                _ => continue,
+            },
+            _ => continue,
+        };
+        timed.push((seconds, event));
+    }
+    Ok(timed)
+}
+
+/// Voice is one sounding note.
+struct Voice {
+    key: u8,
+    /// How loud (and so how big) the note is, from velocity.
+    amplitude: f32,
+    frequency: f32,
+    phase: f32,
+    /// The envelope, which rises from 0 to 1 when the note starts and falls back when it ends.
+    level: f32,
+    released: bool,
+}
+
+/// Seconds for a voice to fade in and out, short enough to sound immediate while avoiding clicks.
+const ATTACK: f32 = 0.005;
+const RELEASE: f32 = 0.05;
+
+/// Synth plays a MIDI file by drawing a shape for each note: pitch sets how many times a second
+/// the shape is drawn, so it sounds at that pitch, and velocity sets its size. Controllers turn
+/// and morph the shapes.
+pub struct Synth {
+    events: Vec<(f64, Event)>,
+    next_event: usize,
+    sample_rate: u32,
+    sample: u64,
+    voices: Vec<Voice>,
+    pub shape: Shape,
+    /// The controller that morphs shapes towards `shape.next()`.
+    pub morph_controller: u8,
+    /// The controller that turns shapes, through a full turn.
+    pub rotation_controller: u8,
+    morph: f32,
+    rotation: f32,
+}
+
+impl Synth {
+    pub fn new(midi: &[u8], sample_rate: u32) -> anyhow::Result<Self> {
+        if sample_rate == 0 {
+            bail!("the sample rate must be more than 0");
+        }
+        Ok(Self {
+            events: load_midi(midi)?,
+            next_event: 0,
+            sample_rate,
+            sample: 0,
+            voices: Vec::new(),
+            shape: Shape::default(),
+            morph_controller: 1,
+            rotation_controller: 74,
+            morph: 0.0,
+            rotation: 0.0,
+        })
+    }
+
+    /// How long the file plays for, including the last note fading out.
+    pub fn duration(&self) -> f32 {
+        self.events
+            .last()
+            .map_or(0.0, |(seconds, _)| *seconds as f32)
+            + RELEASE
+    }
+
+    fn handle(&mut self, event: Event) {
+        match event {
+            Event::NoteOn { key, velocity } => {
+                self.voices.retain(|voice| voice.key != key);
+                self.voices.push(Voice {
+                    key,
+                    amplitude: velocity as f32 / 127.0,
+                    frequency: 440.0 * 2f32.powf((key as f32 - 69.0) / 12.0),
+                    phase: 0.0,
+                    level: 0.0,
+                    released: false,
+                });
+            }
+            Event::NoteOff { key } => {
+                for voice in self.voices.iter_mut().filter(|voice| voice.key == key) {
+                    voice.released = true;
+                }
+            }
+            Event::Controller { controller, value } => {
+                let value = value as f32 / 127.0;
+                if controller == self.morph_controller {
+                    self.morph = value;
+                }
+                if controller == self.rotation_controller {
+                    self.rotation = TAU * value;
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for Synth {
+    type Item = Frame;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let time = self.sample as f64 / self.sample_rate as f64;
+        while let Some(&(seconds, event)) = self.events.get(self.next_event) {
+            if seconds > time {
+                break;
+            }
+            self.handle(event);
+            self.next_event += 1;
+        }
+        // Notes still held at the end of the file fade out, rather than droning forever
+        if self.next_event == self.events.len() {
+            if self.voices.is_empty() {
+                return None;
+            }
+            for voice in &mut self.voices {
+                voice.released = true;
+            }
+        }
+        self.sample += 1;
+
+        let dt = 1.0 / self.sample_rate as f32;
+        let (sin, cos) = self.rotation.sin_cos();
+        let mut sum = [0.0, 0.0];
+        for voice in &mut self.voices {
+            voice.level = if voice.released {
+                voice.level - dt / RELEASE
+            } else {
+                (voice.level + dt / ATTACK).min(1.0)
+            };
+            let [x, y] = super::lerp(
+                self.shape.point(voice.phase),
+                self.shape.next().point(voice.phase),
+                self.morph,
+            );
+            let scale = 0.9 * voice.amplitude * voice.level.max(0.0);
+            sum[0] += (x * cos - y * sin) * scale;
+            sum[1] += (x * sin + y * cos) * scale;
+            voice.phase = (voice.phase + voice.frequency * dt).fract();
+        }
+        // Chords share the screen rather than overflowing it
+        let count = self.voices.len().max(1) as f32;
+        self.voices.retain(|voice| voice.level > 0.0);
+
+        let sample = |axis: f32| ((axis / count).clamp(-1.0, 1.0) * i16::MAX as f32) as i32;
+        Some((sample(sum[0]), sample(sum[1]), None))
+    }
+}
+
+impl Source for Synth {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn channels(&self) -> u16 {
+        2
+    }
+}
+
+/// Plays a Standard MIDI File as shapes, live or into a WAV, eg.
+///
+/// ```sh
+/// oscilloscope midi song.mid --shape=star
+/// oscilloscope midi song.mid --output=song.wav
+/// ```
+///
+/// Options:
+/// - `--output=PATH`: write a WAV instead of playing live
+/// - `--shape=circle|triangle|square|star|lissajous` (default circle)
+/// - `--morph-cc=N`: the controller that morphs towards the next shape (default 1, mod wheel)
+/// - `--rotation-cc=N`: the controller that turns the shapes (default 74)
+/// - `--sample-rate=N` (default 48000)
+/// - `--dsp=CHAIN`: signal processing for the output, eg. `lowpass:8000,clip`
+pub fn main(args: Args) -> anyhow::Result<()> {
+    let sample_rate = super::parse_sample_rate(&args)?;
+    let chain = args.parse_value::<Chain>("dsp")?.unwrap_or_default();
+    let input = match args.positional.as_slice() {
+        [input] => input,
+        _ => bail!("midi needs exactly one MIDI file"),
+    };
+
+    let bytes = std::fs::read(input).with_context(|| format!("reading {}", input))?;
+    let mut synth =
+        Synth::new(&bytes, sample_rate).with_context(|| format!("parsing {}", input))?;
+    if let Some(shape) = args.parse_value("shape")? {
+        synth.shape = shape;
+    }
+    if let Some(controller) = args.parse_value("morph-cc")? {
+        synth.morph_controller = controller;
+    }
+    if let Some(controller) = args.parse_value("rotation-cc")? {
+        synth.rotation_controller = controller;
+    }
+
+    match args.value("output") {
+        Some(output) => {
+            let duration = synth.duration();
+            super::write_wav(output, sample_rate, 2, chain.apply(synth, sample_rate))?;
+            log::info!("wrote {:.1}s to {}", duration, output);
+        }
+        None => onepass::preview(&args, vec![Box::new(synth)])?,
+    }
+    Ok(())
+}
@@ -0,0 +1,163 @@
+use std::str::FromStr;
+
+use anyhow::bail;
+
+use crate::{args::Args, dsp::Chain, onepass};
+
+use super::{font, Generator, Polyline, Sketch};
+
+/// Align is where lines of text sit horizontally.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    #[default]
+    Center,
+    Right,
+}
+
+impl FromStr for Align {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "left" => Ok(Align::Left),
+            "center" => Ok(Align::Center),
+            "right" => Ok(Align::Right),
+            _ => bail!("unknown alignment {:?}, expected left, center or right", s),
+        }
+    }
+}
+
+/// Text draws lines of text in the single-stroke font, optionally scrolling across the screen
+/// like a marquee.
+pub struct Text {
+    lines: Vec<String>,
+    /// The height of a capital letter, where the screen is 2.0 tall.
+    pub size: f32,
+    pub align: Align,
+    /// How fast the text scrolls from right to left, in screen widths per second.
+    pub scroll_speed: Option<f32>,
+}
+
+/// How far from the screen's edges left and right aligned text sits.
+const MARGIN: f32 = 0.05;
+/// The distance between baselines, in glyph units.
+const LINE_HEIGHT: f32 = font::CAP_HEIGHT + font::DESCENT + 2.0;
+
+impl Text {
+    pub fn new<S: AsRef<str>>(lines: &[S]) -> Self {
+        Self {
+            lines: lines.iter().map(|line| line.as_ref().to_string()).collect(),
+            size: 0.2,
+            align: Align::default(),
+            scroll_speed: None,
+        }
+    }
+
+    fn line_width(line: &str) -> f32 {
+        match line.chars().count() {
+            0 => 0.0,
+            n => (n - 1) as f32 * font::ADVANCE + font::GLYPH_WIDTH,
+        }
+    }
+}
+
+impl Sketch for Text {
+    fn draw(&mut self, time: f32) -> Vec<Polyline> {
+        let scale = self.size / font::CAP_HEIGHT;
+        let block_width = self
+            .lines
+            .iter()
+            .map(|line| Text::line_width(line))
+            .fold(0.0, f32::max)
+            * scale;
+
+        // Marquees start just off the right edge and scroll until they're off the left one
+        let block_left = match self.scroll_speed {
+            Some(speed) => 1.0 - (2.0 * speed * time).rem_euclid(2.0 + block_width),
+            None => match self.align {
+                Align::Left => -1.0 + MARGIN,
+                Align::Center => -block_width / 2.0,
+                Align::Right => 1.0 - MARGIN - block_width,
+            },
+        };
+
+        // The block of lines is centered vertically on the capitals
+        let block_height = (self.lines.len() as f32 - 1.0) * LINE_HEIGHT + font::CAP_HEIGHT;
+        let first_baseline = (block_height / 2.0 - font::CAP_HEIGHT) * scale;
+
+        let mut polylines = Vec::new();
+        for (row, line) in self.lines.iter().enumerate() {
+            let line_width = Text::line_width(line) * scale;
+            let left = block_left
+                + match self.align {
+                    Align::Left => 0.0,
+                    Align::Center => (block_width - line_width) / 2.0,
+                    Align::Right => block_width - line_width,
+                };
+            let baseline = first_baseline - row as f32 * LINE_HEIGHT * scale;
+
+            for (column, c) in line.chars().enumerate() {
+                let origin = [left + column as f32 * font::ADVANCE * scale, baseline];
+                polylines.extend(font::glyph(c).into_iter().map(|stroke| {
+                    stroke
+                        .into_iter()
+                        .map(|[x, y]| [origin[0] + x * scale, origin[1] + y * scale])
+                        .collect()
+                }));
+            }
+        }
+        super::clip_x(polylines)
+    }
+}
+
+/// Draws text with the built-in single-stroke font, either live or into a WAV, eg.
+///
+/// ```sh
+/// oscilloscope text "HELLO" "WORLD" --size=0.3
+/// oscilloscope text "Now playing: Blocks" --scroll=0.25 --output=marquee.wav --duration=20
+/// ```
+///
+/// Each positional argument is a line of text.
+///
+/// Options:
+/// - `--output=PATH`: write a WAV instead of showing the text live
+/// - `--size=S`: the height of a capital letter, where the screen is 2 tall (default 0.2)
+/// - `--align=left|center|right` (default center)
+/// - `--scroll=SPEED`: scroll right to left, in screen widths per second
+/// - `--sample-rate=N` (default 48000)
+/// - `--dsp=CHAIN`: signal processing for the output, eg. `lowpass:8000,clip`
+/// - `--fps=N`: how many times a second the text is traced (default 50)
+/// - `--duration=SECONDS`: how much to write with `--output` (default 10)
+pub fn main(args: Args) -> anyhow::Result<()> {
+    let (sample_rate, fps) = super::parse_rates(&args)?;
+    let chain = args.parse_value::<Chain>("dsp")?.unwrap_or_default();
+    let duration = args.parse_value::<f32>("duration")?.unwrap_or(10.0);
+    if args.positional.is_empty() {
+        bail!("text needs at least one line of text");
+    }
+
+    let mut text = Text::new(&args.positional);
+    if let Some(size) = args.parse_value("size")? {
+        text.size = size;
+    }
+    if let Some(align) = args.parse_value("align")? {
+        text.align = align;
+    }
+    text.scroll_speed = args.parse_value("scroll")?;
+
+    let generator = Generator::new(text, sample_rate, fps)?;
+    match args.value("output") {
+        Some(output) => {
+            super::write_wav(
+                output,
+                sample_rate,
+                2,
+                chain.apply(generator.take_duration(duration), sample_rate),
+            )?;
+            log::info!("wrote {}s to {}", duration, output);
+        }
+        None => onepass::preview(&args, vec![Box::new(generator)])?,
+    }
+    Ok(())
+}
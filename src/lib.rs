@@ -0,0 +1,38 @@
+//! Draws audio the way an analog oscilloscope's beam would, live in a window, into video, or
+//! into an app's own textures with its own wgpu device:
+//!
+//! ```no_run
+//! use std::sync::Arc;
+//!
+//! use oscilloscope::{Oscilloscope, Shaderer, Source, WavStreamer, WgpuResources};
+//!
+//! fn scope(
+//!     device: Arc<wgpu::Device>,
+//!     queue: Arc<wgpu::Queue>,
+//!     view: &wgpu::TextureView,
+//! ) -> anyhow::Result<()> {
+//!     let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+//!     let wgpu_resources = WgpuResources::with_device(device, queue, format, 512, 512);
+//!     let source: Box<dyn Source> = Box::new(WavStreamer::new("song.wav")?);
+//!     let mut oscilloscope = Oscilloscope::with_sources(wgpu_resources, vec![source])?;
+//!
+//!     // Then every frame
+//!     oscilloscope.update();
+//!     oscilloscope.render_to(view, 512, 512);
+//!     Ok(())
+//! }
+//! ```
+
+pub mod args;
+mod camera;
+pub mod dsp;
+pub mod generate;
+pub mod onepass;
+pub mod ringbuffer;
+pub mod sound;
+
+pub use self::{
+    onepass::{Oscilloscope, Shaderer, State, WgpuResources},
+    ringbuffer::RingBuffer,
+    sound::{Frame, Source, WavStreamer},
+};
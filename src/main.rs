@@ -1,383 +1,27 @@
-// TODO: remove this
-#![allow(unreachable_code)]
-#![allow(unused_variables)]
-mod blah;
-mod camera;
-mod instancing;
-mod onepass;
-mod ringbuffer;
-mod sound;
-mod texture;
-
-use std::time::{Duration, Instant};
-
-use bytemuck::{self};
-
-use ringbuffer::RingBuffer;
-use sound::WavStreamer;
-use wgpu::util::DeviceExt;
-use winit::{
-    event::*,
-    event_loop::{ControlFlow, EventLoop},
-    window::{Window, WindowBuilder},
-};
-
-struct State {
-    time: Instant,
-    time_delta: Duration,
-    surface: wgpu::Surface,
-    device: wgpu::Device,
-    queue: wgpu::Queue,
-    config: wgpu::SurfaceConfiguration,
-    size: winit::dpi::PhysicalSize<u32>,
-    color: wgpu::Color,
-    render_pipeline: wgpu::RenderPipeline,
-    vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
-    num_indices: u32,
-
-    use_complex: bool,
-
-    depth_texture: texture::Texture,
-
-    wav_streamer: WavStreamer,
-    rb: RingBuffer<(f32, f32)>,
-}
-
-impl State {
-    // Creating some of the wgpu types requires async code
-    async fn new(window: &Window) -> Self {
-        let size = window.inner_size();
-
-        // The instance is a handle to our GPU
-        // Backends::all => Vulkan + Metal + DX12 + Browser WebGPU
-        let instance = wgpu::Instance::new(wgpu::Backends::all());
-        let surface = unsafe { instance.create_surface(window) };
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .unwrap();
-
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: None,
-                    features: wgpu::Features::POLYGON_MODE_LINE,
-                    limits: wgpu::Limits::default(),
-                },
-                None, // Trace path
-            )
-            .await
-            .unwrap();
-
-        let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface.get_preferred_format(&adapter).unwrap(),
-            width: size.width,
-            height: size.height,
-            present_mode: wgpu::PresentMode::Fifo,
-        };
-
-        surface.configure(&device, &config);
-
-        let color = wgpu::Color::BLACK;
-
-        // alternatively, can use include_wgsl! macro
-        // let shader = device.create_shader_module(&include_wgsl!("shader.wgsl"));
-        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
-            label: Some("Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
-        });
-
-        // let camera = camera::new_camera(&config, &device);
-
-        let depth_texture =
-            texture::Texture::create_depth_texture(&device, &config, "depth_texture");
-
-        let render_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[],
-                push_constant_ranges: &[],
-            });
-
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[/*vertex::Vertex::desc()*/],
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                }],
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleStrip,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                // Setting this to anything other than Fill
-                // requires Features::NON_FILL_POLYGON_MODE
-                // polygon_mode: wgpu::PolygonMode::Line,
-                polygon_mode: wgpu::PolygonMode::Line,
-                // Requires Features::DEPTH_CLIP_CONTROL
-                unclipped_depth: false,
-                // Requires Features::CONSERVATIVE_RASTERIZATION
-                conservative: false,
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: texture::Texture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-        });
-
-        let num_indices = 0; //indices.len() as u32;
-
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(&[0.0] /*verts*/),
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-        });
-
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(&[0.0] /*indices*/),
-            usage: wgpu::BufferUsages::INDEX,
-        });
-
-        let use_complex = false;
-
-        let time = Instant::now();
-        let time_delta = Duration::new(0, 0);
-
-        let wav_streamer = WavStreamer::new("music/02 Lines.wav");
-        const SAMPLES: usize = 2 * 44100;
-        let rb = RingBuffer::new(vec![(0.0, 0.0); SAMPLES]);
-
-        Self {
-            time,
-            time_delta,
-            surface,
-            device,
-            queue,
-            config,
-            size,
-            color,
-            render_pipeline,
-            vertex_buffer,
-            index_buffer,
-            num_indices,
-            use_complex,
-            depth_texture,
-            wav_streamer,
-            rb,
-        }
-    }
-
-    fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
-        if new_size.width > 0 && new_size.height > 0 {
-            self.size = new_size;
-            self.config.width = new_size.width;
-            self.config.height = new_size.height;
-            self.surface.configure(&self.device, &self.config);
-            self.depth_texture =
-                texture::Texture::create_depth_texture(&self.device, &self.config, "depth_texture");
-        }
-    }
-
-    fn input(&mut self, event: &WindowEvent) -> bool {
-        match event {
-            WindowEvent::CursorMoved { position, .. } => {
-                self.color = wgpu::Color {
-                    r: 0.0,
-                    g: 0.05 * position.x / (self.size.width as f64),
-                    b: 0.05 * position.y / (self.size.height as f64),
-                    a: 1.0,
-                };
-                false
-            }
-            WindowEvent::KeyboardInput {
-                input:
-                    KeyboardInput {
-                        state,
-                        virtual_keycode: Some(VirtualKeyCode::Space),
-                        ..
-                    },
-                ..
-            } => {
-                self.use_complex = *state == ElementState::Pressed;
-                true
-            }
-            _ => false,
-        }
-    }
-
-    fn update(&mut self) {
-        let new_time = Instant::now();
-        self.time_delta = new_time.duration_since(self.time);
-        self.time = new_time;
-
-        let millis = self.time_delta.as_millis();
-
-        let samples = (22100/* * millis as f64 */) as usize;
-
-        // self.wav_streamer
-        //     .iter()
-        //     .take(samples as usize)
-        //     .for_each(|v| self.rb.push(v));
-
-        // let verts = self
-        //     .rb
-        //     .iter()
-        //     .map(|(x, y)| vertex::Vertex {
-        //         color: RED,
-        //         position: [x, y, 0.0],
-        //     })
-        //     .collect::<Vec<_>>();
-
-        // self.queue
-        //     .write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&verts));
-
-        // self.queue.submit(command_buffers)
-
-        // self.camera
-        //     .controller
-        //     .update_camera(&mut self.camera.camera);
-        // self.camera.uniform.update_view_proj(&self.camera.camera);
-        // self.queue.write_buffer(
-        //     &self.camera.buffer,
-        //     0,
-        //     bytemuck::cast_slice(&[self.camera.uniform]),
-        // );
-    }
-
-    fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let output = self.surface.get_current_texture()?;
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
-
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render Encoder"),
-            });
-
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(self.color),
-                        store: true,
-                    },
-                }],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &self.depth_texture.view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: true,
-                    }),
-                    stencil_ops: None,
-                }),
-            });
-            render_pass.set_pipeline(&self.render_pipeline);
-
-            let data = (&self.vertex_buffer, &self.index_buffer, self.num_indices);
-
-            render_pass.set_vertex_buffer(0, data.0.slice(..));
-            // render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
-
-            render_pass.set_index_buffer(data.1.slice(..), wgpu::IndexFormat::Uint32);
-
-            render_pass.draw_indexed(0..data.2, 0, 0..1 as _);
-            // render_pass.draw(0..self.num_indices, 0..1)
-        }
-
-        // submit will accept anything that implements IntoIter
-        self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
-
-        Ok(())
-    }
-}
+use oscilloscope::{args::Args, generate, onepass};
 
 fn main() {
-    onepass::main();
-    return;
-    let event_loop = EventLoop::new();
-    let window = WindowBuilder::new().build(&event_loop).unwrap();
-
-    // State::new uses async code, so we're going to wait for it to finish
-    let mut state = pollster::block_on(State::new(&window));
-
-    event_loop.run(move |event, _, control_flow| match event {
-        Event::WindowEvent {
-            ref event,
-            window_id,
-        } if window_id == window.id() => {
-            if !state.input(event) {
-                match event {
-                    WindowEvent::CloseRequested
-                    | WindowEvent::KeyboardInput {
-                        input:
-                            KeyboardInput {
-                                state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Escape),
-                                ..
-                            },
-                        ..
-                    } => *control_flow = ControlFlow::Exit,
-                    WindowEvent::Resized(physical_size) => {
-                        state.resize(*physical_size);
-                    }
-                    WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
-                        // new_inner_size is &&mut so we have to dereference it twice
-                        state.resize(**new_inner_size);
-                    }
-                    _ => {}
-                }
-            }
-        }
-        Event::RedrawRequested(window_id) if window_id == window.id() => {
-            state.update();
-            match state.render() {
-                Ok(_) => {}
-                // Reconfigure the surface if lost
-                Err(wgpu::SurfaceError::Lost) => state.resize(state.size),
-                // The system is out of memory, we should probably quit
-                Err(wgpu::SurfaceError::OutOfMemory) => *control_flow = ControlFlow::Exit,
-                // All other errors (Outdated, Timeout) should be resolved by the next frame
-                Err(e) => eprintln!("{:?}", e),
-            }
-        }
-        Event::MainEventsCleared => {
-            // RedrawRequested will only trigger once, unless we manually
-            // request it.
-            window.request_redraw();
-        }
-        _ => {}
-    });
+    env_logger::init();
+    let mut args = Args::from_env();
+    let result = match args
+        .subcommand(&[
+            "export", "clip", "headless", "adapters", "svg", "text", "image", "mesh", "midi",
+        ])
+        .as_deref()
+    {
+        Some("export") => onepass::export::main(args),
+        Some("clip") => onepass::clip::main(args),
+        Some("headless") => onepass::headless(args),
+        Some("adapters") => onepass::gpu::main(args),
+        Some("svg") => generate::svg::main(args),
+        Some("text") => generate::text::main(args),
+        Some("image") => generate::raster::main(args),
+        Some("mesh") => generate::mesh::main(args),
+        Some("midi") => generate::synth::main(args),
+        _ => onepass::main(args),
+    };
+    if let Err(e) = result {
+        eprintln!("error: {:?}", e);
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,343 @@
+//! Key and mouse wheel bindings for the live window. Every binding is a trigger, eg. `Shift+1`,
+//! and an action, eg. `preset save 1`, so config files can change any of them.
+
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, bail, Context};
+use serde::{
+    de::{value::StrDeserializer, IntoDeserializer},
+    Deserialize,
+};
+use winit::event::{ModifiersState, MouseScrollDelta, VirtualKeyCode};
+
+use super::{control::Command, remote};
+
+/// The bindings there are before any config changes them.
+const DEFAULT_BINDINGS: &[(&str, &str)] = &[
+    ("Escape", "quit"),
+    ("H", "toggle hud"),
+    ("Space", "pause"),
+    ("J", "step"),
+    ("F", "cycle fade"),
+    ("C", "cycle color"),
+    ("Tab", "cycle view"),
+    ("V", "cycle visualizer"),
+    ("L", "cycle layout"),
+    ("B", "toggle blanking"),
+    ("P", "screenshot"),
+    ("R", "toggle recording"),
+    ("Equals", "thickness up"),
+    ("Minus", "thickness down"),
+    ("NumpadAdd", "thickness up"),
+    ("NumpadSubtract", "thickness down"),
+    // Some platforms report the shifted symbol rather than the key, so both are bound
+    ("Shift+Equals", "brightness up"),
+    ("Shift+Minus", "brightness down"),
+    ("Shift+Plus", "brightness up"),
+    ("Shift+Underline", "brightness down"),
+    ("Ctrl+Equals", "decay up"),
+    ("Ctrl+Minus", "decay down"),
+    ("Left", "pan left"),
+    ("Right", "pan right"),
+    ("Up", "pan up"),
+    ("Down", "pan down"),
+    ("ScrollUp", "zoom in"),
+    ("ScrollDown", "zoom out"),
+    ("0", "reset view"),
+    ("LBracket", "dsp previous"),
+    ("RBracket", "dsp next"),
+    ("Comma", "dsp down"),
+    ("Period", "dsp up"),
+    ("1", "preset 1"),
+    ("2", "preset 2"),
+    ("3", "preset 3"),
+    ("4", "preset 4"),
+    ("5", "preset 5"),
+    ("6", "preset 6"),
+    ("7", "preset 7"),
+    ("8", "preset 8"),
+    ("9", "preset 9"),
+    ("Shift+1", "preset save 1"),
+    ("Shift+2", "preset save 2"),
+    ("Shift+3", "preset save 3"),
+    ("Shift+4", "preset save 4"),
+    ("Shift+5", "preset save 5"),
+    ("Shift+6", "preset save 6"),
+    ("Shift+7", "preset save 7"),
+    ("Shift+8", "preset save 8"),
+    ("Shift+9", "preset save 9"),
+];
+
+/// Input is what sets a binding off: a key, or a turn of the mouse wheel.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Input {
+    Key(VirtualKeyCode),
+    ScrollUp,
+    ScrollDown,
+}
+
+/// Trigger is an input along with the modifiers that have to be held for it, eg. `Ctrl+Minus`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Trigger {
+    pub input: Input,
+    pub modifiers: ModifiersState,
+}
+
+impl Trigger {
+    pub fn key(key: VirtualKeyCode, modifiers: ModifiersState) -> Self {
+        Self {
+            input: Input::Key(key),
+            modifiers,
+        }
+    }
+
+    /// The trigger for a turn of the mouse wheel, if it turned at all. Each event is one step,
+    /// however far it turned.
+    pub fn scroll(delta: &MouseScrollDelta, modifiers: ModifiersState) -> Option<Self> {
+        let y = match delta {
+            MouseScrollDelta::LineDelta(_, y) => *y,
+            MouseScrollDelta::PixelDelta(position) => position.y as f32,
+        };
+        let input = match y {
+            y if y > 0.0 => Input::ScrollUp,
+            y if y < 0.0 => Input::ScrollDown,
+            _ => return None,
+        };
+        Some(Self { input, modifiers })
+    }
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let modifiers = [
+            (ModifiersState::CTRL, "Ctrl+"),
+            (ModifiersState::ALT, "Alt+"),
+            (ModifiersState::SHIFT, "Shift+"),
+            (ModifiersState::LOGO, "Logo+"),
+        ];
+        for (modifier, name) in modifiers {
+            if self.modifiers.contains(modifier) {
+                f.write_str(name)?;
+            }
+        }
+        match self.input {
+            Input::Key(key) => {
+                // Number keys are written as their digit, eg. `1` rather than `Key1`
+                let name = format!("{:?}", key);
+                match name.strip_prefix("Key") {
+                    Some(digit) if digit.len() == 1 => f.write_str(digit),
+                    _ => f.write_str(&name),
+                }
+            }
+            Input::ScrollUp => f.write_str("ScrollUp"),
+            Input::ScrollDown => f.write_str("ScrollDown"),
+        }
+    }
+}
+
+impl FromStr for Trigger {
+    type Err = anyhow::Error;
+
+    /// Modifiers and then the key, joined with `+`, eg. `Ctrl+Shift+S`. Keys are named as winit
+    /// names them, except that number keys can be just their digit and letters can be lowercase.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split('+').map(str::trim).collect::<Vec<_>>();
+        let name = parts.pop().filter(|name| !name.is_empty());
+        let name = name.ok_or_else(|| anyhow!("{:?} has no key", s))?;
+
+        let mut modifiers = ModifiersState::empty();
+        for part in parts {
+            modifiers |= match part.to_lowercase().as_str() {
+                "ctrl" | "control" => ModifiersState::CTRL,
+                "alt" => ModifiersState::ALT,
+                "shift" => ModifiersState::SHIFT,
+                "logo" | "super" | "cmd" => ModifiersState::LOGO,
+                _ => bail!(
+                    "unknown modifier {:?} in {:?}, expected ctrl, alt, shift or logo",
+                    part,
+                    s
+                ),
+            };
+        }
+
+        let input = match name.to_lowercase().as_str() {
+            "scrollup" => Input::ScrollUp,
+            "scrolldown" => Input::ScrollDown,
+            _ => {
+                let name = match name.chars().collect::<Vec<_>>().as_slice() {
+                    [digit] if digit.is_ascii_digit() => format!("Key{}", digit),
+                    [letter] => letter.to_uppercase().to_string(),
+                    _ => name.to_string(),
+                };
+                let deserializer: StrDeserializer<serde::de::value::Error> =
+                    name.as_str().into_deserializer();
+                let key = VirtualKeyCode::deserialize(deserializer).map_err(|_| {
+                    anyhow!(
+                        "unknown key {:?}, expected a name like H, 1, Equals, Up, F5 or ScrollUp",
+                        name
+                    )
+                })?;
+                Input::Key(key)
+            }
+        };
+        Ok(Self { input, modifiers })
+    }
+}
+
+/// Setting is a setting that can be stepped up and down from the keyboard.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Setting {
+    Thickness,
+    Brightness,
+    Decay,
+}
+
+/// Action is what a binding does. Anything that isn't one of the window's own actions is a
+/// command as `--control` takes it, eg. `set color amber` or `preset 2`.
+#[derive(Clone, Debug)]
+pub enum Action {
+    Quit,
+    ToggleHud,
+    CycleFade,
+    CycleColor,
+    CycleView,
+    CycleVisualizer,
+    CycleLayout,
+    ToggleBlanking,
+    ToggleRecording,
+    Adjust {
+        setting: Setting,
+        up: bool,
+    },
+    /// Moves the traces across the screen, in XY.
+    Pan {
+        x: f32,
+        y: f32,
+    },
+    Zoom {
+        zoom_in: bool,
+    },
+    /// Undoes any panning and zooming.
+    ResetView,
+    /// Selects the next or previous node of the processing chain.
+    SelectNode {
+        next: bool,
+    },
+    /// Nudges the selected node's main parameter.
+    NudgeNode {
+        up: bool,
+    },
+    Command(Command),
+}
+
+impl FromStr for Action {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words = s.split_whitespace().collect::<Vec<_>>();
+        let action = match words.as_slice() {
+            ["quit"] => Action::Quit,
+            ["toggle", "hud"] => Action::ToggleHud,
+            ["cycle", "fade"] => Action::CycleFade,
+            ["cycle", "color"] => Action::CycleColor,
+            ["cycle", "view"] => Action::CycleView,
+            ["cycle", "visualizer"] => Action::CycleVisualizer,
+            ["cycle", "layout"] => Action::CycleLayout,
+            ["toggle", "blanking"] => Action::ToggleBlanking,
+            ["toggle", "recording"] => Action::ToggleRecording,
+            [setting @ ("thickness" | "brightness" | "decay"), direction @ ("up" | "down")] => {
+                Action::Adjust {
+                    setting: match *setting {
+                        "thickness" => Setting::Thickness,
+                        "brightness" => Setting::Brightness,
+                        _ => Setting::Decay,
+                    },
+                    up: *direction == "up",
+                }
+            }
+            ["pan", "left"] => Action::Pan { x: -1.0, y: 0.0 },
+            ["pan", "right"] => Action::Pan { x: 1.0, y: 0.0 },
+            ["pan", "up"] => Action::Pan { x: 0.0, y: 1.0 },
+            ["pan", "down"] => Action::Pan { x: 0.0, y: -1.0 },
+            ["zoom", "in"] => Action::Zoom { zoom_in: true },
+            ["zoom", "out"] => Action::Zoom { zoom_in: false },
+            ["reset", "view"] => Action::ResetView,
+            ["dsp", "next"] => Action::SelectNode { next: true },
+            ["dsp", "previous"] => Action::SelectNode { next: false },
+            ["dsp", "up"] => Action::NudgeNode { up: true },
+            ["dsp", "down"] => Action::NudgeNode { up: false },
+            _ => Action::Command(
+                remote::parse(s).with_context(|| format!("unknown action {:?}", s))?,
+            ),
+        };
+        Ok(action)
+    }
+}
+
+/// Binding is a trigger and the action it sets off, along with the action as it was written so
+/// it can be shown.
+#[derive(Clone, Debug)]
+pub struct Binding {
+    pub trigger: Trigger,
+    pub action: Action,
+    pub text: String,
+}
+
+/// Bindings are every binding there is, in the order they're listed in.
+#[derive(Clone, Debug)]
+pub struct Bindings {
+    bindings: Vec<Binding>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        let mut bindings = Self {
+            bindings: Vec::new(),
+        };
+        for (trigger, action) in DEFAULT_BINDINGS {
+            bindings
+                .bind(trigger, action)
+                .expect("default bindings are valid");
+        }
+        bindings
+    }
+}
+
+impl Bindings {
+    /// Binds `trigger` to `action` in place of whatever it was bound to, or unbinds it if
+    /// `action` is `none` or empty.
+    pub fn bind(&mut self, trigger: &str, action: &str) -> anyhow::Result<()> {
+        let trigger = trigger.parse()?;
+        let action = action.trim();
+        let binding = match action {
+            "" | "none" => None,
+            _ => Some(Binding {
+                trigger,
+                action: action.parse()?,
+                text: action.to_string(),
+            }),
+        };
+        let existing = self.bindings.iter().position(|b| b.trigger == trigger);
+        match (existing, binding) {
+            (Some(i), Some(binding)) => self.bindings[i] = binding,
+            (Some(i), None) => {
+                self.bindings.remove(i);
+            }
+            (None, Some(binding)) => self.bindings.push(binding),
+            (None, None) => {}
+        }
+        Ok(())
+    }
+
+    /// What `trigger` is bound to, if anything.
+    pub fn action(&self, trigger: &Trigger) -> Option<&Action> {
+        self.bindings
+            .iter()
+            .find(|binding| binding.trigger == *trigger)
+            .map(|binding| &binding.action)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Binding> {
+        self.bindings.iter()
+    }
+}
@@ -0,0 +1,278 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::{bail, Context};
+use image::{
+    codecs::{gif, png::PngEncoder},
+    imageops::FilterType,
+    ColorType, Delay, RgbaImage,
+};
+
+use crate::{args::Args, dsp::Chain};
+
+use super::{
+    config, export::render_frames, gpu::GpuOptions, offscreen::Offscreen,
+    oscilloscope::Oscilloscope, wgpu_resources::WgpuResources,
+};
+
+/// Renders a short time range of WAV files to an animated GIF or APNG, eg.
+///
+/// ```sh
+/// oscilloscope clip --output=clip.gif --start=30 --duration=5 --decimate=4 --scale=0.5 song.wav
+/// ```
+///
+/// The format is picked from the output's extension: `.gif`, or `.png`/`.apng` for APNG.
+///
+/// Options:
+/// - `--output=PATH` (required)
+/// - `--start=SECONDS` (default 0)
+/// - `--duration=SECONDS` (default 5)
+/// - `--fps=N`: the rate the scope is simulated at (default 60)
+/// - `--decimate=N`: keep every Nth simulated frame (default 3, ie. 20fps)
+/// - `--size=WIDTHxHEIGHT`: the rendered size (default 1024x1024)
+/// - `--scale=S`: resize rendered frames by S before encoding (default 0.5)
+/// - `--quantize-speed=N`: GIF palette quantization, from 1 (best) to 30 (fastest) (default 10)
+/// - `--dsp=CHAIN`: signal processing before drawing, eg. `dc,ms`
+/// - `--config=PATH`: the project config (default `oscilloscope.toml`)
+/// - `--preset=NAME`: a preset from the config files
+/// - `--backend=LIST`, `--adapter=NAME|INDEX`, `--software`, `--power=low|high`: which GPU to
+///   render on, see `oscilloscope adapters`
+pub fn main(args: Args) -> anyhow::Result<()> {
+    let output = args.value("output").context("clip needs --output=PATH")?;
+    let start = args.parse_value::<f32>("start")?.unwrap_or(0.0);
+    let duration = args.parse_value::<f32>("duration")?.unwrap_or(5.0);
+    let fps = args.parse_value::<u32>("fps")?.unwrap_or(60);
+    if fps == 0 {
+        bail!("--fps must be more than 0");
+    }
+    let decimate = args.parse_value::<u32>("decimate")?.unwrap_or(3).max(1);
+    let (width, height) = args.size("size")?.unwrap_or((1024, 1024));
+    let scale = args.parse_value::<f32>("scale")?.unwrap_or(0.5);
+    let quantize_speed = args.parse_value::<i32>("quantize-speed")?.unwrap_or(10);
+    let chain = args.parse_value::<Chain>("dsp")?;
+    let gpu_options = GpuOptions::parse(&args)?;
+    if args.positional.is_empty() {
+        bail!("clip needs at least one WAV file");
+    }
+    if !(1..=30).contains(&quantize_speed) {
+        bail!("--quantize-speed must be between 1 and 30");
+    }
+
+    let out = BufWriter::new(File::create(output).with_context(|| format!("creating {}", output))?);
+    let extension = Path::new(output)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase());
+    let delay = Delay::from_numer_denom_ms(1000 * decimate, fps);
+    let mut encoder: Box<dyn ClipEncoder> = match extension.as_deref() {
+        Some("gif") => {
+            let mut encoder = gif::GifEncoder::new_with_speed(out, quantize_speed);
+            encoder.set_repeat(gif::Repeat::Infinite)?;
+            Box::new(GifClipEncoder { encoder, delay })
+        }
+        Some("png") | Some("apng") => Box::new(ApngEncoder::new(out, delay)),
+        _ => bail!(
+            "can't tell the clip format from {:?}, use .gif or .png",
+            output
+        ),
+    };
+
+    let wgpu_resources = WgpuResources::new_headless(width, height, &gpu_options)?;
+    let mut offscreen = Offscreen::new(&wgpu_resources, 1)?;
+    let mut oscilloscope = Oscilloscope::with_files(wgpu_resources, &args.positional)?;
+    oscilloscope.set_fixed_frame_rate(Some(fps as f32));
+    config::configure(&mut oscilloscope, &args)?;
+    if let Some(chain) = &chain {
+        oscilloscope.set_chain(chain);
+    }
+
+    let scaled_width = ((width as f32 * scale).round() as u32).max(1);
+    let scaled_height = ((height as f32 * scale).round() as u32).max(1);
+    let skip = (start * fps as f32).round() as u64;
+    let count = (duration * fps as f32).ceil() as u64;
+    let mut frame = 0;
+    render_frames(
+        &mut oscilloscope,
+        &mut offscreen,
+        skip,
+        Some(count),
+        |rgba| {
+            frame += 1;
+            if (frame - 1) % decimate != 0 {
+                return Ok(());
+            }
+            let image =
+                RgbaImage::from_raw(width, height, rgba).context("frame has the wrong size")?;
+            let image = if (scaled_width, scaled_height) == (width, height) {
+                image
+            } else {
+                image::imageops::resize(&image, scaled_width, scaled_height, FilterType::Triangle)
+            };
+            encoder.encode_frame(image)
+        },
+    )?;
+    encoder.finish()?;
+
+    log::info!("wrote {} frames to {}", frame.div_ceil(decimate), output);
+    Ok(())
+}
+
+/// ClipEncoder writes frames of an animation that all share one delay.
+trait ClipEncoder {
+    fn encode_frame(&mut self, image: RgbaImage) -> anyhow::Result<()>;
+    fn finish(self: Box<Self>) -> anyhow::Result<()>;
+}
+
+struct GifClipEncoder<W: Write> {
+    encoder: gif::GifEncoder<W>,
+    delay: Delay,
+}
+
+impl<W: Write> ClipEncoder for GifClipEncoder<W> {
+    fn encode_frame(&mut self, image: RgbaImage) -> anyhow::Result<()> {
+        self.encoder
+            .encode_frame(image::Frame::from_parts(image, 0, 0, self.delay))?;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> anyhow::Result<()> {
+        // GifEncoder writes the trailer when it's dropped
+        Ok(())
+    }
+}
+
+/// ApngEncoder builds an animated PNG out of single-frame PNGs: the first frame's IDAT chunks
+/// are kept as-is, and later frames' are rewritten as fdAT chunks.
+///
+/// Frames are held in memory until `finish`, since the frame count comes before any frame data.
+struct ApngEncoder<W: Write> {
+    out: W,
+    delay: Delay,
+    header: Option<Vec<u8>>,
+    frames: Vec<Vec<u8>>,
+    size: (u32, u32),
+}
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+impl<W: Write> ApngEncoder<W> {
+    fn new(out: W, delay: Delay) -> Self {
+        Self {
+            out,
+            delay,
+            header: None,
+            frames: Vec::new(),
+            size: (0, 0),
+        }
+    }
+
+    fn write_chunk(&mut self, kind: &[u8; 4], data: &[u8]) -> std::io::Result<()> {
+        let mut crc = crc32fast::Hasher::new();
+        crc.update(kind);
+        crc.update(data);
+        self.out.write_all(&(data.len() as u32).to_be_bytes())?;
+        self.out.write_all(kind)?;
+        self.out.write_all(data)?;
+        self.out.write_all(&crc.finalize().to_be_bytes())
+    }
+}
+
+impl<W: Write> ClipEncoder for ApngEncoder<W> {
+    fn encode_frame(&mut self, image: RgbaImage) -> anyhow::Result<()> {
+        let mut png = Vec::new();
+        PngEncoder::new(&mut png).encode(
+            &image,
+            image.width(),
+            image.height(),
+            ColorType::Rgba8,
+        )?;
+
+        let mut idat = Vec::new();
+        for (kind, data) in png_chunks(&png[PNG_SIGNATURE.len()..]) {
+            match &kind {
+                b"IHDR" if self.header.is_none() => {
+                    self.header = Some(data.to_vec());
+                    self.size = image.dimensions();
+                }
+                b"IDAT" => idat.extend_from_slice(data),
+                _ => {}
+            }
+        }
+        self.frames.push(idat);
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> anyhow::Result<()> {
+        let header = self
+            .header
+            .take()
+            .context("an APNG needs at least one frame")?;
+        let frames = std::mem::take(&mut self.frames);
+        let (width, height) = self.size;
+        let (delay_numer, delay_denom) = self.delay.numer_denom_ms();
+        // fcTL delays are in seconds, so scale the millisecond fraction to fit in u16s
+        let delay_denom = delay_denom as u64 * 1000;
+        let divisor = (delay_numer as u64)
+            .max(delay_denom)
+            .div_ceil(u16::MAX as u64)
+            .max(1);
+        let (delay_numer, delay_denom) = (
+            (delay_numer as u64 / divisor) as u16,
+            (delay_denom / divisor) as u16,
+        );
+
+        self.out.write_all(&PNG_SIGNATURE)?;
+        self.write_chunk(b"IHDR", &header)?;
+
+        let mut actl = Vec::new();
+        actl.extend_from_slice(&(frames.len() as u32).to_be_bytes());
+        actl.extend_from_slice(&0u32.to_be_bytes()); // loop forever
+        self.write_chunk(b"acTL", &actl)?;
+
+        let mut sequence = 0u32;
+        for (i, idat) in frames.iter().enumerate() {
+            let mut fctl = Vec::new();
+            fctl.extend_from_slice(&sequence.to_be_bytes());
+            fctl.extend_from_slice(&width.to_be_bytes());
+            fctl.extend_from_slice(&height.to_be_bytes());
+            fctl.extend_from_slice(&0u32.to_be_bytes()); // x offset
+            fctl.extend_from_slice(&0u32.to_be_bytes()); // y offset
+            fctl.extend_from_slice(&delay_numer.to_be_bytes());
+            fctl.extend_from_slice(&delay_denom.to_be_bytes());
+            fctl.push(0); // dispose op: none
+            fctl.push(0); // blend op: source
+            self.write_chunk(b"fcTL", &fctl)?;
+            sequence += 1;
+
+            if i == 0 {
+                self.write_chunk(b"IDAT", idat)?;
+            } else {
+                let mut fdat = sequence.to_be_bytes().to_vec();
+                fdat.extend_from_slice(idat);
+                self.write_chunk(b"fdAT", &fdat)?;
+                sequence += 1;
+            }
+        }
+
+        self.write_chunk(b"IEND", &[])?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Splits the chunks of a PNG (after its signature) into their kinds and data.
+fn png_chunks(mut png: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+    std::iter::from_fn(move || {
+        if png.len() < 12 {
+            return None;
+        }
+        let length = u32::from_be_bytes([png[0], png[1], png[2], png[3]]) as usize;
+        let kind = [png[4], png[5], png[6], png[7]];
+        let data = png.get(8..8 + length)?;
+        png = png.get(12 + length..)?;
+        Some((kind, data))
+    })
+}
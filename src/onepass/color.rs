@@ -0,0 +1,164 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, bail};
+
+/// ColorMode decides how the trace is colored between `ColorScheme::primary` and `secondary`.
+/// The discriminants must match the `COLOR_*` constants in `render.wgsl`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColorMode {
+    /// Every sample is drawn in the primary color.
+    Solid = 0,
+    /// Fresh samples are drawn in the primary color, fading to the secondary color with age.
+    Age = 1,
+    /// Slow moving beams are drawn in the primary color, fast ones in the secondary color.
+    Velocity = 2,
+}
+
+/// ColorScheme is a named set of trace and background colors.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ColorScheme {
+    pub name: &'static str,
+    pub mode: ColorMode,
+    pub primary: [f32; 3],
+    pub secondary: [f32; 3],
+    pub background: [f32; 3],
+}
+
+impl ColorScheme {
+    /// P1 phosphor: the green of most analog scopes.
+    pub const P1: Self = Self::solid("P1 green", [0.2, 1.0, 0.3]);
+    /// P7 phosphor: a blue-white flash with a long yellow afterglow.
+    pub const P7: Self = Self {
+        name: "P7 blue-yellow",
+        mode: ColorMode::Age,
+        primary: [0.6, 0.75, 1.0],
+        secondary: [1.0, 0.85, 0.2],
+        background: [0.0, 0.0, 0.0],
+    };
+    /// P31 phosphor: a brighter, yellower green.
+    pub const P31: Self = Self::solid("P31", [0.55, 1.0, 0.35]);
+    pub const AMBER: Self = Self::solid("amber", [1.0, 0.6, 0.1]);
+    pub const VELOCITY: Self = Self {
+        name: "velocity",
+        mode: ColorMode::Velocity,
+        primary: [0.2, 0.4, 1.0],
+        secondary: [1.0, 0.2, 0.4],
+        background: [0.0, 0.0, 0.0],
+    };
+
+    /// PRESETS are the schemes cycled through at runtime.
+    pub const PRESETS: [Self; 5] = [Self::P1, Self::P7, Self::P31, Self::AMBER, Self::VELOCITY];
+
+    pub const fn solid(name: &'static str, color: [f32; 3]) -> Self {
+        Self {
+            name,
+            mode: ColorMode::Solid,
+            primary: color,
+            secondary: color,
+            background: [0.0, 0.0, 0.0],
+        }
+    }
+
+    /// A single custom color, eg. from user configuration.
+    pub const fn custom(color: [f32; 3]) -> Self {
+        Self::solid("custom", color)
+    }
+
+    pub const fn gradient(mode: ColorMode, from: [f32; 3], to: [f32; 3]) -> Self {
+        Self {
+            name: "custom gradient",
+            mode,
+            primary: from,
+            secondary: to,
+            background: [0.0, 0.0, 0.0],
+        }
+    }
+
+    /// Returns the preset after this one, or the first preset if this one isn't a preset.
+    pub fn next_preset(&self) -> Self {
+        let i = Self::PRESETS
+            .iter()
+            .position(|preset| preset == self)
+            .map_or(0, |i| (i + 1) % Self::PRESETS.len());
+        Self::PRESETS[i]
+    }
+
+    pub fn clear_color(&self) -> wgpu::Color {
+        let [r, g, b] = self.background;
+        wgpu::Color {
+            r: r as f64,
+            g: g as f64,
+            b: b as f64,
+            a: 1.0,
+        }
+    }
+}
+
+impl Default for ColorScheme {
+    fn default() -> Self {
+        Self::P1
+    }
+}
+
+/// Parses a preset name (eg. `p1`, `amber`), a custom `#rrggbb` color, or a gradient written as
+/// `age:#rrggbb:#rrggbb` or `velocity:#rrggbb:#rrggbb`.
+impl FromStr for ColorScheme {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        if let Some(preset) = Self::PRESETS.iter().find(|preset| {
+            let name = preset.name.to_lowercase();
+            name == s || name.split_whitespace().next() == Some(&s)
+        }) {
+            return Ok(*preset);
+        }
+
+        let parts = s.split(':').collect::<Vec<_>>();
+        match parts.as_slice() {
+            [color] => Ok(Self::custom(parse_hex(color)?)),
+            [mode, from, to] => {
+                let mode = match *mode {
+                    "age" => ColorMode::Age,
+                    "velocity" => ColorMode::Velocity,
+                    _ => bail!("unknown gradient {:?}, expected age or velocity", mode),
+                };
+                Ok(Self::gradient(mode, parse_hex(from)?, parse_hex(to)?))
+            }
+            _ => bail!("unknown color scheme {:?}", s),
+        }
+    }
+}
+
+/// Writes a scheme the way it's parsed: a preset by its short name, or its colors.
+impl fmt::Display for ColorScheme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if Self::PRESETS.contains(self) {
+            let name = self.name.split_whitespace().next().unwrap_or(self.name);
+            return f.write_str(&name.to_lowercase());
+        }
+        match self.mode {
+            ColorMode::Solid => f.write_str(&hex(self.primary)),
+            ColorMode::Age => write!(f, "age:{}:{}", hex(self.primary), hex(self.secondary)),
+            ColorMode::Velocity => {
+                write!(f, "velocity:{}:{}", hex(self.primary), hex(self.secondary))
+            }
+        }
+    }
+}
+
+fn hex(color: [f32; 3]) -> String {
+    let [r, g, b] = color.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+fn parse_hex(color: &str) -> anyhow::Result<[f32; 3]> {
+    let hex = color
+        .strip_prefix('#')
+        .filter(|hex| hex.len() == 6)
+        .ok_or_else(|| anyhow!("expected a color like #rrggbb, got {:?}", color))?;
+    let channel = |i: usize| -> anyhow::Result<f32> {
+        Ok(u8::from_str_radix(&hex[i..i + 2], 16)? as f32 / 255.0)
+    };
+    Ok([channel(0)?, channel(2)?, channel(4)?])
+}
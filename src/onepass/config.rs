@@ -0,0 +1,344 @@
+//! Config files and named presets for the live window.
+//!
+//! Settings are layered: the built-in defaults, then the user's config, then the project's, then
+//! the command line. Presets are switched between at runtime, and saved to a file of their own
+//! next to the project's config, so the config itself is never rewritten.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{args::Args, dsp::Chain};
+
+use super::{
+    bindings::Bindings,
+    color::ColorScheme,
+    oscilloscope::{Layout, Oscilloscope},
+    state::{FadeCurve, ViewMode},
+};
+
+/// The project's config, looked for in the working directory unless `--config` says otherwise.
+const PROJECT_CONFIG: &str = "oscilloscope.toml";
+/// The top of the saved presets file, which is rewritten whenever a preset is saved.
+const SAVED_PRESETS_HEADER: &str =
+    "# Presets saved from the oscilloscope, which rewrites this file when it saves one\n\n";
+
+/// Settings are values for the renderer's parameters. Anything left out keeps whatever value it
+/// had, so settings can be layered on top of each other.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Settings {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thickness: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gain: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub brightness: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decay: Option<f32>,
+    #[serde(with = "as_string", skip_serializing_if = "Option::is_none")]
+    pub fade: Option<FadeCurve>,
+    #[serde(with = "as_string", skip_serializing_if = "Option::is_none")]
+    pub color: Option<ColorScheme>,
+    #[serde(with = "as_string", skip_serializing_if = "Option::is_none")]
+    pub view: Option<ViewMode>,
+    #[serde(with = "as_string", skip_serializing_if = "Option::is_none")]
+    pub layout: Option<Layout>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blanking: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blanking_threshold: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fog_density: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size_attenuation: Option<f32>,
+    #[serde(with = "as_string", skip_serializing_if = "Option::is_none")]
+    pub dsp: Option<Chain>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shader_params: Option<Vec<f32>>,
+}
+
+impl Settings {
+    /// Returns these settings with any that `over` has replaced.
+    fn merged(self, over: Settings) -> Settings {
+        Settings {
+            thickness: over.thickness.or(self.thickness),
+            gain: over.gain.or(self.gain),
+            brightness: over.brightness.or(self.brightness),
+            decay: over.decay.or(self.decay),
+            fade: over.fade.or(self.fade),
+            color: over.color.or(self.color),
+            view: over.view.or(self.view),
+            layout: over.layout.or(self.layout),
+            blanking: over.blanking.or(self.blanking),
+            blanking_threshold: over.blanking_threshold.or(self.blanking_threshold),
+            fog_density: over.fog_density.or(self.fog_density),
+            size_attenuation: over.size_attenuation.or(self.size_attenuation),
+            dsp: over.dsp.or(self.dsp),
+            shader_params: over.shader_params.or(self.shader_params),
+        }
+    }
+}
+
+/// Reads and writes settings as the strings they're given as on the command line, eg. `p31` for
+/// a color scheme.
+mod as_string {
+    use super::*;
+
+    pub fn serialize<T, S>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: fmt::Display,
+        S: Serializer,
+    {
+        match value {
+            Some(value) => serializer.collect_str(value),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+    where
+        T: FromStr<Err = anyhow::Error>,
+        D: Deserializer<'de>,
+    {
+        Option::<String>::deserialize(deserializer)?
+            .map(|value| value.parse().map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Preset {
+    pub name: String,
+    #[serde(flatten)]
+    pub settings: Settings,
+    // Anything else, so typos can be reported rather than ignored
+    #[serde(flatten, skip_serializing)]
+    unknown: BTreeMap<String, toml::Value>,
+}
+
+impl Preset {
+    fn new(name: &str, settings: Settings) -> Self {
+        Self {
+            name: name.to_string(),
+            settings,
+            unknown: BTreeMap::new(),
+        }
+    }
+}
+
+/// Config is a config file: default settings at the top, any number of `[[preset]]` tables, and
+/// a `[bindings]` table of keys and the actions they're bound to.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Config {
+    #[serde(flatten)]
+    defaults: Settings,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    bindings: BTreeMap<String, String>,
+    #[serde(default, rename = "preset", skip_serializing_if = "Vec::is_empty")]
+    presets: Vec<Preset>,
+    #[serde(flatten, skip_serializing)]
+    unknown: BTreeMap<String, toml::Value>,
+}
+
+impl Config {
+    /// Reads the config at `path`, or an empty one if there's no file there.
+    fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+        };
+        let config: Config =
+            toml::from_str(&text).with_context(|| format!("reading {}", path.display()))?;
+        let unknown = config
+            .unknown
+            .keys()
+            .chain(
+                config
+                    .presets
+                    .iter()
+                    .flat_map(|preset| preset.unknown.keys()),
+            )
+            .next();
+        if let Some(key) = unknown {
+            bail!("unknown setting {:?} in {}", key, path.display());
+        }
+        Ok(config)
+    }
+}
+
+/// Where the user's config lives, following the platform's conventions.
+fn user_config_path() -> Option<PathBuf> {
+    let dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))?;
+    Some(dir.join("oscilloscope").join("config.toml"))
+}
+
+/// The presets every install has, before any config adds to or replaces them.
+fn builtin_presets() -> Vec<Preset> {
+    let look = |color: ColorScheme, thickness, decay, fade, dsp: &str| Settings {
+        thickness: Some(thickness),
+        gain: Some(1.0),
+        brightness: Some(1.0),
+        decay: Some(decay),
+        fade: Some(fade),
+        color: Some(color),
+        dsp: Some(dsp.parse().expect("built-in chains are valid")),
+        ..Settings::default()
+    };
+    vec![
+        Preset::new(
+            "classic green",
+            look(ColorScheme::P1, 0.0075, 4.0, FadeCurve::Exponential, ""),
+        ),
+        Preset::new(
+            "bloom heavy",
+            look(ColorScheme::P7, 0.02, 1.5, FadeCurve::Exponential, ""),
+        ),
+        Preset::new(
+            "vectorscope",
+            look(ColorScheme::AMBER, 0.005, 1.0, FadeCurve::Linear, "dc,ms"),
+        ),
+    ]
+}
+
+/// Presets are the named presets there are to switch between, in the order of the number keys.
+pub struct Presets {
+    presets: Vec<Preset>,
+    // Where saved presets are written
+    saved: PathBuf,
+}
+
+impl Presets {
+    /// Finds a preset by name, or by number counting from 1.
+    pub fn get(&self, key: &str) -> anyhow::Result<&Preset> {
+        let by_number = key
+            .parse::<usize>()
+            .ok()
+            .and_then(|number| self.presets.get(number.checked_sub(1)?));
+        by_number
+            .or_else(|| self.presets.iter().find(|preset| preset.name == key))
+            .with_context(|| {
+                let names = self.presets.iter().map(|preset| preset.name.as_str());
+                format!(
+                    "no preset {:?}, there's {}",
+                    key,
+                    names.collect::<Vec<_>>().join(", ")
+                )
+            })
+    }
+
+    /// The preset on number key `number`, counting from 1.
+    pub fn numbered(&self, number: usize) -> Option<&Preset> {
+        self.presets.get(number.checked_sub(1)?)
+    }
+
+    /// Saves `settings` as the preset `name` in the saved presets file, replacing any preset of
+    /// that name. Returns where it was written.
+    pub fn save(&mut self, name: &str, settings: Settings) -> anyhow::Result<&Path> {
+        let mut saved = Config::load(&self.saved)?;
+        add_preset(&mut saved.presets, Preset::new(name, settings.clone()));
+        let text = toml::to_string(&saved).context("writing presets")?;
+        std::fs::write(&self.saved, SAVED_PRESETS_HEADER.to_string() + &text)
+            .with_context(|| format!("writing {}", self.saved.display()))?;
+        add_preset(&mut self.presets, Preset::new(name, settings));
+        Ok(&self.saved)
+    }
+}
+
+/// Adds a preset to a list, in place of any of the same name.
+fn add_preset(presets: &mut Vec<Preset>, preset: Preset) {
+    match presets.iter_mut().find(|p| p.name == preset.name) {
+        Some(existing) => *existing = preset,
+        None => presets.push(preset),
+    }
+}
+
+/// Where presets saved for the project's config at `project` go, eg. `oscilloscope.presets.toml`
+/// for `oscilloscope.toml`.
+fn saved_presets_path(project: &Path) -> PathBuf {
+    project.with_extension("presets.toml")
+}
+
+/// Loads the user's config and then the project's, which is `--config=PATH` if given, followed
+/// by the presets saved alongside it. Returns the default settings they add up to, every preset,
+/// and the bindings.
+fn load(args: &Args) -> anyhow::Result<(Settings, Presets, Bindings)> {
+    let project = args
+        .parse_value::<PathBuf>("config")?
+        .unwrap_or_else(|| PathBuf::from(PROJECT_CONFIG));
+    let mut defaults = Settings::default();
+    let mut presets = builtin_presets();
+    let mut bindings = Bindings::default();
+    for path in user_config_path().into_iter().chain([project.clone()]) {
+        let config = Config::load(&path)?;
+        defaults = defaults.merged(config.defaults);
+        for preset in config.presets {
+            add_preset(&mut presets, preset);
+        }
+        for (trigger, action) in &config.bindings {
+            bindings
+                .bind(trigger, action)
+                .with_context(|| format!("binding {:?} in {}", trigger, path.display()))?;
+        }
+    }
+    // Only presets are saved there, so they're all that's taken from it
+    let saved = saved_presets_path(&project);
+    for preset in Config::load(&saved)?.presets {
+        add_preset(&mut presets, preset);
+    }
+    Ok((defaults, Presets { presets, saved }, bindings))
+}
+
+/// Loads the config files for `args` and applies their defaults to `oscilloscope`, followed by
+/// `--preset=NAME` if given. Returns the presets to switch between, and the bindings for the
+/// live window.
+pub fn configure(
+    oscilloscope: &mut Oscilloscope,
+    args: &Args,
+) -> anyhow::Result<(Presets, Bindings)> {
+    let (defaults, presets, bindings) = load(args)?;
+    oscilloscope.apply_settings(&defaults)?;
+    if let Some(name) = args.value("preset") {
+        oscilloscope.apply_settings(&presets.get(name)?.settings)?;
+    }
+    Ok((presets, bindings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saving_presets_leaves_the_config_alone() {
+        let dir = std::env::temp_dir().join(format!("oscilloscope-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let project = dir.join("oscilloscope.toml");
+        let text = "# Mine\nthickness = 0.005\n\n[[preset]]\nname = \"mine\"\ndecay = 1\n";
+        std::fs::write(&project, text).unwrap();
+        let args = Args::parse([format!("--config={}", project.display())]);
+
+        let (_, mut presets, _) = load(&args).unwrap();
+        let settings = Settings {
+            thickness: Some(0.02),
+            ..Settings::default()
+        };
+        let saved = presets.save("mine", settings).unwrap().to_path_buf();
+        assert_eq!(saved, dir.join("oscilloscope.presets.toml"));
+        assert_eq!(std::fs::read_to_string(&project).unwrap(), text);
+
+        // Next time, the saved preset replaces the config's
+        let (_, presets, _) = load(&args).unwrap();
+        assert_eq!(presets.get("mine").unwrap().settings.thickness, Some(0.02));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
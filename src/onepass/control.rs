@@ -0,0 +1,102 @@
+use std::sync::mpsc::Sender;
+
+use winit::event_loop::EventLoopProxy;
+
+use super::{
+    color::ColorScheme,
+    oscilloscope::Layout,
+    state::{FadeCurve, ViewMode},
+    visualizer::Visualizer,
+};
+
+/// Command is a change to the live window, or a question about it, that comes from outside it,
+/// eg. over OSC. Commands are sent to the event loop as user events, so they're applied between
+/// frames on its thread.
+#[derive(Clone, Debug)]
+pub enum Command {
+    /// Pauses or resumes, or toggles for `None`.
+    Pause(Option<bool>),
+    /// Draws one frame while paused.
+    Step,
+    /// Jumps every trace to this many seconds into its source.
+    Seek(f32),
+    Thickness(f32),
+    Gain(f32),
+    Brightness(f32),
+    /// How quickly traces fade, ie. the fade curve's strength.
+    Decay(f32),
+    FadeCurve(FadeCurve),
+    Color(ColorScheme),
+    View(ViewMode),
+    Layout(Layout),
+    Blanking(bool),
+    BlankingThreshold(f32),
+    DspParam {
+        node: usize,
+        param: String,
+        value: f32,
+    },
+    ShaderParam {
+        index: usize,
+        value: f32,
+    },
+    /// Shows or hides one trace.
+    Show {
+        trace: usize,
+        visible: bool,
+    },
+    /// Shows only one trace, or every trace for `None`.
+    Solo(Option<usize>),
+    /// Starts or stops recording.
+    Record(bool),
+    /// Saves a screenshot to a path, or to one named after the time.
+    Screenshot(Option<String>),
+    /// Switches to drawing with another visualizer.
+    Visualizer(Visualizer),
+    /// Sets one of the current visualizer's parameters.
+    VisualizerParam {
+        param: String,
+        value: f32,
+    },
+    /// Switches to a preset, by name or number from 1.
+    Preset(String),
+    /// Saves the current settings as a preset, by name or number from 1.
+    SavePreset(String),
+    Query(Query),
+}
+
+/// Query is a question about the live window, answered with a line of JSON.
+#[derive(Copy, Clone, Debug)]
+pub enum Query {
+    /// Settings, and where each trace has got to.
+    Status,
+    /// Each trace's levels over the last frame.
+    Levels,
+}
+
+/// Request is a Command on its way to the event loop, with somewhere to send the answer if the
+/// client is waiting for one.
+#[derive(Debug)]
+pub struct Request {
+    pub command: Command,
+    pub reply: Option<Sender<String>>,
+}
+
+/// Requests is somewhere requests can be sent from other threads, ie. the live window's event
+/// loop, or a channel for anything else that applies commands, such as a test.
+pub trait Requests: Clone + Send + 'static {
+    /// Sends `request`, returning false once there's nothing left to receive it.
+    fn send(&self, request: Request) -> bool;
+}
+
+impl Requests for EventLoopProxy<Request> {
+    fn send(&self, request: Request) -> bool {
+        self.send_event(request).is_ok()
+    }
+}
+
+impl Requests for Sender<Request> {
+    fn send(&self, request: Request) -> bool {
+        Sender::send(self, request).is_ok()
+    }
+}
@@ -0,0 +1,193 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    str::FromStr,
+};
+
+use anyhow::{bail, Context};
+
+use crate::{args::Args, dsp::Chain};
+
+use super::{
+    config, gpu::GpuOptions, offscreen::Offscreen, oscilloscope::Oscilloscope,
+    wgpu_resources::WgpuResources, Shaderer,
+};
+
+/// VideoFormat is the container written by `export`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum VideoFormat {
+    /// YUV4MPEG2 with 4:4:4 chroma, which ffmpeg reads without any extra flags.
+    Y4m,
+    /// Headerless RGBA frames, for `ffmpeg -f rawvideo -pix_fmt rgba -s WxH -r FPS -i -`.
+    Rgba,
+}
+
+impl FromStr for VideoFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "y4m" => Ok(VideoFormat::Y4m),
+            "rgba" | "raw" => Ok(VideoFormat::Rgba),
+            _ => bail!("unknown video format {:?}, expected y4m or rgba", s),
+        }
+    }
+}
+
+/// VideoWriter streams frames of tightly packed RGBA rows to `out` in a VideoFormat.
+pub struct VideoWriter<W: Write> {
+    out: W,
+    format: VideoFormat,
+    width: u32,
+    height: u32,
+    // Reused between frames to hold the Y, U and V planes
+    planes: Vec<u8>,
+}
+
+impl<W: Write> VideoWriter<W> {
+    pub fn new(
+        mut out: W,
+        format: VideoFormat,
+        width: u32,
+        height: u32,
+        fps: u32,
+    ) -> io::Result<Self> {
+        if format == VideoFormat::Y4m {
+            writeln!(
+                out,
+                "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444",
+                width, height, fps
+            )?;
+        }
+        Ok(Self {
+            out,
+            format,
+            width,
+            height,
+            planes: Vec::new(),
+        })
+    }
+
+    pub fn write_frame(&mut self, rgba: &[u8]) -> io::Result<()> {
+        debug_assert_eq!(rgba.len(), (self.width * self.height * 4) as usize);
+        match self.format {
+            VideoFormat::Rgba => self.out.write_all(rgba),
+            VideoFormat::Y4m => {
+                let pixels = rgba.len() / 4;
+                self.planes.resize(pixels * 3, 0);
+                let (y, uv) = self.planes.split_at_mut(pixels);
+                let (u, v) = uv.split_at_mut(pixels);
+                for (i, pixel) in rgba.chunks(4).enumerate() {
+                    let (r, g, b) = (pixel[0] as f32, pixel[1] as f32, pixel[2] as f32);
+                    // BT.601, limited range
+                    y[i] = (16.0 + (65.738 * r + 129.057 * g + 25.064 * b) / 256.0) as u8;
+                    u[i] = (128.0 + (-37.945 * r - 74.494 * g + 112.439 * b) / 256.0) as u8;
+                    v[i] = (128.0 + (112.439 * r - 94.154 * g - 18.285 * b) / 256.0) as u8;
+                }
+                self.out.write_all(b"FRAME\n")?;
+                self.out.write_all(&self.planes)
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+/// Renders WAV files offscreen and streams the frames as video, eg.
+///
+/// ```sh
+/// oscilloscope export --fps=60 --size=1920x1080 song.wav | ffmpeg -i - -i song.wav out.mp4
+/// ```
+///
+/// Options:
+/// - `--format=y4m|rgba` (default y4m)
+/// - `--fps=N` (default 60)
+/// - `--size=WIDTHxHEIGHT` (default 1920x1080)
+/// - `--duration=SECONDS` (default: until every source ends)
+/// - `--output=PATH` (default: stdout)
+/// - `--dsp=CHAIN`: signal processing before drawing, eg. `dc,ms`
+/// - `--config=PATH`: the project config (default `oscilloscope.toml`)
+/// - `--preset=NAME`: a preset from the config files
+/// - `--backend=LIST`, `--adapter=NAME|INDEX`, `--software`, `--power=low|high`: which GPU to
+///   render on, see `oscilloscope adapters`
+pub fn main(args: Args) -> anyhow::Result<()> {
+    let format = args.parse_value("format")?.unwrap_or(VideoFormat::Y4m);
+    let fps = args.parse_value::<u32>("fps")?.unwrap_or(60);
+    if fps == 0 {
+        bail!("--fps must be more than 0");
+    }
+    let (width, height) = args.size("size")?.unwrap_or((1920, 1080));
+    let duration = args.parse_value::<f32>("duration")?;
+    let chain = args.parse_value::<Chain>("dsp")?;
+    let gpu_options = GpuOptions::parse(&args)?;
+    if args.positional.is_empty() {
+        bail!("export needs at least one WAV file");
+    }
+
+    let out: Box<dyn Write> = match args.value("output") {
+        Some(path) => Box::new(File::create(path).with_context(|| format!("creating {}", path))?),
+        None => Box::new(io::stdout()),
+    };
+
+    let wgpu_resources = WgpuResources::new_headless(width, height, &gpu_options)?;
+    let mut offscreen = Offscreen::new(&wgpu_resources, 1)?;
+    let mut oscilloscope = Oscilloscope::with_files(wgpu_resources, &args.positional)?;
+    oscilloscope.set_fixed_frame_rate(Some(fps as f32));
+    config::configure(&mut oscilloscope, &args)?;
+    if let Some(chain) = &chain {
+        oscilloscope.set_chain(chain);
+    }
+
+    let mut writer = VideoWriter::new(BufWriter::new(out), format, width, height, fps)?;
+    let frame_count = duration.map(|duration| (duration * fps as f32).ceil() as u64);
+    let frames = render_frames(&mut oscilloscope, &mut offscreen, 0, frame_count, |rgba| {
+        writer.write_frame(&rgba)?;
+        Ok(())
+    })?;
+    writer.finish()?;
+
+    log::info!("exported {} frames", frames);
+    Ok(())
+}
+
+/// Advances `oscilloscope` by `skip` frames without drawing them, then renders `count` frames (or
+/// until every source ends) into `offscreen`, handing each one to `on_frame` as RGBA rows.
+/// Returns the number of frames rendered.
+pub(super) fn render_frames<F>(
+    oscilloscope: &mut Oscilloscope,
+    offscreen: &mut Offscreen,
+    skip: u64,
+    count: Option<u64>,
+    mut on_frame: F,
+) -> anyhow::Result<u64>
+where
+    F: FnMut(Vec<u8>) -> anyhow::Result<()>,
+{
+    for _ in 0..skip {
+        oscilloscope.update();
+    }
+
+    let mut frame = 0;
+    loop {
+        let done = match count {
+            Some(count) => frame >= count,
+            None => oscilloscope.is_finished(),
+        };
+        if done {
+            break;
+        }
+        oscilloscope.update();
+        oscilloscope.render(offscreen.view());
+        if let Some(rgba) = offscreen.read_back(&oscilloscope.wgpu_resources)? {
+            on_frame(rgba)?;
+        }
+        frame += 1;
+    }
+    for rgba in offscreen.finish(&oscilloscope.wgpu_resources)? {
+        on_frame(rgba)?;
+    }
+    Ok(frame)
+}
@@ -0,0 +1,210 @@
+//! Choosing the graphics adapter and how frames are presented, from the command line.
+
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, bail};
+
+use crate::args::Args;
+
+/// What to suggest when there's no adapter to be had, added to the error.
+const NO_ADAPTER: &str = "`oscilloscope adapters` lists the adapters there are, or try --software \
+                          for a software renderer";
+/// What to suggest when there's no software adapter either.
+const NO_SOFTWARE_ADAPTER: &str = "Mesa's lavapipe (Vulkan) or llvmpipe (GL) drivers provide one";
+
+/// PresentMode is how finished frames are shown in the window.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum PresentMode {
+    /// Waits for the display's refresh, so frames never tear.
+    #[default]
+    Vsync,
+    /// Shows the newest frame at each refresh, dropping any older one, so it doesn't tear or
+    /// hold the renderer back.
+    Mailbox,
+    /// Shows frames as soon as they're done, which can tear.
+    Immediate,
+}
+
+impl From<PresentMode> for wgpu::PresentMode {
+    fn from(present_mode: PresentMode) -> Self {
+        match present_mode {
+            PresentMode::Vsync => wgpu::PresentMode::Fifo,
+            PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+            PresentMode::Immediate => wgpu::PresentMode::Immediate,
+        }
+    }
+}
+
+impl FromStr for PresentMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "vsync" => Ok(PresentMode::Vsync),
+            "mailbox" => Ok(PresentMode::Mailbox),
+            "immediate" => Ok(PresentMode::Immediate),
+            _ => bail!(
+                "unknown present mode {:?}, expected vsync, mailbox or immediate",
+                s
+            ),
+        }
+    }
+}
+
+/// Backends is a set of graphics APIs to look for adapters on, eg. `vulkan,gl`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Backends(pub wgpu::Backends);
+
+impl Default for Backends {
+    fn default() -> Self {
+        Self(wgpu::Backends::all())
+    }
+}
+
+impl FromStr for Backends {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut backends = wgpu::Backends::empty();
+        for name in s.split(',').map(str::trim) {
+            backends |= match name {
+                "vulkan" => wgpu::Backends::VULKAN,
+                "metal" => wgpu::Backends::METAL,
+                "dx12" => wgpu::Backends::DX12,
+                "dx11" => wgpu::Backends::DX11,
+                "gl" => wgpu::Backends::GL,
+                "primary" => wgpu::Backends::PRIMARY,
+                "all" => wgpu::Backends::all(),
+                _ => bail!(
+                    "unknown backend {:?}, expected vulkan, metal, dx12, dx11, gl, primary or all",
+                    name
+                ),
+            };
+        }
+        Ok(Self(backends))
+    }
+}
+
+/// Power is which kind of GPU to prefer when there's more than one.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Power(pub wgpu::PowerPreference);
+
+impl FromStr for Power {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "low" => Ok(Self(wgpu::PowerPreference::LowPower)),
+            "high" => Ok(Self(wgpu::PowerPreference::HighPerformance)),
+            _ => bail!("unknown power preference {:?}, expected low or high", s),
+        }
+    }
+}
+
+/// GpuOptions are the command line options for picking an adapter and presenting frames.
+#[derive(Clone, Debug, Default)]
+pub struct GpuOptions {
+    pub backends: Backends,
+    /// An adapter's index in `oscilloscope adapters`, or part of its name, eg. `--adapter=intel`.
+    pub adapter: Option<String>,
+    /// Asks for the fallback adapter, which is usually a software renderer.
+    pub software: bool,
+    pub power: Power,
+    pub present_mode: PresentMode,
+}
+
+impl GpuOptions {
+    pub fn parse(args: &Args) -> anyhow::Result<Self> {
+        let options = Self {
+            backends: args.parse_value("backend")?.unwrap_or_default(),
+            adapter: args.value("adapter").map(str::to_string),
+            software: args.flag("software"),
+            power: args.parse_value("power")?.unwrap_or_default(),
+            present_mode: args.parse_value("present")?.unwrap_or_default(),
+        };
+        if options.adapter.is_some() && options.software {
+            bail!("--adapter and --software can't be used together");
+        }
+        Ok(options)
+    }
+
+    /// Picks an adapter, which has to be able to draw to `surface` if there is one.
+    pub fn adapter(
+        &self,
+        instance: &wgpu::Instance,
+        surface: Option<&wgpu::Surface>,
+    ) -> anyhow::Result<wgpu::Adapter> {
+        let choice = match &self.adapter {
+            Some(choice) => choice,
+            None => {
+                let request = instance.request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: self.power.0,
+                    compatible_surface: surface,
+                    force_fallback_adapter: self.software,
+                });
+                return pollster::block_on(request).ok_or_else(|| match (self.software, surface) {
+                    (true, _) => anyhow!("no software adapter found, {}", NO_SOFTWARE_ADAPTER),
+                    (false, Some(_)) => {
+                        anyhow!("no graphics adapter can draw to the window, {}", NO_ADAPTER)
+                    }
+                    (false, None) => anyhow!("no graphics adapter found, {}", NO_ADAPTER),
+                });
+            }
+        };
+
+        let mut adapters = instance
+            .enumerate_adapters(self.backends.0)
+            .collect::<Vec<_>>();
+        let found = match choice.parse::<usize>() {
+            Ok(index) if index < adapters.len() => Some(adapters.swap_remove(index)),
+            Ok(_) => None,
+            Err(_) => {
+                let name = choice.to_lowercase();
+                adapters
+                    .into_iter()
+                    .find(|adapter| adapter.get_info().name.to_lowercase().contains(&name))
+            }
+        };
+        let adapter = found.ok_or_else(|| anyhow!("no adapter {:?}, {}", choice, NO_ADAPTER))?;
+        if let Some(surface) = surface {
+            if !adapter.is_surface_supported(surface) {
+                bail!("{} can't draw to the window", Info(&adapter));
+            }
+        }
+        Ok(adapter)
+    }
+}
+
+/// Info describes an adapter in a line, eg. `llvmpipe (LLVM 12.0.0, 256 bits) (Vulkan, Cpu)`.
+pub struct Info<'a>(pub &'a wgpu::Adapter);
+
+impl fmt::Display for Info<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let info = self.0.get_info();
+        write!(
+            f,
+            "{} ({:?}, {:?})",
+            info.name, info.backend, info.device_type
+        )
+    }
+}
+
+/// Lists the adapters on `--backend`, numbered for `--adapter`.
+///
+/// ```sh
+/// oscilloscope adapters --backend=vulkan
+/// ```
+pub fn main(args: Args) -> anyhow::Result<()> {
+    let options = GpuOptions::parse(&args)?;
+    let instance = wgpu::Instance::new(options.backends.0);
+    let adapters = instance
+        .enumerate_adapters(options.backends.0)
+        .collect::<Vec<_>>();
+    if adapters.is_empty() {
+        bail!("no adapters found, {}", NO_SOFTWARE_ADAPTER);
+    }
+    for (i, adapter) in adapters.iter().enumerate() {
+        println!("{}: {}", i, Info(adapter));
+    }
+    Ok(())
+}
@@ -1,98 +1,839 @@
+mod bindings;
+pub mod clip;
+mod color;
+mod config;
+mod control;
+pub mod export;
+pub mod gpu;
+mod offscreen;
+mod osc;
 mod oscilloscope;
+mod overlay;
+mod remote;
+mod shader_source;
+mod spectrum;
 mod state;
+mod visualizer;
+mod waveform;
 mod wgpu_resources;
 
+pub use self::{
+    color::ColorScheme,
+    config::Settings,
+    oscilloscope::{Layout, Oscilloscope},
+    state::{FadeCurve, State, ViewMode},
+    visualizer::{Audio, Param, SharedAudio, TraceAudio, Visualizer},
+    wgpu_resources::WgpuResources,
+};
+
+use std::{
+    path::PathBuf,
+    sync::mpsc::{self, RecvTimeoutError},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Context};
+use itertools::Itertools;
 use winit::{
-    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event::{ElementState, Event, KeyboardInput, ModifiersState, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
-    window::WindowBuilder,
+    window::{Window, WindowBuilder},
 };
 
-use self::{oscilloscope::Oscilloscope, wgpu_resources::WgpuResources};
+use crate::{
+    args::Args,
+    dsp::Chain,
+    sound::{Source, WavFormat},
+};
 
-pub fn main() {
-    env_logger::init();
-    let event_loop = EventLoop::new();
-    let window = WindowBuilder::new().build(&event_loop).unwrap();
+use self::{
+    bindings::{Action, Bindings, Setting, Trigger},
+    config::Presets,
+    control::{Command, Query, Request, Requests},
+    gpu::GpuOptions,
+    offscreen::Offscreen,
+    remote::{json_number, json_string},
+};
+
+/// Thickness and brightness are scaled by this much per step from the keyboard.
+const ADJUST_STEP: f32 = 1.25;
+/// How much the fade curve's strength changes per step from the keyboard.
+const DECAY_STEP: f32 = 0.5;
+/// How far traces move per step when panning, where the screen is 2 wide.
+const PAN_STEP: f32 = 0.05;
+/// How much traces are scaled per step of the mouse wheel.
+const ZOOM_STEP: f32 = 1.1;
+/// How often frames are drawn when running headless, which has no display to keep time.
+const HEADLESS_FRAME_TIME: Duration = Duration::from_micros(16_667);
 
-    let wgpu_resources = wgpu_resources::WgpuResources::new(&window);
+pub fn main(args: Args) -> anyhow::Result<()> {
+    let gpu_options = GpuOptions::parse(&args)?;
+    let event_loop = EventLoop::with_user_event();
+    let window = WindowBuilder::new()
+        .build(&event_loop)
+        .context("opening a window")?;
 
-    let mut oscilloscope = Oscilloscope::new(wgpu_resources);
+    let wgpu_resources = WgpuResources::new(&window, &gpu_options)?;
+
+    // Each file given on the command line becomes its own trace
+    let oscilloscope = if args.positional.is_empty() {
+        Oscilloscope::new(wgpu_resources, SharedAudio::default())?
+    } else {
+        Oscilloscope::with_files(wgpu_resources, &args.positional)?
+    };
+    run(event_loop, window, oscilloscope, &args)
+}
 
-    let mut paused = false;
+/// Shows sources other than WAV files, such as generators, live in a window.
+pub fn preview(args: &Args, sources: Vec<Box<dyn Source>>) -> anyhow::Result<()> {
+    let gpu_options = GpuOptions::parse(args)?;
+    let event_loop = EventLoop::with_user_event();
+    let window = WindowBuilder::new()
+        .build(&event_loop)
+        .context("opening a window")?;
+
+    let wgpu_resources = WgpuResources::new(&window, &gpu_options)?;
+    let oscilloscope = Oscilloscope::with_sources(wgpu_resources, sources)?;
+    run(event_loop, window, oscilloscope, args)
+}
+
+/// Runs the live window until it's closed. Only fails while setting up, since once the window
+/// is running, problems are reported and it carries on.
+fn run(
+    event_loop: EventLoop<Request>,
+    window: Window,
+    oscilloscope: Oscilloscope,
+    args: &Args,
+) -> anyhow::Result<()> {
+    let mut session = Session::new(oscilloscope, args, event_loop.create_proxy())?;
+    // Held with a key or the mouse wheel, modifiers pick between bindings, eg. Shift+1
+    let mut modifiers = ModifiersState::default();
 
     event_loop.run(move |event, _, control_flow| match event {
         Event::WindowEvent {
             ref event,
             window_id,
-        } if window_id == window.id() => match event {
-            WindowEvent::CloseRequested
-            | WindowEvent::KeyboardInput {
-                input:
-                    KeyboardInput {
-                        state: ElementState::Pressed,
-                        virtual_keycode: Some(VirtualKeyCode::Escape),
-                        ..
-                    },
-                ..
-            } => *control_flow = ControlFlow::Exit,
-            WindowEvent::Resized(_physical_size) => {
-                // TODO
-                todo!();
-            }
-            WindowEvent::ScaleFactorChanged {
-                new_inner_size: _, ..
-            } => {
-                // TODO
-                todo!()
+        } if window_id == window.id() && !session.input(event) => match event {
+            WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+            WindowEvent::Resized(size) => session.resize(size.width, size.height),
+            WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                session.resize(new_inner_size.width, new_inner_size.height)
             }
+            WindowEvent::ModifiersChanged(state) => modifiers = *state,
             WindowEvent::KeyboardInput {
                 input:
                     KeyboardInput {
                         state: ElementState::Pressed,
-                        virtual_keycode: keycode,
+                        virtual_keycode: Some(key),
                         ..
                     },
                 ..
-            } => match keycode {
-                Some(VirtualKeyCode::Space) => {
-                    paused = !paused;
+            } => session.trigger(Trigger::key(*key, modifiers), control_flow),
+            WindowEvent::MouseWheel { delta, .. } => {
+                if let Some(trigger) = Trigger::scroll(delta, modifiers) {
+                    session.trigger(trigger, control_flow);
                 }
-                Some(VirtualKeyCode::J) => {
-                    window.request_redraw();
-                }
-                _ => {}
-            },
+            }
             _ => {}
         },
         Event::RedrawRequested(window_id) if window_id == window.id() => {
-            let WgpuResources {
-                surface,
-                device,
-                config,
-                ..
-            } = &oscilloscope.wgpu_resources;
-
-            let frame = oscilloscope.wgpu_resources.frame();
+            let frame = match session.oscilloscope.wgpu_resources.frame() {
+                Ok(frame) => frame,
+                Err(wgpu::SurfaceError::OutOfMemory) => {
+                    eprintln!("error: out of memory for the window's frames");
+                    *control_flow = ControlFlow::Exit;
+                    return;
+                }
+                // Timeouts and the like pass, so the frame is just skipped
+                Err(e) => {
+                    log::warn!("skipping a frame: {}", e);
+                    return;
+                }
+            };
             let view = &frame
                 .texture
                 .create_view(&wgpu::TextureViewDescriptor::default());
-
-            oscilloscope.update();
-            oscilloscope.render(view);
+            session.draw(view);
             frame.present();
         }
-        Event::MainEventsCleared => {
-            if !paused {
-                window.request_redraw();
-            }
-        }
+        Event::MainEventsCleared if session.is_running() => window.request_redraw(),
+        Event::UserEvent(request) => session.handle(request),
+        Event::LoopDestroyed => session.finish(),
         _ => {}
     })
 }
 
+/// Runs the scope offscreen without a window, taking commands from `--control` and `--osc`
+/// until nothing's left to send them, ie. until stdin is closed if that's all there is. This is
+/// for driving it from scripts and tests, on machines without a display.
+///
+/// ```sh
+/// printf 'thickness 0.01\nstatus\n' | oscilloscope headless --control=stdin song.wav
+/// ```
+///
+/// Takes the live window's options, along with:
+///
+/// - `--size=WIDTHxHEIGHT`: the size frames are drawn at (default 512x512)
+pub fn headless(args: Args) -> anyhow::Result<()> {
+    let gpu_options = GpuOptions::parse(&args)?;
+    let (width, height) = args.size("size")?.unwrap_or((512, 512));
+    if !args.flag("control") && !args.flag("osc") {
+        bail!("headless needs --control or --osc to take commands from");
+    }
+    let wgpu_resources = WgpuResources::new_headless(width, height, &gpu_options)?;
+    let oscilloscope = if args.positional.is_empty() {
+        Oscilloscope::new(wgpu_resources, SharedAudio::default())?
+    } else {
+        Oscilloscope::with_files(wgpu_resources, &args.positional)?
+    };
+
+    // Listeners each hold a sender, so once they've all finished the channel disconnects
+    let (requests, received) = mpsc::channel();
+    let mut session = Session::new(oscilloscope, &args, requests)?;
+    let offscreen = Offscreen::new(&session.oscilloscope.wgpu_resources, 1)?;
+    let mut next_frame = Instant::now();
+    loop {
+        match received.recv_timeout(next_frame.saturating_duration_since(Instant::now())) {
+            Ok(request) => session.handle(request),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        let now = Instant::now();
+        if now >= next_frame {
+            if session.is_running() {
+                session.draw(offscreen.view());
+            }
+            next_frame = (next_frame + HEADLESS_FRAME_TIME).max(now);
+        }
+    }
+    session.finish();
+    Ok(())
+}
+
+/// RunOptions are the command line options for the live window.
+struct RunOptions {
+    /// Screenshots can be supersampled beyond the window's resolution, eg. --screenshot-scale=2
+    screenshot_scale: u32,
+    view_mode: Option<ViewMode>,
+    /// The color scheme for every trace, eg. --color=amber
+    color: Option<ColorScheme>,
+    chain: Option<Chain>,
+    /// Where to load shaders from instead of the built-in ones, reloading them as they change.
+    shader_dir: Option<PathBuf>,
+    /// A custom render shader for the traces, reloaded as it changes like those in shader_dir.
+    trace_shader: Option<PathBuf>,
+    /// Initial values of the custom shader's free parameters, eg. --shader-params=0.5,2
+    shader_params: Vec<f32>,
+    /// Where to take text commands from: stdin, or a Unix socket to create at this path.
+    control: Option<String>,
+    /// Where to listen for OSC, eg. --osc=9000 for port 9000 on localhost.
+    osc: Option<String>,
+    record_format: WavFormat,
+    /// Recordings stop by themselves after this many seconds, if set.
+    record_duration: Option<f32>,
+    /// What to draw with instead of the beam in the current view, eg. --visualizer=spectrum
+    visualizer: Option<Visualizer>,
+}
+
+impl RunOptions {
+    fn parse(args: &Args) -> anyhow::Result<Self> {
+        Ok(Self {
+            screenshot_scale: args.parse_value("screenshot-scale")?.unwrap_or(1).max(1),
+            view_mode: args.parse_value("view")?,
+            color: args.parse_value("color")?,
+            chain: args.parse_value("dsp")?,
+            shader_dir: args.parse_value("shader-dir")?,
+            trace_shader: args.parse_value("trace-shader")?,
+            shader_params: args
+                .value("shader-params")
+                .map(|params| {
+                    params
+                        .split(',')
+                        .map(|param| {
+                            param
+                                .trim()
+                                .parse()
+                                .with_context(|| format!("invalid shader parameter {:?}", param))
+                        })
+                        .collect()
+                })
+                .transpose()?
+                .unwrap_or_default(),
+            control: args.value("control").map(str::to_string),
+            // A bare port is only listened for locally
+            osc: args
+                .value("osc")
+                .map(|address| match address.parse::<u16>() {
+                    Ok(port) => format!("127.0.0.1:{}", port),
+                    Err(_) => address.to_string(),
+                }),
+            record_format: args.parse_value("record-format")?.unwrap_or_default(),
+            record_duration: args.parse_value("record-duration")?,
+            visualizer: args.parse_value("visualizer")?,
+        })
+    }
+}
+
+/// Session is the live window, or a headless scope, along with everything about it that bindings
+/// and commands from outside can change.
+struct Session {
+    oscilloscope: Oscilloscope,
+    options: RunOptions,
+    presets: Presets,
+    bindings: Bindings,
+    paused: bool,
+    // Set by a step while paused, until the frame is drawn
+    step: bool,
+    // The node of the processing chain that's nudged from the keyboard
+    selected_node: usize,
+    hud: bool,
+    visualizer: Visualizer,
+    // What's drawn with instead of the oscilloscope, for visualizers other than the beam
+    shaderer: Option<Box<dyn Shaderer>>,
+}
+
+impl Session {
+    /// Sets `oscilloscope` up from the config files and command line, and starts listening for
+    /// commands, which are sent to `requests`.
+    fn new(
+        mut oscilloscope: Oscilloscope,
+        args: &Args,
+        requests: impl Requests,
+    ) -> anyhow::Result<Self> {
+        let options = RunOptions::parse(args)?;
+        // Settings from the command line go on top of the config's
+        let (presets, bindings) = config::configure(&mut oscilloscope, args)?;
+        if let Some(view_mode) = options.view_mode {
+            oscilloscope.set_view_mode(view_mode);
+        }
+        if let Some(color_scheme) = options.color {
+            oscilloscope.set_color_scheme(color_scheme);
+        }
+        if let Some(chain) = &options.chain {
+            oscilloscope.set_chain(chain);
+        }
+        if let Some(dir) = &options.shader_dir {
+            oscilloscope.watch_shaders(dir);
+        }
+        if let Some(path) = &options.trace_shader {
+            oscilloscope.use_trace_shader(path);
+        }
+        for (i, &value) in options.shader_params.iter().enumerate() {
+            oscilloscope.set_shader_param(i, value)?;
+        }
+        if let Some(path) = args.value("record") {
+            let paths = oscilloscope.start_recording(
+                path,
+                options.record_format,
+                options.record_duration,
+            )?;
+            eprintln!("recording: {}", paths.join(", "));
+        }
+
+        if let Some(address) = &options.osc {
+            let address = osc::listen(address, requests.clone())?;
+            eprintln!("listening for OSC on {}", address);
+        }
+        if let Some(target) = &options.control {
+            remote::listen(target, requests)?;
+        }
+
+        let view_mode = oscilloscope.traces()[0].view_mode();
+        let mut session = Session {
+            oscilloscope,
+            options,
+            presets,
+            bindings,
+            paused: false,
+            step: false,
+            selected_node: 0,
+            hud: false,
+            visualizer: Visualizer::for_view(view_mode),
+            shaderer: None,
+        };
+        if let Some(visualizer) = session.options.visualizer {
+            session.set_visualizer(visualizer)?;
+        }
+        Ok(session)
+    }
+
+    /// Whether frames should be drawn, ie. it isn't paused, or it's stepping.
+    fn is_running(&self) -> bool {
+        !self.paused || self.step
+    }
+
+    /// Moves the traces on and draws them into `view`, with whichever visualizer is current.
+    fn draw(&mut self, view: &wgpu::TextureView) {
+        self.step = false;
+        self.oscilloscope.update();
+        match &mut self.shaderer {
+            Some(shaderer) => {
+                shaderer.update();
+                shaderer.render(view);
+                self.oscilloscope.render_overlays(view);
+            }
+            None => self.oscilloscope.render(view),
+        }
+    }
+
+    /// Applies a command from outside, answering it if the client is waiting for an answer.
+    fn handle(&mut self, Request { command, reply }: Request) {
+        let answer = self.apply(command);
+        match reply {
+            // The client may have gone, which is its business
+            Some(reply) => drop(reply.send(remote::answer(answer))),
+            None => {
+                if let Err(e) = answer {
+                    eprintln!("control: {:#}", e);
+                }
+            }
+        }
+        self.update_hud();
+    }
+
+    /// Finishes any recordings, which need it to be readable.
+    fn finish(&mut self) {
+        match self.oscilloscope.stop_recording() {
+            Ok(paths) if !paths.is_empty() => eprintln!("saved recording: {}", paths.join(", ")),
+            Ok(_) => {}
+            Err(e) => eprintln!("failed to save recording: {:?}", e),
+        }
+    }
+
+    /// Does whatever `trigger` is bound to, if anything.
+    fn trigger(&mut self, trigger: Trigger, control_flow: &mut ControlFlow) {
+        let action = match self.bindings.action(&trigger) {
+            Some(action) => action.clone(),
+            None => return,
+        };
+        if let Err(e) = self.perform(action, control_flow) {
+            eprintln!("{}: {:#}", trigger, e);
+        }
+        self.update_hud();
+    }
+
+    fn perform(&mut self, action: Action, control_flow: &mut ControlFlow) -> anyhow::Result<()> {
+        let oscilloscope = &mut self.oscilloscope;
+        match action {
+            Action::Quit => *control_flow = ControlFlow::Exit,
+            Action::ToggleHud => self.hud = !self.hud,
+            Action::CycleFade => {
+                let fade_curve = oscilloscope.cycle_fade_curve();
+                eprintln!("fade curve: {:?}", fade_curve);
+            }
+            Action::CycleColor => {
+                let color_scheme = oscilloscope.cycle_color_scheme();
+                eprintln!("color scheme: {}", color_scheme.name);
+            }
+            Action::CycleView => {
+                let view_mode = oscilloscope.cycle_view_mode();
+                self.follow_view(view_mode);
+                eprintln!("view mode: {:?}", view_mode);
+            }
+            Action::CycleVisualizer => {
+                let visualizer = self.visualizer.next();
+                self.set_visualizer(visualizer)?;
+                eprintln!("visualizer: {}", visualizer);
+            }
+            Action::CycleLayout => {
+                let layout = oscilloscope.cycle_layout();
+                eprintln!("layout: {:?}", layout);
+            }
+            Action::ToggleBlanking => {
+                let hard_blanking = oscilloscope.toggle_hard_blanking();
+                eprintln!("hard blanking: {}", hard_blanking);
+            }
+            Action::ToggleRecording => toggle_recording(oscilloscope, &self.options)?,
+            Action::Adjust { setting, up } => self.adjust(setting, up),
+            Action::Pan { x, y } => oscilloscope.pan(x * PAN_STEP, y * PAN_STEP),
+            Action::Zoom { zoom_in } => {
+                oscilloscope.zoom(if zoom_in { ZOOM_STEP } else { 1.0 / ZOOM_STEP });
+                eprintln!("gain: {:.2}", oscilloscope.traces()[0].gain());
+            }
+            Action::ResetView => oscilloscope.reset_view(),
+            Action::SelectNode { next } => {
+                let count = oscilloscope.chain().nodes.len();
+                if count == 0 {
+                    bail!("no dsp nodes, add some with --dsp");
+                }
+                self.selected_node = if next {
+                    (self.selected_node + 1) % count
+                } else {
+                    (self.selected_node + count - 1) % count
+                };
+                let name = oscilloscope.chain().nodes[self.selected_node].name();
+                eprintln!("dsp: selected {} ({})", self.selected_node + 1, name);
+            }
+            Action::NudgeNode { up } => {
+                let (param, value) = oscilloscope.nudge_chain_param(self.selected_node, up)?;
+                eprintln!("dsp: {} = {:.2}", param, value);
+            }
+            Action::Command(command) => {
+                if let Some(answer) = self.apply(command)? {
+                    eprintln!("{}", answer);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Steps a setting up or down, on every trace.
+    fn adjust(&mut self, setting: Setting, up: bool) {
+        let oscilloscope = &mut self.oscilloscope;
+        let trace = &oscilloscope.traces()[0];
+        let scale = if up { ADJUST_STEP } else { 1.0 / ADJUST_STEP };
+        match setting {
+            Setting::Thickness => {
+                let thickness = trace.uniforms.line_thickness * scale;
+                oscilloscope.set_line_thickness(thickness);
+                eprintln!("thickness: {:.4}", thickness);
+            }
+            Setting::Brightness => {
+                let brightness = trace.brightness() * scale;
+                oscilloscope.set_brightness(brightness);
+                eprintln!("brightness: {:.2}", brightness);
+            }
+            // Decay can be 0, which no amount of scaling gets it back from
+            Setting::Decay => {
+                let step = if up { DECAY_STEP } else { -DECAY_STEP };
+                let decay = (trace.uniforms.fade_strength + step).max(0.0);
+                oscilloscope.set_fade_strength(decay);
+                eprintln!("decay: {:.1}", decay);
+            }
+        }
+    }
+
+    /// Applies a command from outside the window, eg. from OSC, returning the answer to a query.
+    fn apply(&mut self, command: Command) -> anyhow::Result<Option<String>> {
+        let oscilloscope = &mut self.oscilloscope;
+        match command {
+            Command::Pause(pause) => {
+                let paused = pause.unwrap_or(!self.paused);
+                if self.paused && !paused {
+                    oscilloscope.resume();
+                }
+                self.paused = paused;
+            }
+            Command::Step => {
+                if self.paused {
+                    oscilloscope.resume();
+                }
+                self.step = true;
+            }
+            Command::Seek(seconds) => oscilloscope.seek(seconds)?,
+            Command::Thickness(thickness) => oscilloscope.set_line_thickness(thickness),
+            Command::Gain(gain) => oscilloscope.set_gain(gain),
+            Command::Brightness(brightness) => oscilloscope.set_brightness(brightness),
+            Command::Decay(decay) => oscilloscope.set_fade_strength(decay),
+            Command::FadeCurve(fade_curve) => oscilloscope.set_fade_curve(fade_curve),
+            Command::Color(color_scheme) => oscilloscope.set_color_scheme(color_scheme),
+            Command::View(view_mode) => {
+                oscilloscope.set_view_mode(view_mode);
+                self.follow_view(view_mode);
+            }
+            Command::Layout(layout) => oscilloscope.set_layout(layout),
+            Command::Blanking(hard_blanking) => oscilloscope.set_hard_blanking(hard_blanking),
+            Command::BlankingThreshold(threshold) => oscilloscope.set_blanking_threshold(threshold),
+            Command::DspParam { node, param, value } => {
+                oscilloscope.set_chain_param(node, &param, value)?
+            }
+            Command::ShaderParam { index, value } => oscilloscope.set_shader_param(index, value)?,
+            Command::Show { trace, visible } => oscilloscope.set_visible(trace, visible)?,
+            Command::Solo(trace) => oscilloscope.solo(trace)?,
+            Command::Record(record) => {
+                if record != oscilloscope.is_recording() {
+                    toggle_recording(oscilloscope, &self.options)?;
+                }
+            }
+            Command::Screenshot(path) => {
+                let shaderer = self.shaderer.as_deref();
+                let scale = self.options.screenshot_scale;
+                let path = save_screenshot(oscilloscope, shaderer, scale, path)?;
+                eprintln!("saved screenshot: {}", path);
+            }
+            Command::Visualizer(visualizer) => self.set_visualizer(visualizer)?,
+            Command::VisualizerParam { param, value } => match &mut self.shaderer {
+                Some(shaderer) => shaderer.set_param(&param, value)?,
+                None => oscilloscope.set_param(&param, value)?,
+            },
+            Command::Preset(key) => load_preset(oscilloscope, &self.presets, &key)?,
+            Command::SavePreset(key) => save_preset(oscilloscope, &mut self.presets, &key)?,
+            Command::Query(Query::Status) => {
+                let params = self.params();
+                let status = status(&self.oscilloscope, self.paused, self.visualizer, &params);
+                return Ok(Some(status));
+            }
+            Command::Query(Query::Levels) => return Ok(Some(levels(oscilloscope))),
+        }
+        Ok(None)
+    }
+
+    /// Offers an event from the window to whatever's drawing, returning whether it was used.
+    fn input(&mut self, event: &WindowEvent) -> bool {
+        match &mut self.shaderer {
+            Some(shaderer) => shaderer.input(event),
+            None => self.oscilloscope.input(event),
+        }
+    }
+
+    /// Switches to drawing with `visualizer`.
+    fn set_visualizer(&mut self, visualizer: Visualizer) -> anyhow::Result<()> {
+        self.shaderer = visualizer.create(&mut self.oscilloscope)?;
+        self.visualizer = visualizer;
+        Ok(())
+    }
+
+    /// Keeps the visualizer in step with the beam's view, while the beam is what's drawn.
+    fn follow_view(&mut self, view_mode: ViewMode) {
+        if self.shaderer.is_none() {
+            self.visualizer = Visualizer::for_view(view_mode);
+        }
+    }
+
+    /// The parameters of whatever's drawing.
+    fn params(&self) -> Vec<Param> {
+        match &self.shaderer {
+            Some(shaderer) => shaderer.params(),
+            None => self.oscilloscope.params(),
+        }
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        self.oscilloscope.resize(width, height);
+        if let Some(shaderer) = &mut self.shaderer {
+            shaderer.resize(width, height);
+        }
+        // The HUD's lines are packed to the window's width
+        self.update_hud();
+    }
+
+    /// Redraws the HUD with the current settings, or hides it if it's off.
+    fn update_hud(&mut self) {
+        if !self.hud {
+            self.oscilloscope.set_hud(None);
+            return;
+        }
+        let text = self.hud_text();
+        self.oscilloscope.set_hud(Some(&text));
+    }
+
+    fn hud_text(&self) -> String {
+        let oscilloscope = &self.oscilloscope;
+        let trace = &oscilloscope.traces()[0];
+        let [pan_x, pan_y] = trace.pan();
+        let node = match oscilloscope.chain().nodes.get(self.selected_node) {
+            Some(node) => format!("{} ({})", self.selected_node + 1, node.name()),
+            None => String::from("none"),
+        };
+        let state = match (self.paused, oscilloscope.is_recording()) {
+            (true, true) => "paused, recording",
+            (true, false) => "paused",
+            (false, true) => "recording",
+            (false, false) => "playing",
+        };
+        let mut lines = vec![
+            format!("thickness   {:.4}", trace.uniforms.line_thickness),
+            format!("brightness  {:.2}", trace.brightness()),
+            format!("decay       {:.1}", trace.uniforms.fade_strength),
+            format!("fade        {}", trace.fade_curve()),
+            format!("gain        {:.2}", trace.gain()),
+            format!("pan         {:.2}, {:.2}", pan_x, pan_y),
+            format!("view        {}", trace.view_mode()),
+            format!("layout      {}", oscilloscope.layout()),
+            format!("color       {}", trace.color_scheme()),
+            format!(
+                "blanking    {}",
+                if trace.hard_blanking() { "on" } else { "off" }
+            ),
+            format!("dsp node    {}", node),
+            format!("visualizer  {}", self.visualizer),
+        ];
+        if self.shaderer.is_some() {
+            lines.extend(
+                self.params()
+                    .iter()
+                    .map(|param| format!("  {:<10}{:.3}", param.name, param.value)),
+            );
+        }
+        lines.push(state.to_string());
+        lines.push(String::new());
+
+        // Bindings are packed onto as few lines as they fit on, so they all fit on screen
+        let columns = oscilloscope.hud_columns();
+        let mut line = String::new();
+        for binding in self.bindings.iter() {
+            let entry = format!("{} {}", binding.trigger, binding.text);
+            if !line.is_empty() && line.len() + 3 + entry.len() > columns {
+                lines.push(std::mem::take(&mut line));
+            }
+            if !line.is_empty() {
+                line += "   ";
+            }
+            line += &entry;
+        }
+        lines.push(line);
+        lines.join("\n")
+    }
+}
+
+/// Switches to a preset, by name or number from 1.
+fn load_preset(
+    oscilloscope: &mut Oscilloscope,
+    presets: &Presets,
+    key: &str,
+) -> anyhow::Result<()> {
+    let preset = presets.get(key)?;
+    oscilloscope.apply_settings(&preset.settings)?;
+    eprintln!("preset: {}", preset.name);
+    Ok(())
+}
+
+/// Saves the current settings as a preset. A number saves over the preset it picks, keeping its
+/// name, or adds a new preset if there isn't one.
+fn save_preset(
+    oscilloscope: &Oscilloscope,
+    presets: &mut Presets,
+    key: &str,
+) -> anyhow::Result<()> {
+    let name = match key.parse::<usize>() {
+        Ok(number) => presets.numbered(number).map_or_else(
+            || format!("preset {}", number),
+            |preset| preset.name.clone(),
+        ),
+        Err(_) => key.to_string(),
+    };
+    let path = presets.save(&name, oscilloscope.settings())?;
+    eprintln!("saved preset {:?} to {}", name, path.display());
+    Ok(())
+}
+
+/// The settings and where each trace has got to, as a line of JSON.
+fn status(
+    oscilloscope: &Oscilloscope,
+    paused: bool,
+    visualizer: Visualizer,
+    params: &[Param],
+) -> String {
+    let first = &oscilloscope.traces()[0];
+    let traces = oscilloscope
+        .traces()
+        .iter()
+        .map(|trace| {
+            format!(
+                r#"{{"position": {}, "visible": {}, "finished": {}, "color": {}}}"#,
+                json_number(trace.position()),
+                trace.is_visible(),
+                trace.is_finished(),
+                json_string(trace.color_scheme().name)
+            )
+        })
+        .join(", ");
+    let params = params
+        .iter()
+        .map(|param| format!("{}: {}", json_string(param.name), json_number(param.value)))
+        .join(", ");
+    format!(
+        r#"{{"paused": {}, "visualizer": {}, "params": {{{}}}, "view": {}, "layout": {}, "fade": {}, "decay": {}, "thickness": {}, "gain": {}, "brightness": {}, "blanking": {}, "blanking_threshold": {}, "recording": {}, "traces": [{}]}}"#,
+        paused,
+        json_string(&visualizer.to_string()),
+        params,
+        json_string(&first.view_mode().to_string()),
+        json_string(&oscilloscope.layout().to_string()),
+        json_string(&first.fade_curve().to_string()),
+        json_number(first.uniforms.fade_strength),
+        json_number(first.uniforms.line_thickness),
+        json_number(first.gain()),
+        json_number(first.brightness()),
+        first.hard_blanking(),
+        json_number(first.uniforms.blanking_threshold),
+        oscilloscope.is_recording(),
+        traces
+    )
+}
+
+/// Each trace's RMS on X, Y and Z and its peak over the last frame, as a line of JSON.
+fn levels(oscilloscope: &Oscilloscope) -> String {
+    let traces = oscilloscope
+        .traces()
+        .iter()
+        .map(|trace| {
+            let [x, y, z, peak] = trace.levels().map(json_number);
+            format!(r#"{{"rms": [{}, {}, {}], "peak": {}}}"#, x, y, z, peak)
+        })
+        .join(", ");
+    format!(r#"{{"traces": [{}]}}"#, traces)
+}
+
+/// Starts recording to a WAV in the working directory, named after the time it started, or
+/// finishes the recording that's going.
+fn toggle_recording(oscilloscope: &mut Oscilloscope, options: &RunOptions) -> anyhow::Result<()> {
+    if oscilloscope.is_recording() {
+        let paths = oscilloscope
+            .stop_recording()
+            .context("saving the recording")?;
+        eprintln!("saved recording: {}", paths.join(", "));
+        return Ok(());
+    }
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+    let path = format!("recording-{}.wav", timestamp);
+    let paths = oscilloscope
+        .start_recording(&path, options.record_format, options.record_duration)
+        .context("starting a recording")?;
+    eprintln!("recording: {}", paths.join(", "));
+    Ok(())
+}
+
+/// Saves the current frame to `path`, or to a PNG in the working directory named after the time
+/// it was taken. It's drawn by `shaderer` if another visualizer is showing, or else by
+/// `oscilloscope`.
+fn save_screenshot(
+    oscilloscope: &Oscilloscope,
+    shaderer: Option<&dyn Shaderer>,
+    scale: u32,
+    path: Option<String>,
+) -> anyhow::Result<String> {
+    let path = match path {
+        Some(path) => path,
+        None => {
+            let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+            format!("screenshot-{}.png", timestamp)
+        }
+    };
+    let image = match shaderer {
+        Some(shaderer) => {
+            let mut offscreen = Offscreen::new(&oscilloscope.wgpu_resources, scale)?;
+            shaderer.render(offscreen.view());
+            offscreen.image(&oscilloscope.wgpu_resources)?
+        }
+        None => oscilloscope.screenshot(scale)?,
+    };
+    image
+        .save(&path)
+        .with_context(|| format!("writing {}", path))?;
+    Ok(path)
+}
+
+/// Shaderer is something the live window can draw with, like the oscilloscope itself or one of
+/// the other visualizers. Each frame it's updated and then rendered into the window's frame.
 pub trait Shaderer {
-    fn new(wgpu_resources: WgpuResources) -> Self;
+    /// Sets up to draw with `wgpu_resources`'s device into targets the size and format of its
+    /// config, drawing from `audio`.
+    fn new(wgpu_resources: WgpuResources, audio: SharedAudio) -> anyhow::Result<Self>
+    where
+        Self: Sized;
     fn update(&mut self);
     fn render(&self, view: &wgpu::TextureView);
+    /// Follows the size of what's rendered into, eg. when the window is resized.
+    fn resize(&mut self, width: u32, height: u32);
+    /// Offers an event from the window, returning whether it was used, eg. to move a camera.
+    fn input(&mut self, _event: &WindowEvent) -> bool {
+        false
+    }
+    /// The parameters `set_param` takes, with their current values.
+    fn params(&self) -> Vec<Param> {
+        Vec::new()
+    }
+    fn set_param(&mut self, name: &str, _value: f32) -> anyhow::Result<()> {
+        bail!("unknown parameter {:?}, there aren't any", name)
+    }
 }
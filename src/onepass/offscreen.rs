@@ -0,0 +1,215 @@
+use std::{
+    future::Future,
+    num::NonZeroU32,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, Wake, Waker},
+};
+
+use anyhow::{bail, Context as _};
+use image::RgbaImage;
+
+use super::wgpu_resources::WgpuResources;
+
+type MapFuture = Pin<Box<dyn Future<Output = Result<(), wgpu::BufferAsyncError>> + Send>>;
+
+/// Offscreen is a render target whose frames can be read back to the CPU.
+///
+/// Readback is double-buffered: each call to `read_back` starts copying the latest frame into one
+/// staging buffer and returns the frame that buffer held before, two frames back, so the GPU can
+/// render and copy the next frame while the last one is being mapped.
+pub struct Offscreen {
+    pub width: u32,
+    pub height: u32,
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    format: wgpu::TextureFormat,
+    // Rows in staging buffers are padded to wgpu::COPY_BYTES_PER_ROW_ALIGNMENT
+    padded_bytes_per_row: u32,
+    staging_buffers: [wgpu::Buffer; 2],
+    pending: [Option<MapFuture>; 2],
+    next: usize,
+}
+
+/// Offscreen frames are always 8-bit RGBA.
+pub const BYTES_PER_PIXEL: u32 = 4;
+
+impl Offscreen {
+    /// Creates a render target matching `wgpu_resources.config`, scaled by `scale` so that
+    /// frames can be rendered at a higher resolution than the window. Fails if that's bigger than
+    /// the device's textures can be.
+    pub fn new(wgpu_resources: &WgpuResources, scale: u32) -> anyhow::Result<Self> {
+        let WgpuResources { device, config, .. } = wgpu_resources;
+        let max = device.limits().max_texture_dimension_2d;
+        let (width, height) = match (
+            config.width.checked_mul(scale),
+            config.height.checked_mul(scale),
+        ) {
+            (Some(width), Some(height)) if width <= max && height <= max => (width, height),
+            _ => bail!(
+                "{}x{} scaled by {} is too big, this device's textures can be at most {}x{}",
+                config.width,
+                config.height,
+                scale,
+                max,
+                max
+            ),
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Offscreen Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let unpadded_bytes_per_row = width * BYTES_PER_PIXEL;
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(align) * align;
+
+        let staging_buffer = |i| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(&format!("Offscreen Staging Buffer {}", i)),
+                size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            })
+        };
+
+        Ok(Self {
+            width,
+            height,
+            texture,
+            view,
+            format: config.format,
+            padded_bytes_per_row,
+            staging_buffers: [staging_buffer(0), staging_buffer(1)],
+            pending: [None, None],
+            next: 0,
+        })
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    /// Starts reading back whatever was last rendered into `view`, and returns the frame read
+    /// back two calls ago as tightly packed RGBA rows, if there was one.
+    pub fn read_back(&mut self, wgpu_resources: &WgpuResources) -> anyhow::Result<Option<Vec<u8>>> {
+        let WgpuResources { device, queue, .. } = wgpu_resources;
+        let i = self.next;
+        self.next = (self.next + 1) % self.staging_buffers.len();
+
+        // The buffer we're about to copy into may still hold an unread frame
+        let previous = self.take_frame(wgpu_resources, i)?;
+
+        let mut command_encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Offscreen Readback Encoder"),
+        });
+        command_encoder.copy_texture_to_buffer(
+            self.texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &self.staging_buffers[i],
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(self.padded_bytes_per_row),
+                    rows_per_image: NonZeroU32::new(self.height),
+                },
+            },
+            wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1,
+            },
+        );
+        queue.submit(Some(command_encoder.finish()));
+
+        self.pending[i] = Some(Box::pin(
+            self.staging_buffers[i]
+                .slice(..)
+                .map_async(wgpu::MapMode::Read),
+        ));
+
+        Ok(previous)
+    }
+
+    /// Reads back whatever was last rendered into `view` as an image, waiting for it.
+    pub fn image(&mut self, wgpu_resources: &WgpuResources) -> anyhow::Result<RgbaImage> {
+        self.read_back(wgpu_resources)?;
+        let rgba = self
+            .finish(wgpu_resources)?
+            .pop()
+            .context("the frame wasn't read back")?;
+        RgbaImage::from_raw(self.width, self.height, rgba)
+            .context("the frame doesn't match its size")
+    }
+
+    /// Waits for and returns the frames still being read back, oldest first.
+    pub fn finish(&mut self, wgpu_resources: &WgpuResources) -> anyhow::Result<Vec<Vec<u8>>> {
+        let mut frames = Vec::new();
+        for i in 0..self.staging_buffers.len() {
+            let i = (self.next + i) % self.staging_buffers.len();
+            frames.extend(self.take_frame(wgpu_resources, i)?);
+        }
+        Ok(frames)
+    }
+
+    /// Waits for staging buffer `i` to be mapped, and returns the frame in it if there is one.
+    fn take_frame(
+        &mut self,
+        wgpu_resources: &WgpuResources,
+        i: usize,
+    ) -> anyhow::Result<Option<Vec<u8>>> {
+        let mut pending = match self.pending[i].take() {
+            Some(pending) => pending,
+            None => return Ok(None),
+        };
+        // Waiting on the device would wait for everything submitted since, including the frame
+        // being rendered, so it's polled until just this buffer is mapped
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut context = Context::from_waker(&waker);
+        let mapped = loop {
+            wgpu_resources.device.poll(wgpu::Maintain::Poll);
+            match pending.as_mut().poll(&mut context) {
+                Poll::Ready(mapped) => break mapped,
+                Poll::Pending => std::thread::yield_now(),
+            }
+        };
+        mapped.context("mapping an offscreen staging buffer")?;
+
+        let buffer = &self.staging_buffers[i];
+        let unpadded_bytes_per_row = (self.width * BYTES_PER_PIXEL) as usize;
+        let mut frame: Vec<u8> = {
+            let mapped = buffer.slice(..).get_mapped_range();
+            mapped
+                .chunks(self.padded_bytes_per_row as usize)
+                .flat_map(|row| &row[..unpadded_bytes_per_row])
+                .copied()
+                .collect()
+        };
+        buffer.unmap();
+
+        // Window surfaces are usually BGRA, but frames are always handed out as RGBA
+        if let wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb = self.format {
+            frame
+                .chunks_mut(BYTES_PER_PIXEL as usize)
+                .for_each(|pixel| pixel.swap(0, 2));
+        }
+        Ok(Some(frame))
+    }
+}
+
+/// Wakes nothing, since mapping futures are polled in a loop rather than waited on.
+struct NoopWaker;
+
+impl Wake for NoopWaker {
+    fn wake(self: Arc<Self>) {}
+}
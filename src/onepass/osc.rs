@@ -0,0 +1,439 @@
+//! A minimal Open Sound Control server, so lighting desks, DAWs and scripts can drive the live
+//! window over UDP. Messages and bundles are decoded by hand; bundles are applied as soon as
+//! they arrive, whatever their time tag.
+
+use std::{
+    net::{SocketAddr, UdpSocket},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, bail, Context};
+
+use super::control::{Command, Query, Request, Requests};
+
+/// Argument is one argument of a message. Doubles and 64-bit integers are narrowed, since
+/// nothing they control needs the precision.
+#[derive(Clone, Debug)]
+pub(super) enum Argument {
+    Int(i32),
+    Float(f32),
+    String(String),
+    Bool(bool),
+}
+
+/// Message is an address, eg. `/thickness`, and its arguments. The text protocol in `remote`
+/// is turned into messages too, so both share one set of addresses.
+#[derive(Clone, Debug)]
+pub(super) struct Message {
+    pub address: String,
+    pub arguments: Vec<Argument>,
+}
+
+impl Message {
+    fn argument(&self, i: usize) -> anyhow::Result<&Argument> {
+        self.arguments
+            .get(i)
+            .ok_or_else(|| anyhow!("missing argument {}", i + 1))
+    }
+
+    fn float(&self, i: usize) -> anyhow::Result<f32> {
+        match self.argument(i)? {
+            Argument::Int(value) => Ok(*value as f32),
+            Argument::Float(value) => Ok(*value),
+            Argument::Bool(value) => Ok(*value as u8 as f32),
+            Argument::String(value) => value
+                .parse()
+                .with_context(|| format!("expected a number, got {:?}", value)),
+        }
+    }
+
+    fn int(&self, i: usize) -> anyhow::Result<i32> {
+        Ok(self.float(i)?.round() as i32)
+    }
+
+    /// Any non-zero number is true, so buttons sending 1.0 and 0.0 work too.
+    fn bool(&self, i: usize) -> anyhow::Result<bool> {
+        match self.argument(i)? {
+            Argument::Bool(value) => Ok(*value),
+            Argument::String(value) if value == "on" => Ok(true),
+            Argument::String(value) if value == "off" => Ok(false),
+            _ => Ok(self.float(i)? != 0.0),
+        }
+    }
+
+    fn string(&self, i: usize) -> anyhow::Result<&str> {
+        match self.argument(i)? {
+            Argument::String(value) => Ok(value),
+            argument => bail!("expected a string, got {:?}", argument),
+        }
+    }
+
+    /// A name, or a number from 1, eg. for a preset.
+    fn key(&self, i: usize) -> anyhow::Result<String> {
+        match self.argument(i)? {
+            Argument::String(value) => Ok(value.clone()),
+            _ => Ok(self.int(i)?.to_string()),
+        }
+    }
+
+    fn parse<T>(&self, i: usize) -> anyhow::Result<T>
+    where
+        T: FromStr<Err = anyhow::Error>,
+    {
+        self.string(i)?.parse()
+    }
+
+    /// A trace or node number from the address, counting from 1 like everything user facing.
+    fn index(part: &str) -> anyhow::Result<usize> {
+        match part.parse::<usize>() {
+            Ok(number) if number > 0 => Ok(number - 1),
+            _ => bail!("expected a number from 1, got {:?}", part),
+        }
+    }
+
+    pub fn command(&self) -> anyhow::Result<Command> {
+        let parts = self.address.split('/').skip(1).collect::<Vec<_>>();
+        let command = match parts.as_slice() {
+            ["pause"] if self.arguments.is_empty() => Command::Pause(None),
+            ["pause"] => Command::Pause(Some(self.bool(0)?)),
+            ["play"] => Command::Pause(Some(false)),
+            ["step"] => Command::Step,
+            ["seek"] => Command::Seek(self.float(0)?),
+            ["thickness"] => Command::Thickness(self.float(0)?),
+            ["gain"] => Command::Gain(self.float(0)?),
+            ["brightness"] => Command::Brightness(self.float(0)?),
+            ["decay"] => Command::Decay(self.float(0)?),
+            ["fade"] => Command::FadeCurve(self.parse(0)?),
+            ["color"] => Command::Color(self.parse(0)?),
+            ["view" | "mode"] => Command::View(self.parse(0)?),
+            ["layout"] => Command::Layout(self.parse(0)?),
+            ["blanking"] => Command::Blanking(self.bool(0)?),
+            ["blanking", "threshold"] => Command::BlankingThreshold(self.float(0)?),
+            ["dsp", node, param] => Command::DspParam {
+                node: Message::index(node)?,
+                param: param.to_string(),
+                value: self.float(0)?,
+            },
+            ["param", index] => Command::ShaderParam {
+                index: Message::index(index)?,
+                value: self.float(0)?,
+            },
+            ["source", trace] => Command::Show {
+                trace: Message::index(trace)?,
+                visible: self.bool(0)?,
+            },
+            // 0 shows every trace again
+            ["solo"] => match self.int(0)? {
+                number if number <= 0 => Command::Solo(None),
+                number => Command::Solo(Some(number as usize - 1)),
+            },
+            ["record"] => Command::Record(self.bool(0)?),
+            ["screenshot"] if self.arguments.is_empty() => Command::Screenshot(None),
+            ["screenshot"] => Command::Screenshot(Some(self.string(0)?.to_string())),
+            ["visualizer"] => Command::Visualizer(self.parse(0)?),
+            ["visualizer", param] => Command::VisualizerParam {
+                param: param.to_string(),
+                value: self.float(0)?,
+            },
+            ["preset"] => Command::Preset(self.key(0)?),
+            ["preset", "save"] => Command::SavePreset(self.key(0)?),
+            ["status"] => Command::Query(Query::Status),
+            ["levels"] => Command::Query(Query::Levels),
+            _ => bail!("unknown command"),
+        };
+        Ok(command)
+    }
+}
+
+/// Reader walks through a packet, where everything is big-endian and padded to four bytes.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> anyhow::Result<&'a [u8]> {
+        if count > self.bytes.len() {
+            bail!("packet ends early");
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> anyhow::Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().expect("took N bytes"))
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        let length = self
+            .bytes
+            .iter()
+            .position(|&byte| byte == 0)
+            .ok_or_else(|| anyhow!("string isn't terminated"))?;
+        let string = std::str::from_utf8(&self.bytes[..length])
+            .context("string isn't UTF-8")?
+            .to_string();
+        // The terminator and padding up to the next multiple of four
+        self.take((length + 1).div_ceil(4) * 4)?;
+        Ok(string)
+    }
+
+    fn blob(&mut self) -> anyhow::Result<&'a [u8]> {
+        let length = i32::from_be_bytes(self.array()?).max(0) as usize;
+        let blob = self.take(length)?;
+        self.take(length.div_ceil(4) * 4 - length)?;
+        Ok(blob)
+    }
+}
+
+/// Decodes a packet, which is either a message or a bundle of packets, into its messages.
+fn parse_packet(bytes: &[u8], messages: &mut Vec<Message>) -> anyhow::Result<()> {
+    let mut reader = Reader { bytes };
+    if bytes.starts_with(b"#bundle\0") {
+        reader.take(8)?;
+        // The time tag
+        reader.take(8)?;
+        while !reader.bytes.is_empty() {
+            parse_packet(reader.blob()?, messages)?;
+        }
+        return Ok(());
+    }
+
+    let address = reader.string()?;
+    if !address.starts_with('/') {
+        bail!("{:?} isn't an OSC address", address);
+    }
+    // Very old clients leave the type tags out, which means no arguments
+    let tags = if reader.bytes.is_empty() {
+        String::from(",")
+    } else {
+        reader.string()?
+    };
+    let tags = tags
+        .strip_prefix(',')
+        .ok_or_else(|| anyhow!("{}: type tags don't start with ','", address))?;
+
+    let mut arguments = Vec::new();
+    for tag in tags.chars() {
+        let argument = match tag {
+            'i' => Argument::Int(i32::from_be_bytes(reader.array()?)),
+            'f' => Argument::Float(f32::from_be_bytes(reader.array()?)),
+            'h' => Argument::Int(i64::from_be_bytes(reader.array()?) as i32),
+            'd' => Argument::Float(f64::from_be_bytes(reader.array()?) as f32),
+            's' | 'S' => Argument::String(reader.string()?),
+            'T' => Argument::Bool(true),
+            'F' => Argument::Bool(false),
+            // Skipped, but their data still has to be read past
+            'b' => {
+                reader.blob()?;
+                continue;
+            }
+            't' | 'c' | 'r' | 'm' => {
+                reader.take(if tag == 't' { 8 } else { 4 })?;
+                continue;
+            }
+            'N' | 'I' | '[' | ']' => continue,
+            _ => bail!("{}: unknown type tag {:?}", address, tag),
+        };
+        arguments.push(argument);
+    }
+    messages.push(Message { address, arguments });
+    Ok(())
+}
+
+/// Listens for OSC on `address` on a thread of its own, sending each message that arrives to
+/// `requests` as a Command. Nothing is sent back, so queries go unanswered. Returns the address
+/// it's bound to.
+pub fn listen(address: &str, requests: impl Requests) -> anyhow::Result<SocketAddr> {
+    let socket =
+        UdpSocket::bind(address).with_context(|| format!("listening for OSC on {}", address))?;
+    let bound = socket.local_addr()?;
+    std::thread::Builder::new()
+        .name("osc".to_string())
+        .spawn(move || {
+            // The largest UDP payload there can be
+            let mut buffer = vec![0; 65536];
+            loop {
+                let (length, from) = match socket.recv_from(&mut buffer) {
+                    Ok(received) => received,
+                    Err(e) => {
+                        log::warn!("osc: {}", e);
+                        continue;
+                    }
+                };
+                let mut messages = Vec::new();
+                if let Err(e) = parse_packet(&buffer[..length], &mut messages) {
+                    log::warn!("osc: bad packet from {}: {:#}", from, e);
+                    continue;
+                }
+                for message in messages {
+                    match message.command() {
+                        Ok(command) => {
+                            log::debug!("osc: {:?}", command);
+                            // The event loop has gone, so there's nothing left to control
+                            let request = Request {
+                                command,
+                                reply: None,
+                            };
+                            if !requests.send(request) {
+                                return;
+                            }
+                        }
+                        Err(e) => log::warn!("osc: {} from {}: {:#}", message.address, from, e),
+                    }
+                }
+            }
+        })?;
+    Ok(bound)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::mpsc, time::Duration};
+
+    use super::*;
+
+    /// A string as OSC sends it, terminated and padded to four bytes.
+    fn padded(string: &str) -> Vec<u8> {
+        let mut bytes = string.as_bytes().to_vec();
+        bytes.resize((string.len() + 1).div_ceil(4) * 4, 0);
+        bytes
+    }
+
+    fn blob(bytes: &[u8]) -> Vec<u8> {
+        let mut blob = (bytes.len() as i32).to_be_bytes().to_vec();
+        blob.extend(bytes);
+        blob.resize(4 + bytes.len().div_ceil(4) * 4, 0);
+        blob
+    }
+
+    fn message(address: &str, tags: &str, data: &[u8]) -> Vec<u8> {
+        let mut packet = padded(address);
+        packet.extend(padded(tags));
+        packet.extend(data);
+        packet
+    }
+
+    fn bundle(packets: &[Vec<u8>]) -> Vec<u8> {
+        let mut bundle = padded("#bundle");
+        // Immediately
+        bundle.extend(1u64.to_be_bytes());
+        for packet in packets {
+            bundle.extend(blob(packet));
+        }
+        bundle
+    }
+
+    fn parse(bytes: &[u8]) -> anyhow::Result<Vec<Message>> {
+        let mut messages = Vec::new();
+        parse_packet(bytes, &mut messages)?;
+        Ok(messages)
+    }
+
+    #[test]
+    fn padded_strings() {
+        // Lengths that need 0 to 3 bytes of padding past the terminator
+        for color in ["p7", "p31", "amber", "green"] {
+            let packet = message(
+                "/color",
+                ",si",
+                &[padded(color), 7i32.to_be_bytes().to_vec()].concat(),
+            );
+            let messages = parse(&packet).unwrap();
+            assert_eq!(messages[0].string(0).unwrap(), color);
+            assert_eq!(messages[0].int(1).unwrap(), 7);
+        }
+    }
+
+    #[test]
+    fn blobs_are_skipped() {
+        let data = [blob(&[1, 2, 3, 4, 5]), 0.5f32.to_be_bytes().to_vec()].concat();
+        let messages = parse(&message("/thickness", ",bf", &data)).unwrap();
+        assert_eq!(messages[0].arguments.len(), 1);
+        assert_eq!(messages[0].float(0).unwrap(), 0.5);
+    }
+
+    #[test]
+    fn nested_bundles() {
+        let inner = bundle(&[message("/gain", ",f", &2.0f32.to_be_bytes())]);
+        let outer = bundle(&[message("/play", ",", &[]), inner]);
+        let messages = parse(&outer).unwrap();
+        let addresses = messages
+            .iter()
+            .map(|message| message.address.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(addresses, ["/play", "/gain"]);
+        assert!(matches!(messages[1].command().unwrap(), Command::Gain(gain) if gain == 2.0));
+    }
+
+    #[test]
+    fn missing_type_tags() {
+        let messages = parse(&padded("/step")).unwrap();
+        assert!(messages[0].arguments.is_empty());
+        assert!(matches!(messages[0].command().unwrap(), Command::Step));
+    }
+
+    #[test]
+    fn unknown_type_tags() {
+        let error = parse(&message("/gain", ",x", &[0; 4])).unwrap_err();
+        assert!(error.to_string().contains("unknown type tag"));
+    }
+
+    #[test]
+    fn truncated_packets() {
+        let packet = message("/gain", ",f", &2.0f32.to_be_bytes());
+        for length in [3, packet.len() - 1] {
+            assert!(parse(&packet[..length]).is_err());
+        }
+        let bundle = bundle(&[packet]);
+        assert!(parse(&bundle[..bundle.len() - 2]).is_err());
+    }
+
+    #[test]
+    fn commands() {
+        let command = |address: &str, arguments: Vec<Argument>| {
+            Message {
+                address: address.to_string(),
+                arguments,
+            }
+            .command()
+        };
+        assert!(matches!(
+            command("/dsp/2/cutoff", vec![Argument::Int(4000)]).unwrap(),
+            Command::DspParam { node: 1, ref param, value } if param == "cutoff" && value == 4000.0
+        ));
+        assert!(matches!(
+            command("/pause", vec![Argument::Float(0.0)]).unwrap(),
+            Command::Pause(Some(false))
+        ));
+        assert!(matches!(
+            command("/solo", vec![Argument::Int(0)]).unwrap(),
+            Command::Solo(None)
+        ));
+        assert!(command("/source/0", vec![Argument::Bool(true)]).is_err());
+        assert!(command("/thickness", vec![]).is_err());
+        assert!(command("/nothing", vec![]).is_err());
+    }
+
+    #[test]
+    fn listens_over_udp() {
+        let (requests, received) = mpsc::channel();
+        let address = listen("127.0.0.1:0", requests).unwrap();
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let packet = bundle(&[
+            message("/thickness", ",f", &0.01f32.to_be_bytes()),
+            message("/view", ",s", &padded("xyz")),
+        ]);
+        client.send_to(&packet, address).unwrap();
+
+        let timeout = Duration::from_secs(5);
+        let first = received.recv_timeout(timeout).unwrap();
+        assert!(matches!(first.command, Command::Thickness(thickness) if thickness == 0.01));
+        assert!(first.reply.is_none());
+        let second = received.recv_timeout(timeout).unwrap();
+        assert!(matches!(
+            second.command,
+            Command::View(crate::onepass::ViewMode::Xyz)
+        ));
+    }
+}
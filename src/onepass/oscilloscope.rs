@@ -1,10 +1,47 @@
-use std::borrow::Cow;
+use std::{
+    borrow::Cow,
+    fmt,
+    path::Path,
+    str::FromStr,
+    time::{Duration, Instant},
+};
 
+use anyhow::{anyhow, bail, Context};
 use bytemuck::{Pod, Zeroable};
 
+use image::RgbaImage;
+
 use wgpu::util::DeviceExt;
 
-use super::{state, wgpu_resources::WgpuResources, Shaderer};
+use winit::event::WindowEvent;
+
+use crate::{
+    camera::{self, Cam},
+    dsp::Chain,
+    sound::{Frame, Source, WavFormat, WavStreamer},
+};
+
+use super::{
+    color::ColorScheme,
+    config::Settings,
+    offscreen::Offscreen,
+    overlay::Overlay,
+    shader_source::{self, ShaderSource},
+    state,
+    visualizer::{Audio, Param, SharedAudio},
+    wgpu_resources::WgpuResources,
+    Shaderer,
+};
+
+const RENDER_SHADER: &str = include_str!("shaders/render.wgsl");
+const COMPUTE_SHADER: &str = include_str!("shaders/compute.wgsl");
+
+/// How often shaders loaded from disk are checked for changes.
+const SHADER_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+/// Shader errors are drawn in this color over the traces.
+const ERROR_COLOR: [f32; 4] = [1.0, 0.3, 0.3, 1.0];
+/// The HUD is drawn in this color, dim enough to see the traces through.
+const HUD_COLOR: [f32; 4] = [0.8, 0.8, 0.8, 0.7];
 
 pub struct Oscilloscope {
     pub wgpu_resources: WgpuResources,
@@ -12,36 +49,234 @@ pub struct Oscilloscope {
     render_pipeline: wgpu::RenderPipeline,
     compute_pipeline: wgpu::ComputePipeline,
 
-    state: state::State,
+    // Each trace has its own source, ring buffer, instance buffer and colors.
+    traces: Vec<state::State>,
+    layout: Layout,
+    camera: Cam,
+
+    render_shader: ShaderSource,
+    compute_shader: ShaderSource,
+    shaders_checked: Instant,
+    overlay: Overlay,
+    // Drawn in the window, but left out of screenshots, and hidden by any shader errors
+    hud: Overlay,
+    // The traces' latest samples, for other visualizers to draw
+    audio: SharedAudio,
+}
+
+/// Layout decides where each trace is drawn when there is more than one.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Layout {
+    /// All traces share the whole view, blended additively.
+    Overlay,
+    /// The view is split into side-by-side panes, one per trace, each drawn square.
+    SideBySide,
+}
+
+impl Layout {
+    pub fn next(self) -> Self {
+        match self {
+            Layout::Overlay => Layout::SideBySide,
+            Layout::SideBySide => Layout::Overlay,
+        }
+    }
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Layout::Overlay => "overlay",
+            Layout::SideBySide => "side-by-side",
+        })
+    }
+}
+
+impl FromStr for Layout {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "overlay" => Ok(Layout::Overlay),
+            "side-by-side" => Ok(Layout::SideBySide),
+            _ => bail!("unknown layout {:?}, expected overlay or side-by-side", s),
+        }
+    }
 }
 
+/// Vertex is a single beam position: X and Y, plus Z which modulates the beam's intensity
+/// (or, in the XYZ view, is its depth).
 #[repr(C)]
 #[derive(Pod, Copy, Zeroable, Clone)]
-pub struct Vertex(pub [f32; 2]);
+pub struct Vertex(pub [f32; 3]);
 
 impl Oscilloscope {
-    fn new(wgpu_resources: WgpuResources) -> Self {
-        let state = state::State::new(&wgpu_resources, "music/03 Blocks.wav");
-        Self {
-            render_pipeline: Oscilloscope::new_render_pipeline(&wgpu_resources, &state),
-            compute_pipeline: Oscilloscope::new_compute_pipeline(&wgpu_resources, &state),
+    fn new(wgpu_resources: WgpuResources) -> anyhow::Result<Self> {
+        Oscilloscope::with_files(wgpu_resources, &["music/03 Blocks.wav"])
+    }
+
+    /// Creates an oscilloscope with one trace per WAV file.
+    pub fn with_files<S: AsRef<str>>(
+        wgpu_resources: WgpuResources,
+        filenames: &[S],
+    ) -> anyhow::Result<Self> {
+        let sources = filenames
+            .iter()
+            .map(|filename| {
+                let source = WavStreamer::new(filename.as_ref())?;
+                Ok(Box::new(source) as Box<dyn Source>)
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Oscilloscope::with_sources(wgpu_resources, sources)
+    }
+
+    /// Creates an oscilloscope with one trace per source, of which there has to be at least one.
+    /// Traces are colored with successive preset color schemes.
+    pub fn with_sources(
+        wgpu_resources: WgpuResources,
+        sources: Vec<Box<dyn Source>>,
+    ) -> anyhow::Result<Self> {
+        if sources.is_empty() {
+            bail!("an oscilloscope needs at least one source");
+        }
+
+        let traces = sources
+            .into_iter()
+            .enumerate()
+            .map(|(i, source)| {
+                let mut trace = state::State::new(&wgpu_resources, source);
+                trace.set_color_scheme(ColorScheme::PRESETS[i % ColorScheme::PRESETS.len()]);
+                trace
+            })
+            .collect::<Vec<_>>();
+
+        let camera = camera::new_camera(&wgpu_resources.config, &wgpu_resources.device);
+        Ok(Self {
+            render_pipeline: Oscilloscope::new_render_pipeline(
+                &wgpu_resources,
+                &traces[0],
+                &camera,
+                RENDER_SHADER,
+            )
+            .expect("the built-in render shader is valid"),
+            compute_pipeline: Oscilloscope::new_compute_pipeline(
+                &wgpu_resources,
+                &traces[0],
+                COMPUTE_SHADER,
+            )
+            .expect("the built-in compute shader is valid"),
+            overlay: Overlay::new(&wgpu_resources),
+            hud: Overlay::new(&wgpu_resources),
             wgpu_resources,
-            state,
+            traces,
+            layout: Layout::Overlay,
+            camera,
+            render_shader: ShaderSource::Builtin(RENDER_SHADER),
+            compute_shader: ShaderSource::Builtin(COMPUTE_SHADER),
+            shaders_checked: Instant::now(),
+            audio: SharedAudio::default(),
+        })
+    }
+
+    /// The traces' latest samples after processing, which are updated along with the traces.
+    pub fn audio(&self) -> SharedAudio {
+        self.audio.clone()
+    }
+
+    /// Loads `render.wgsl` and `compute.wgsl` from `dir` in place of the built-in shaders (either
+    /// can be left out to keep the built-in one), and reloads them whenever they change.
+    pub fn watch_shaders(&mut self, dir: &Path) {
+        self.render_shader = ShaderSource::from_dir(dir, "render.wgsl", RENDER_SHADER);
+        self.compute_shader = ShaderSource::from_dir(dir, "compute.wgsl", COMPUTE_SHADER);
+        self.reload_shaders();
+    }
+
+    /// Draws traces with the render shader at `path` in place of the built-in one, and reloads
+    /// it whenever it changes. See `shaders/custom_trace.wgsl` for the interface it gets.
+    pub fn use_trace_shader(&mut self, path: &Path) {
+        self.render_shader = ShaderSource::file(path);
+        self.reload_shaders();
+    }
+
+    /// Sets one of the free parameters custom trace shaders read from `uniforms.params`, on
+    /// every trace.
+    pub fn set_shader_param(&mut self, index: usize, value: f32) -> anyhow::Result<()> {
+        for trace in &mut self.traces {
+            trace.set_shader_param(index, value)?;
+        }
+        Ok(())
+    }
+
+    /// Rebuilds the pipelines from the current shaders. If either fails to build, its error is
+    /// logged and drawn over the traces, and the pipelines already built keep being used.
+    fn reload_shaders(&mut self) {
+        let render_pipeline = self.render_shader.load().and_then(|source| {
+            Oscilloscope::new_render_pipeline(
+                &self.wgpu_resources,
+                &self.traces[0],
+                &self.camera,
+                &source,
+            )
+        });
+        let compute_pipeline = self.compute_shader.load().and_then(|source| {
+            Oscilloscope::new_compute_pipeline(&self.wgpu_resources, &self.traces[0], &source)
+        });
+
+        let mut errors = Vec::new();
+        match render_pipeline {
+            Ok(pipeline) => self.render_pipeline = pipeline,
+            Err(e) => errors.push(format!("{}: {:#}", self.render_shader.name(), e)),
+        }
+        match compute_pipeline {
+            Ok(pipeline) => self.compute_pipeline = pipeline,
+            Err(e) => errors.push(format!("{}: {:#}", self.compute_shader.name(), e)),
+        }
+
+        if errors.is_empty() {
+            log::info!("reloaded shaders");
+            self.overlay.clear();
+        } else {
+            let message = errors.join("\n\n");
+            log::error!("{}", message);
+            self.overlay
+                .set_text(&self.wgpu_resources, &message, ERROR_COLOR);
+        }
+    }
+
+    /// Creates a pipeline with an error scope around it, so invalid shaders are reported as
+    /// errors rather than panicking.
+    fn capture_errors<T>(device: &wgpu::Device, create: impl FnOnce() -> T) -> anyhow::Result<T> {
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let created = create();
+        match pollster::block_on(device.pop_error_scope()) {
+            Some(e) => Err(anyhow!("{}", e)),
+            None => Ok(created),
         }
     }
 
     fn new_render_pipeline(
         wgpu_resources: &WgpuResources,
         state: &state::State,
-    ) -> wgpu::RenderPipeline {
+        camera: &Cam,
+        source: &str,
+    ) -> anyhow::Result<wgpu::RenderPipeline> {
+        let module = shader_source::validate(source)?;
+        shader_source::check_render_interface(
+            &module,
+            &[
+                std::mem::size_of::<state::Uniforms>() as u64,
+                std::mem::size_of::<camera::CameraUniform>() as u64,
+            ],
+        )?;
         let WgpuResources { device, config, .. } = wgpu_resources;
-        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
-            label: Some("Shader"),
-            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("shaders/render.wgsl"))),
-        });
+        let shader = Oscilloscope::capture_errors(device, || {
+            device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("Shader"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(source)),
+            })
+        })?;
         let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Pipeline Layout"),
-            bind_group_layouts: &[&state.uniform_bind_group_layout], // TODO: use these maybe??
+            bind_group_layouts: &[&state.uniform_bind_group_layout, &camera.bind_group_layout],
             push_constant_ranges: &[],
         });
 
@@ -49,16 +284,29 @@ impl Oscilloscope {
             module: &shader,
             entry_point: "main_vs",
             buffers: &[wgpu::VertexBufferLayout {
-                array_stride: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
                 step_mode: wgpu::VertexStepMode::Instance,
-                attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2],
-            }], // example: blah.rs:381
+                attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3],
+            }],
         };
 
         let fragment = wgpu::FragmentState {
             module: &shader,
             entry_point: "main_fs",
-            targets: &[config.format.into()],
+            // Additive, so overlapping traces (and overlapping segments of one trace) add up
+            // like light on a phosphor.
+            targets: &[wgpu::ColorTargetState {
+                format: config.format,
+                blend: Some(wgpu::BlendState {
+                    color: wgpu::BlendComponent {
+                        src_factor: wgpu::BlendFactor::One,
+                        dst_factor: wgpu::BlendFactor::One,
+                        operation: wgpu::BlendOperation::Add,
+                    },
+                    alpha: wgpu::BlendComponent::OVER,
+                }),
+                write_mask: wgpu::ColorWrites::ALL,
+            }],
         };
 
         let primitive = wgpu::PrimitiveState {
@@ -69,41 +317,432 @@ impl Oscilloscope {
             ..Default::default()
         };
 
-        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState::default(),
-            multiview: None,
-            layout: Some(&layout),
-            fragment: Some(fragment),
-            vertex,
-            primitive,
+        Oscilloscope::capture_errors(device, || {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Render Pipeline"),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                layout: Some(&layout),
+                fragment: Some(fragment),
+                vertex,
+                primitive,
+            })
         })
     }
 
     fn new_compute_pipeline(
         wgpu_resources: &WgpuResources,
         state: &state::State,
-    ) -> wgpu::ComputePipeline {
+        source: &str,
+    ) -> anyhow::Result<wgpu::ComputePipeline> {
+        shader_source::validate(source)?;
         let WgpuResources { device, .. } = wgpu_resources;
-        let compute_shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
-            label: Some("Compute Shader"),
-            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("shaders/compute.wgsl"))),
-        });
+        let compute_shader = Oscilloscope::capture_errors(device, || {
+            device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("Compute Shader"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(source)),
+            })
+        })?;
         let compute_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Compute Pipeline Layout"),
                 bind_group_layouts: &[&state.wav_stream_bind_group_layout],
                 push_constant_ranges: &[],
             });
-        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-            label: Some("Compute Pipeline"),
-            layout: Some(&compute_pipeline_layout),
-            module: &compute_shader,
-            entry_point: "main",
+        Oscilloscope::capture_errors(device, || {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("Compute Pipeline"),
+                layout: Some(&compute_pipeline_layout),
+                module: &compute_shader,
+                entry_point: "main",
+            })
         })
     }
 
+    /// Applies every setting that `settings` has, leaving the rest as they are.
+    pub fn apply_settings(&mut self, settings: &Settings) -> anyhow::Result<()> {
+        let Settings {
+            thickness,
+            gain,
+            brightness,
+            decay,
+            fade,
+            color,
+            view,
+            layout,
+            blanking,
+            blanking_threshold,
+            fog_density,
+            size_attenuation,
+            dsp,
+            shader_params,
+        } = settings;
+        if let Some(params) = shader_params {
+            if params.len() > state::SHADER_PARAM_COUNT {
+                bail!(
+                    "{} shader parameters given, there are only {}",
+                    params.len(),
+                    state::SHADER_PARAM_COUNT
+                );
+            }
+            for (i, &value) in params.iter().enumerate() {
+                self.set_shader_param(i, value)?;
+            }
+        }
+        if let Some(thickness) = *thickness {
+            self.set_line_thickness(thickness);
+        }
+        if let Some(gain) = *gain {
+            self.set_gain(gain);
+        }
+        if let Some(brightness) = *brightness {
+            self.set_brightness(brightness);
+        }
+        if let Some(decay) = *decay {
+            self.set_fade_strength(decay);
+        }
+        if let Some(fade) = *fade {
+            self.set_fade_curve(fade);
+        }
+        if let Some(color) = *color {
+            self.set_color_scheme(color);
+        }
+        if let Some(view) = *view {
+            self.set_view_mode(view);
+        }
+        if let Some(layout) = *layout {
+            self.set_layout(layout);
+        }
+        if let Some(blanking) = *blanking {
+            self.set_hard_blanking(blanking);
+        }
+        if let Some(blanking_threshold) = *blanking_threshold {
+            self.set_blanking_threshold(blanking_threshold);
+        }
+        for trace in &mut self.traces {
+            if let Some(fog_density) = *fog_density {
+                trace.uniforms.fog_density = fog_density;
+            }
+            if let Some(size_attenuation) = *size_attenuation {
+                trace.uniforms.size_attenuation = size_attenuation;
+            }
+        }
+        if let Some(chain) = dsp {
+            self.set_chain(chain);
+        }
+        Ok(())
+    }
+
+    /// Every setting as it is now, taken from the first trace where traces differ.
+    pub fn settings(&self) -> Settings {
+        let trace = &self.traces[0];
+        Settings {
+            thickness: Some(trace.uniforms.line_thickness),
+            gain: Some(trace.gain()),
+            brightness: Some(trace.brightness()),
+            decay: Some(trace.uniforms.fade_strength),
+            fade: Some(trace.fade_curve()),
+            color: Some(*trace.color_scheme()),
+            view: Some(trace.view_mode()),
+            layout: Some(self.layout),
+            blanking: Some(trace.hard_blanking()),
+            blanking_threshold: Some(trace.uniforms.blanking_threshold),
+            fog_density: Some(trace.uniforms.fog_density),
+            size_attenuation: Some(trace.uniforms.size_attenuation),
+            dsp: Some(trace.chain().clone()),
+            shader_params: Some(trace.shader_params()),
+        }
+    }
+
+    /// Switches every trace to the next fade curve, returning the one now in use.
+    pub fn cycle_fade_curve(&mut self) -> state::FadeCurve {
+        let fade_curve = self.traces[0].fade_curve().next();
+        for trace in &mut self.traces {
+            trace.set_fade_curve(fade_curve);
+        }
+        fade_curve
+    }
+
+    pub fn set_fade_curve(&mut self, fade_curve: state::FadeCurve) {
+        for trace in &mut self.traces {
+            trace.set_fade_curve(fade_curve);
+        }
+    }
+
+    /// How quickly the exponential and power fade curves fall off.
+    pub fn set_fade_strength(&mut self, fade_strength: f32) {
+        for trace in &mut self.traces {
+            trace.uniforms.fade_strength = fade_strength;
+        }
+    }
+
+    pub fn set_line_thickness(&mut self, line_thickness: f32) {
+        for trace in &mut self.traces {
+            trace.uniforms.line_thickness = line_thickness;
+        }
+    }
+
+    /// Scales every trace on screen, without changing what's recorded.
+    pub fn set_gain(&mut self, gain: f32) {
+        for trace in &mut self.traces {
+            trace.set_gain(gain);
+        }
+    }
+
+    /// Scales every trace's colors, where 1 is the color scheme's own.
+    pub fn set_brightness(&mut self, brightness: f32) {
+        for trace in &mut self.traces {
+            trace.set_brightness(brightness);
+        }
+    }
+
+    /// Moves every trace across the screen by `x` and `y`, where the screen is 2 wide.
+    pub fn pan(&mut self, x: f32, y: f32) {
+        for trace in &mut self.traces {
+            let [pan_x, pan_y] = trace.pan();
+            trace.set_pan([pan_x + x, pan_y + y]);
+        }
+    }
+
+    /// Scales every trace by `zoom` about the middle of the screen.
+    pub fn zoom(&mut self, zoom: f32) {
+        for trace in &mut self.traces {
+            trace.set_gain(trace.gain() * zoom);
+            trace.set_pan(trace.pan().map(|pan| pan * zoom));
+        }
+    }
+
+    /// Undoes any panning and zooming.
+    pub fn reset_view(&mut self) {
+        for trace in &mut self.traces {
+            trace.set_gain(1.0);
+            trace.set_pan([0.0, 0.0]);
+        }
+    }
+
+    /// Shows `text` over the traces in the window, or hides the HUD for `None`.
+    pub fn set_hud(&mut self, text: Option<&str>) {
+        match text {
+            Some(text) => self.hud.set_text(&self.wgpu_resources, text, HUD_COLOR),
+            None => self.hud.clear(),
+        }
+    }
+
+    /// How many characters fit across a line of the HUD.
+    pub fn hud_columns(&self) -> usize {
+        Overlay::columns(&self.wgpu_resources)
+    }
+
+    /// Colors every trace with the same scheme.
+    pub fn set_color_scheme(&mut self, color_scheme: ColorScheme) {
+        for trace in &mut self.traces {
+            trace.set_color_scheme(color_scheme);
+        }
+    }
+
+    /// Switches each trace to its next preset color scheme, returning the first trace's.
+    pub fn cycle_color_scheme(&mut self) -> ColorScheme {
+        for trace in &mut self.traces {
+            let color_scheme = trace.color_scheme().next_preset();
+            trace.set_color_scheme(color_scheme);
+        }
+        *self.traces[0].color_scheme()
+    }
+
+    /// Switches every trace to the next view mode, returning the one now in use.
+    pub fn cycle_view_mode(&mut self) -> state::ViewMode {
+        let view_mode = self.traces[0].view_mode().next();
+        self.set_view_mode(view_mode);
+        view_mode
+    }
+
+    pub fn set_view_mode(&mut self, view_mode: state::ViewMode) {
+        for trace in &mut self.traces {
+            trace.set_view_mode(view_mode);
+        }
+    }
+
+    /// Steps every trace's clock by exactly `1 / fps` per frame rather than following the wall
+    /// clock, or follows the wall clock again for `None`.
+    pub fn set_fixed_frame_rate(&mut self, fps: Option<f32>) {
+        for trace in &mut self.traces {
+            trace.set_fixed_frame_rate(fps);
+        }
+    }
+
+    /// Restarts every trace's clock from now, after being paused.
+    pub fn resume(&mut self) {
+        for trace in &mut self.traces {
+            trace.resume();
+        }
+    }
+
+    /// Whether every trace's source has run out of samples.
+    pub fn is_finished(&self) -> bool {
+        self.traces.iter().all(|trace| trace.is_finished())
+    }
+
+    /// Runs every trace's samples through its own copy of `chain` before they're shown.
+    pub fn set_chain(&mut self, chain: &Chain) {
+        for trace in &mut self.traces {
+            trace.set_chain(chain.clone());
+        }
+    }
+
+    /// Changes a parameter of the node at `index` in every trace's chain.
+    pub fn set_chain_param(&mut self, index: usize, param: &str, value: f32) -> anyhow::Result<()> {
+        for trace in &mut self.traces {
+            trace.chain_mut().set(index, param, value)?;
+        }
+        Ok(())
+    }
+
+    /// The chain every trace's samples run through.
+    pub fn chain(&self) -> &Chain {
+        self.traces[0].chain()
+    }
+
+    /// Nudges the main parameter of the node at `index` up or down a step in every trace's
+    /// chain, returning the parameter and its new value.
+    pub fn nudge_chain_param(
+        &mut self,
+        index: usize,
+        up: bool,
+    ) -> anyhow::Result<(&'static str, f32)> {
+        let node = self
+            .chain()
+            .nodes
+            .get(index)
+            .with_context(|| format!("no node {} in the chain", index))?;
+        let (param, value) = node
+            .nudged(up)
+            .with_context(|| format!("{} has nothing to nudge", node.name()))?;
+        self.set_chain_param(index, param, value)?;
+        Ok((param, value))
+    }
+
+    /// Starts recording what each trace shows to its own WAV: `path` itself when there's one
+    /// trace, or `path` with the trace's number added (eg. `take-2.wav`) when there are more.
+    /// Returns the paths being written.
+    pub fn start_recording(
+        &mut self,
+        path: &str,
+        format: WavFormat,
+        duration: Option<f32>,
+    ) -> anyhow::Result<Vec<String>> {
+        let paths = match self.traces.len() {
+            1 => vec![path.to_string()],
+            n => {
+                let path = Path::new(path);
+                let stem = path.with_extension("");
+                let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("wav");
+                (1..=n)
+                    .map(|i| format!("{}-{}.{}", stem.display(), i, extension))
+                    .collect()
+            }
+        };
+        for (trace, path) in self.traces.iter_mut().zip(&paths) {
+            trace.start_recording(path, format, duration)?;
+        }
+        Ok(paths)
+    }
+
+    /// Finishes every trace's recording, returning the paths written.
+    pub fn stop_recording(&mut self) -> anyhow::Result<Vec<String>> {
+        let mut paths = Vec::new();
+        for trace in &mut self.traces {
+            paths.extend(trace.stop_recording()?);
+        }
+        Ok(paths)
+    }
+
+    /// Whether any trace is still recording.
+    pub fn is_recording(&self) -> bool {
+        self.traces.iter().any(|trace| trace.is_recording())
+    }
+
+    pub fn set_layout(&mut self, layout: Layout) {
+        self.layout = layout;
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    pub fn traces(&self) -> &[state::State] {
+        &self.traces
+    }
+
+    /// Jumps every trace to `seconds` into its source.
+    pub fn seek(&mut self, seconds: f32) -> anyhow::Result<()> {
+        for trace in &mut self.traces {
+            trace.seek(seconds)?;
+        }
+        Ok(())
+    }
+
+    /// Shows or hides the trace at `index`.
+    pub fn set_visible(&mut self, index: usize, visible: bool) -> anyhow::Result<()> {
+        let count = self.traces.len();
+        self.traces
+            .get_mut(index)
+            .with_context(|| format!("no trace {} of {}", index, count))?
+            .set_visible(visible);
+        Ok(())
+    }
+
+    /// Shows only the trace at `index`, or every trace for `None`.
+    pub fn solo(&mut self, index: Option<usize>) -> anyhow::Result<()> {
+        if let Some(index) = index.filter(|&index| index >= self.traces.len()) {
+            bail!("no trace {} of {}", index, self.traces.len());
+        }
+        for (i, trace) in self.traces.iter_mut().enumerate() {
+            trace.set_visible(index.map_or(true, |index| index == i));
+        }
+        Ok(())
+    }
+
+    /// Switches to the next layout, returning the one now in use.
+    pub fn cycle_layout(&mut self) -> Layout {
+        self.layout = self.layout.next();
+        self.layout
+    }
+
+    fn update_camera(&mut self) {
+        let Cam {
+            camera,
+            uniform,
+            buffer,
+            controller,
+            ..
+        } = &mut self.camera;
+        controller.update_camera(camera);
+        uniform.update_view_proj(camera);
+        self.wgpu_resources
+            .queue
+            .write_buffer(buffer, 0, bytemuck::cast_slice(&[*uniform]));
+    }
+
+    pub fn toggle_hard_blanking(&mut self) -> bool {
+        let hard_blanking = !self.traces[0].hard_blanking();
+        self.set_hard_blanking(hard_blanking);
+        hard_blanking
+    }
+
+    pub fn set_hard_blanking(&mut self, hard_blanking: bool) {
+        for trace in &mut self.traces {
+            trace.set_hard_blanking(hard_blanking);
+        }
+    }
+
+    /// The Z level hard blanking switches the beam at.
+    pub fn set_blanking_threshold(&mut self, blanking_threshold: f32) {
+        for trace in &mut self.traces {
+            trace.uniforms.blanking_threshold = blanking_threshold;
+        }
+    }
+
     fn cpass(&self, command_encoder: &mut wgpu::CommandEncoder) {
         let compute_pass_descriptor = wgpu::ComputePassDescriptor {
             label: Some("Compute Pass"),
@@ -113,18 +752,69 @@ impl Oscilloscope {
         {
             let mut cpass = command_encoder.begin_compute_pass(&compute_pass_descriptor);
             cpass.set_pipeline(&self.compute_pipeline);
-            cpass.set_bind_group(0, &self.state.wav_stream_bind_groups[0], &[]);
-            cpass.dispatch(64 as u32, 1, 1);
+            for trace in &self.traces {
+                cpass.set_bind_group(0, &trace.wav_stream_bind_groups[0], &[]);
+                cpass.dispatch(64, 1, 1);
+            }
         }
         command_encoder.pop_debug_group();
     }
 
-    fn rpass(&self, command_encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+    /// Draws every trace into `view`, which is `width` by `height` pixels.
+    pub fn render_to(&self, view: &wgpu::TextureView, width: u32, height: u32) {
+        self.draw(view, width, height, false);
+    }
+
+    /// Records drawing every trace into `view` on `command_encoder`, for apps that submit their
+    /// own command buffers, eg. from an egui paint callback. `view` has to be in
+    /// `wgpu_resources.config`'s format, and is cleared first.
+    pub fn encode(
+        &self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        width: u32,
+        height: u32,
+    ) {
+        self.rpass(command_encoder, view, width, height, false);
+    }
+
+    fn draw(&self, view: &wgpu::TextureView, width: u32, height: u32, hud: bool) {
+        let WgpuResources { device, queue, .. } = &self.wgpu_resources;
+
+        let mut command_encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Command Encoder"),
+        });
+
+        // println!("cpass");
+        // self.cpass(&mut command_encoder);
+
+        // Logged rather than printed, since `export` can stream video over stdout
+        log::debug!("rpass ({})", self.traces[0].frame);
+        self.rpass(&mut command_encoder, view, width, height, hud);
+        queue.submit(Some(command_encoder.finish()));
+    }
+
+    /// Renders the current frame offscreen at `scale` times the window's resolution, without
+    /// advancing any trace.
+    pub fn screenshot(&self, scale: u32) -> anyhow::Result<RgbaImage> {
+        let mut offscreen = Offscreen::new(&self.wgpu_resources, scale)?;
+        self.render_to(offscreen.view(), offscreen.width, offscreen.height);
+        offscreen.image(&self.wgpu_resources)
+    }
+
+    fn rpass(
+        &self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        width: u32,
+        height: u32,
+        hud: bool,
+    ) {
         let color_attachments = [wgpu::RenderPassColorAttachment {
             view,
             resolve_target: None,
             ops: wgpu::Operations {
-                load: wgpu::LoadOp::Clear(wgpu::Color::default()),
+                load: wgpu::LoadOp::Clear(self.traces[0].color_scheme().clear_color()),
                 store: true,
             },
         }];
@@ -139,37 +829,172 @@ impl Oscilloscope {
         {
             let mut rpass = command_encoder.begin_render_pass(&render_pass_descriptor);
             rpass.set_pipeline(&self.render_pipeline);
-            rpass.set_vertex_buffer(0, self.state.instance_buffer.slice(..)); // TODO: fill in this buffer
-            rpass.set_bind_group(0, &self.state.uniform_bind_group, &[]);
-            rpass.draw(0..4, 0..(state::SAMPLE_BUFFER_SIZE as u32)); // NOTE: this is one less than instance_buffer len because the last element wouldn't have a pair
+            rpass.set_bind_group(1, &self.camera.bind_group, &[]);
+
+            // Hidden traces give up their panes
+            let visible = self
+                .traces
+                .iter()
+                .filter(|trace| trace.is_visible())
+                .collect::<Vec<_>>();
+            let pane_width = width as f32 / visible.len().max(1) as f32;
+            // Traces are drawn square in the middle of their panes, so figures keep their shape
+            let side = pane_width.min(height as f32);
+            for (i, trace) in visible.into_iter().enumerate() {
+                if self.layout == Layout::SideBySide {
+                    let x = i as f32 * pane_width + (pane_width - side) / 2.0;
+                    let y = (height as f32 - side) / 2.0;
+                    rpass.set_viewport(x, y, side, side, 0.0, 1.0);
+                }
+                rpass.set_vertex_buffer(0, trace.instance_buffer.slice(..));
+                rpass.set_bind_group(0, &trace.uniform_bind_group, &[]);
+                rpass.draw(0..4, 0..(state::SAMPLE_BUFFER_SIZE as u32)); // NOTE: this is one less than instance_buffer len because the last element mirrors slot 0
+            }
+            rpass.set_viewport(0.0, 0.0, width as f32, height as f32, 0.0, 1.0);
+            self.draw_overlays(&mut rpass, hud);
         }
         command_encoder.pop_debug_group();
     }
+
+    fn draw_overlays<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>, hud: bool) {
+        if hud && self.overlay.is_empty() {
+            self.hud.draw(rpass);
+        } else {
+            self.overlay.draw(rpass);
+        }
+    }
+
+    /// Draws the HUD, or any shader errors, over what's already in `view`, for when another
+    /// visualizer has drawn the frame.
+    pub fn render_overlays(&self, view: &wgpu::TextureView) {
+        let WgpuResources { device, queue, .. } = &self.wgpu_resources;
+        let mut command_encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Overlay Command Encoder"),
+        });
+        {
+            let mut rpass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Overlay Render Pass"),
+                color_attachments: &[wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+            self.draw_overlays(&mut rpass, true);
+        }
+        queue.submit(Some(command_encoder.finish()));
+    }
+
+    /// Shares each trace's new samples with other visualizers.
+    fn share_audio(&self, frames: Vec<Vec<Frame>>) {
+        let Audio { traces } = &mut *self.audio.borrow_mut();
+        traces.resize_with(self.traces.len(), Default::default);
+        for ((trace, audio), frames) in self.traces.iter().zip(traces).zip(frames) {
+            audio.sample_rate = trace.sample_rate();
+            audio.color = trace.color_scheme().primary;
+            audio.visible = trace.is_visible();
+            audio.push(&frames);
+        }
+    }
 }
 
 impl Shaderer for Oscilloscope {
-    fn new(wgpu_resources: WgpuResources) -> Self {
-        Oscilloscope::new(wgpu_resources)
+    /// Plays the default WAV, sharing its samples through `audio`.
+    fn new(wgpu_resources: WgpuResources, audio: SharedAudio) -> anyhow::Result<Self> {
+        let mut oscilloscope = Oscilloscope::new(wgpu_resources)?;
+        oscilloscope.audio = audio;
+        Ok(oscilloscope)
     }
 
     fn update(&mut self) {
-        self.state.update_uniforms();
-        self.state.update_instances(&self.wgpu_resources.queue);
-        self.state.write_queue(&self.wgpu_resources.queue);
+        if self.shaders_checked.elapsed() >= SHADER_CHECK_INTERVAL {
+            self.shaders_checked = Instant::now();
+            // Both are checked, so neither change is left to be noticed next time
+            let render_changed = self.render_shader.changed();
+            let compute_changed = self.compute_shader.changed();
+            if render_changed || compute_changed {
+                self.reload_shaders();
+            }
+        }
+        self.update_camera();
+        let WgpuResources { config, .. } = &self.wgpu_resources;
+        let mut frames = Vec::with_capacity(self.traces.len());
+        for trace in &mut self.traces {
+            trace.set_resolution(config.width, config.height);
+            trace.update_uniforms();
+            frames.push(trace.update_instances(&self.wgpu_resources.queue));
+            trace.write_queue(&self.wgpu_resources.queue);
+        }
+        self.share_audio(frames);
     }
 
     fn render(&self, view: &wgpu::TextureView) {
-        let WgpuResources { device, queue, .. } = &self.wgpu_resources;
+        let WgpuResources { config, .. } = &self.wgpu_resources;
+        self.draw(view, config.width, config.height, true);
+    }
 
-        let mut command_encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
-            label: Some("Command Encoder"),
-        });
+    /// Resizes the window's surface, or what's rendered into without one, keeping the XYZ view's
+    /// camera in proportion.
+    fn resize(&mut self, width: u32, height: u32) {
+        self.wgpu_resources.resize(width, height);
+        let WgpuResources { config, .. } = &self.wgpu_resources;
+        self.camera.camera.aspect = config.width as f32 / config.height as f32;
+    }
 
-        // println!("cpass");
-        // self.cpass(&mut command_encoder);
+    /// Handles camera input while in the XYZ view.
+    fn input(&mut self, event: &WindowEvent) -> bool {
+        match self.traces[0].view_mode() {
+            state::ViewMode::Xyz => self.camera.controller.process_events(event),
+            state::ViewMode::Xy => false,
+        }
+    }
 
-        println!("rpass ({})", self.state.frame);
-        self.rpass(&mut command_encoder, view);
-        queue.submit(Some(command_encoder.finish()));
+    fn params(&self) -> Vec<Param> {
+        let trace = &self.traces[0];
+        vec![
+            Param {
+                name: "thickness",
+                description: "line thickness, where the screen is 2 wide",
+                value: trace.uniforms.line_thickness,
+                min: 0.0,
+                max: 0.1,
+            },
+            Param {
+                name: "brightness",
+                description: "scales the traces' colors",
+                value: trace.brightness(),
+                min: 0.0,
+                max: 10.0,
+            },
+            Param {
+                name: "decay",
+                description: "how quickly traces fade",
+                value: trace.uniforms.fade_strength,
+                min: 0.0,
+                max: 100.0,
+            },
+            Param {
+                name: "gain",
+                description: "scales the traces on screen",
+                value: trace.gain(),
+                min: 0.0,
+                max: 100.0,
+            },
+        ]
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> anyhow::Result<()> {
+        Param::check(&self.params(), name, value)?;
+        match name {
+            "thickness" => self.set_line_thickness(value),
+            "brightness" => self.set_brightness(value),
+            "decay" => self.set_fade_strength(value),
+            _ => self.set_gain(value),
+        }
+        Ok(())
     }
 }
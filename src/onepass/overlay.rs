@@ -0,0 +1,218 @@
+use std::borrow::Cow;
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+use crate::generate::font;
+
+use super::wgpu_resources::WgpuResources;
+
+/// Text is drawn this many pixels per font unit, so capitals are 12 pixels tall.
+const PIXELS_PER_UNIT: f32 = 2.0;
+/// How far text sits from the top left corner, in pixels.
+const MARGIN: f32 = 16.0;
+/// The distance between baselines, in font units.
+const LINE_HEIGHT: f32 = font::CAP_HEIGHT + font::DESCENT + 2.0;
+
+/// Swaps the box drawing characters in compiler errors for ASCII look-alikes, since the font
+/// only has ASCII.
+fn ascii(c: char) -> char {
+    match c {
+        '│' | '┃' | '║' => '|',
+        '─' | '━' | '═' => '-',
+        '┌' | '┐' | '└' | '┘' | '├' | '┤' | '┬' | '┴' | '┼' => '+',
+        _ => c,
+    }
+}
+
+/// FlatVertex is a point that's already in clip space, with a color of its own, for drawing text
+/// and other flat shapes that don't need the traces' shaders.
+#[repr(C)]
+#[derive(Pod, Copy, Zeroable, Clone)]
+pub struct FlatVertex {
+    pub position: [f32; 2],
+    pub color: [f32; 4],
+}
+
+/// Creates a pipeline drawing `FlatVertex`es as `topology`, blended over whatever's there.
+pub fn flat_pipeline(
+    wgpu_resources: &WgpuResources,
+    label: &str,
+    topology: wgpu::PrimitiveTopology,
+) -> wgpu::RenderPipeline {
+    let WgpuResources { device, config, .. } = wgpu_resources;
+    let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+        label: Some(&format!("{} Shader", label)),
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("shaders/overlay.wgsl"))),
+    });
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some(&format!("{} Pipeline Layout", label)),
+        bind_group_layouts: &[],
+        push_constant_ranges: &[],
+    });
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(&format!("{} Pipeline", label)),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "main_vs",
+            buffers: &[wgpu::VertexBufferLayout {
+                array_stride: std::mem::size_of::<FlatVertex>() as wgpu::BufferAddress,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x4],
+            }],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "main_fs",
+            targets: &[wgpu::ColorTargetState {
+                format: config.format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: wgpu::ColorWrites::ALL,
+            }],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology,
+            ..Default::default()
+        },
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
+
+/// Uploads `vertices` for drawing, along with how many there are, or returns `None` if there
+/// aren't any, since wgpu can't make empty buffers.
+pub fn flat_vertices(
+    wgpu_resources: &WgpuResources,
+    label: &str,
+    vertices: &[FlatVertex],
+) -> Option<(wgpu::Buffer, u32)> {
+    if vertices.is_empty() {
+        return None;
+    }
+    let buffer = wgpu_resources
+        .device
+        .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(label),
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+    Some((buffer, vertices.len() as u32))
+}
+
+/// Clears `view` to black and draws `vertices` on it with `pipeline`, for visualizers that are
+/// only flat shapes.
+pub fn render_flat(
+    wgpu_resources: &WgpuResources,
+    view: &wgpu::TextureView,
+    pipeline: &wgpu::RenderPipeline,
+    vertices: &Option<(wgpu::Buffer, u32)>,
+) {
+    let WgpuResources { device, queue, .. } = wgpu_resources;
+    let mut command_encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Flat Command Encoder"),
+    });
+    {
+        let mut rpass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Flat Render Pass"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+        if let Some((buffer, count)) = vertices {
+            rpass.set_pipeline(pipeline);
+            rpass.set_vertex_buffer(0, buffer.slice(..));
+            rpass.draw(0..*count, 0..1);
+        }
+    }
+    queue.submit(Some(command_encoder.finish()));
+}
+
+/// Overlay draws text over the traces in the built-in single-stroke font, eg. to report errors.
+/// It has its own built-in shader, so it keeps working whatever happens to the others.
+pub struct Overlay {
+    pipeline: wgpu::RenderPipeline,
+    // Line list vertices for the current text, and how many there are
+    vertices: Option<(wgpu::Buffer, u32)>,
+}
+
+impl Overlay {
+    pub fn new(wgpu_resources: &WgpuResources) -> Self {
+        Self {
+            pipeline: flat_pipeline(wgpu_resources, "Overlay", wgpu::PrimitiveTopology::LineList),
+            vertices: None,
+        }
+    }
+
+    /// Replaces the overlay's text, laid out from the top left corner of a target the size of
+    /// `wgpu_resources.config`. Lines too long for the target wrap, and empty text clears the
+    /// overlay.
+    pub fn set_text(&mut self, wgpu_resources: &WgpuResources, text: &str, color: [f32; 4]) {
+        let WgpuResources { config, .. } = wgpu_resources;
+        let (width, height) = (config.width as f32, config.height as f32);
+        let advance = font::ADVANCE * PIXELS_PER_UNIT;
+        let columns = Overlay::columns(wgpu_resources);
+        let to_clip = |[x, y]: [f32; 2]| [2.0 * x / width - 1.0, 1.0 - 2.0 * y / height];
+
+        let mut vertices = Vec::new();
+        let rows = text.lines().flat_map(|line| {
+            let chars: Vec<char> = line.replace('\t', "    ").chars().map(ascii).collect();
+            match chars.len() {
+                0 => vec![Vec::new()],
+                _ => chars.chunks(columns).map(<[char]>::to_vec).collect(),
+            }
+        });
+        for (row, chars) in rows.enumerate() {
+            let baseline = MARGIN + (font::CAP_HEIGHT + row as f32 * LINE_HEIGHT) * PIXELS_PER_UNIT;
+            for (column, c) in chars.into_iter().enumerate() {
+                let left = MARGIN + column as f32 * advance;
+                for stroke in font::glyph(c) {
+                    // Each segment of a stroke is its own line in the list
+                    for segment in stroke.windows(2) {
+                        for &[x, y] in segment {
+                            vertices.push(FlatVertex {
+                                position: to_clip([
+                                    left + x * PIXELS_PER_UNIT,
+                                    baseline - y * PIXELS_PER_UNIT,
+                                ]),
+                                color,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        self.vertices = flat_vertices(wgpu_resources, "Overlay Vertex Buffer", &vertices);
+    }
+
+    /// How many characters fit on a line before it wraps, on a target the size of
+    /// `wgpu_resources.config`.
+    pub fn columns(wgpu_resources: &WgpuResources) -> usize {
+        let width = wgpu_resources.config.width as f32;
+        (((width - 2.0 * MARGIN) / (font::ADVANCE * PIXELS_PER_UNIT)) as usize).max(1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_none()
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices = None;
+    }
+
+    pub fn draw<'a>(&'a self, rpass: &mut wgpu::RenderPass<'a>) {
+        if let Some((buffer, count)) = &self.vertices {
+            rpass.set_pipeline(&self.pipeline);
+            rpass.set_vertex_buffer(0, buffer.slice(..));
+            rpass.draw(0..*count, 0..1);
+        }
+    }
+}
@@ -0,0 +1,252 @@
+//! A line-based text protocol for driving the live window from the shell and test harnesses, over
+//! stdin or a Unix domain socket. Each line is a command and its arguments, eg. `set thickness
+//! 0.01`, `seek 30.5` or `screenshot out.png`, and gets exactly one line back: the answer to a
+//! query, `{"ok": true}`, or `{"error": "..."}`. Everything else the window has to say goes to
+//! stderr, so stdout is left to the answers.
+//!
+//! Commands are the OSC addresses without the slashes, so `set dsp 2 cutoff 4000` does what
+//! `/dsp/2/cutoff 4000` does over OSC.
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    sync::mpsc,
+};
+
+use anyhow::{anyhow, bail, Context};
+
+use super::{
+    control::{Command, Request, Requests},
+    osc::{Argument, Message},
+};
+
+/// Splits a line into words, where double quotes keep spaces in a word, eg. a path.
+fn words(line: &str) -> anyhow::Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        let mut word = String::new();
+        if c == '"' {
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some(c) => word.push(c),
+                    None => bail!("unterminated quote"),
+                }
+            }
+        } else {
+            word.push(c);
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                word.push(c);
+            }
+        }
+        words.push(word);
+    }
+    Ok(words)
+}
+
+/// Parses a line into a command. `set` is optional, so `set mode xy` and `mode xy` are the same.
+pub(super) fn parse(line: &str) -> anyhow::Result<Command> {
+    let words = words(line)?;
+    let mut words = words.iter().map(String::as_str).peekable();
+    let mut name = words.next().ok_or_else(|| anyhow!("empty command"))?;
+    if name == "set" {
+        name = words.next().ok_or_else(|| anyhow!("set what?"))?;
+    }
+
+    // Some addresses have more parts, eg. the node and parameter in `dsp 2 cutoff`
+    let mut address = format!("/{}", name);
+    let remaining = words.len();
+    let parts = match (name, words.peek()) {
+        ("dsp", _) => 2,
+        ("param" | "source", _) => 1,
+        ("blanking", Some(&"threshold")) | ("preset", Some(&"save")) => 1,
+        // A parameter and its value, rather than which visualizer to switch to
+        ("visualizer", _) if remaining == 2 => 1,
+        _ => 0,
+    };
+    for part in words.by_ref().take(parts) {
+        address += "/";
+        address += part;
+    }
+
+    let arguments = words
+        .map(|word| match word.parse() {
+            Ok(number) => Argument::Float(number),
+            Err(_) => Argument::String(word.to_string()),
+        })
+        .collect();
+    Message { address, arguments }.command()
+}
+
+/// Runs commands from `reader` until it ends or whatever applies them goes away, writing each
+/// answer to `writer`.
+fn serve(
+    reader: impl BufRead,
+    mut writer: impl Write,
+    requests: &impl Requests,
+) -> anyhow::Result<()> {
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let answer = match parse(line) {
+            Ok(command) => {
+                let (reply, answer) = mpsc::channel();
+                let request = Request {
+                    command,
+                    reply: Some(reply),
+                };
+                if !requests.send(request) {
+                    break;
+                }
+                match answer.recv() {
+                    Ok(answer) => answer,
+                    Err(_) => break,
+                }
+            }
+            Err(e) => answer(Err(e)),
+        };
+        writeln!(writer, "{}", answer)?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+/// The line sent back for a command: its answer if it's a query, or whether it worked.
+pub fn answer(answer: anyhow::Result<Option<String>>) -> String {
+    match answer {
+        Ok(Some(answer)) => answer,
+        Ok(None) => r#"{"ok": true}"#.to_string(),
+        Err(e) => format!(r#"{{"error": {}}}"#, json_string(&format!("{:#}", e))),
+    }
+}
+
+/// Quotes `string` as a JSON string.
+pub(super) fn json_string(string: &str) -> String {
+    let mut quoted = String::with_capacity(string.len() + 2);
+    quoted.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => quoted.push_str(r#"\""#),
+            '\\' => quoted.push_str(r"\\"),
+            '\n' => quoted.push_str(r"\n"),
+            '\r' => quoted.push_str(r"\r"),
+            '\t' => quoted.push_str(r"\t"),
+            c if c < ' ' => quoted.push_str(&format!(r"\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Writes `number` for JSON, which has no NaN or infinity, so those are `null`.
+pub(super) fn json_number(number: f32) -> String {
+    if number.is_finite() {
+        number.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+/// Takes commands from stdin, answering on stdout, or else from clients of a Unix socket created
+/// at `target`, each on a thread of its own.
+pub fn listen(target: &str, requests: impl Requests) -> anyhow::Result<()> {
+    if target == "stdin" {
+        std::thread::Builder::new()
+            .name("remote".to_string())
+            .spawn(move || {
+                let stdin = std::io::stdin();
+                if let Err(e) = serve(stdin.lock(), std::io::stdout(), &requests) {
+                    log::error!("remote: {:#}", e);
+                }
+            })?;
+        return Ok(());
+    }
+    listen_unix(target, requests)
+}
+
+#[cfg(unix)]
+fn listen_unix(path: &str, requests: impl Requests) -> anyhow::Result<()> {
+    use std::os::unix::{fs::FileTypeExt, net::UnixListener};
+
+    // A socket left behind by an earlier run would stop it being bound again
+    if std::fs::metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+        std::fs::remove_file(path).with_context(|| format!("removing old socket {}", path))?;
+    }
+    let listener =
+        UnixListener::bind(path).with_context(|| format!("listening on socket {}", path))?;
+    std::thread::Builder::new()
+        .name("remote".to_string())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        log::warn!("remote: {}", e);
+                        continue;
+                    }
+                };
+                let requests = requests.clone();
+                let spawned = std::thread::Builder::new()
+                    .name("remote client".to_string())
+                    .spawn(move || {
+                        let served = stream
+                            .try_clone()
+                            .map_err(anyhow::Error::from)
+                            .and_then(|reader| serve(BufReader::new(reader), stream, &requests));
+                        if let Err(e) = served {
+                            log::warn!("remote: {:#}", e);
+                        }
+                    });
+                if let Err(e) = spawned {
+                    log::warn!("remote: {}", e);
+                }
+            }
+        })?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn listen_unix(path: &str, _requests: impl Requests) -> anyhow::Result<()> {
+    bail!(
+        "can't listen on {}, Unix sockets aren't supported here, use --control=stdin",
+        path
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+
+    use super::*;
+
+    #[test]
+    fn quotes_json_strings() {
+        assert_eq!(json_string("p31"), r#""p31""#);
+        assert_eq!(
+            json_string("C:\\scope \"a\"\n\u{1}é"),
+            r#""C:\\scope \"a\"\n\u0001é""#
+        );
+    }
+
+    #[test]
+    fn writes_non_finite_numbers_as_null() {
+        assert_eq!(json_number(0.01), "0.01");
+        assert_eq!(json_number(-2.0), "-2");
+        assert_eq!(json_number(f32::NAN), "null");
+        assert_eq!(json_number(f32::INFINITY), "null");
+    }
+
+    #[test]
+    fn answers_errors_as_json() {
+        let error = Err(anyhow!("no file \"a.wav\"").context("loading"));
+        assert_eq!(answer(error), r#"{"error": "loading: no file \"a.wav\""}"#);
+        assert_eq!(answer(Ok(None)), r#"{"ok": true}"#);
+    }
+}
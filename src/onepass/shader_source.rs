@@ -0,0 +1,203 @@
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::{anyhow, bail, Context};
+
+/// ShaderSource is where one of the oscilloscope's shaders comes from: baked into the binary, or
+/// a WGSL file on disk that's watched for changes.
+pub enum ShaderSource {
+    Builtin(&'static str),
+    File {
+        path: PathBuf,
+        // When the file was last seen to change, or `None` if it couldn't be read
+        modified: Option<SystemTime>,
+    },
+}
+
+impl ShaderSource {
+    /// Uses the file at `path`, whether or not it exists yet.
+    pub fn file(path: &Path) -> Self {
+        ShaderSource::File {
+            path: path.to_path_buf(),
+            modified: modified(path),
+        }
+    }
+
+    /// Uses `dir/name` if it exists, or else the built-in shader.
+    pub fn from_dir(dir: &Path, name: &str, builtin: &'static str) -> Self {
+        let path = dir.join(name);
+        if path.exists() {
+            ShaderSource::file(&path)
+        } else {
+            ShaderSource::Builtin(builtin)
+        }
+    }
+
+    pub fn load(&self) -> anyhow::Result<Cow<'static, str>> {
+        match self {
+            ShaderSource::Builtin(source) => Ok(Cow::Borrowed(source)),
+            ShaderSource::File { path, .. } => std::fs::read_to_string(path)
+                .map(Cow::Owned)
+                .with_context(|| format!("reading {}", path.display())),
+        }
+    }
+
+    /// Whether the file has changed since it was loaded or last checked.
+    pub fn changed(&mut self) -> bool {
+        match self {
+            ShaderSource::Builtin(_) => false,
+            ShaderSource::File { path, modified } => {
+                let now = self::modified(path);
+                let changed = now != *modified;
+                *modified = now;
+                changed
+            }
+        }
+    }
+
+    /// A name for the shader in error messages.
+    pub fn name(&self) -> Cow<'static, str> {
+        match self {
+            ShaderSource::Builtin(_) => Cow::Borrowed("built-in shader"),
+            ShaderSource::File { path, .. } => Cow::Owned(path.display().to_string()),
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Parses and validates WGSL, so mistakes are reported with the lines they're on rather than
+/// when the pipeline is created. Returns the parsed module for further checks.
+pub fn validate(source: &str) -> anyhow::Result<naga::Module> {
+    let module = naga::front::wgsl::parse_str(source)
+        .map_err(|e| anyhow!("{}", e.emit_to_string(source).trim_end()))?;
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    )
+    .validate(&module)
+    .map_err(|e| {
+        // Point at the lines the error is about, since the error alone rarely says
+        let mut message = e.to_string();
+        for (span, label) in e.spans() {
+            if let Some(range) = span.to_range() {
+                let line = source[..range.start].matches('\n').count() + 1;
+                message += &format!("\n  line {}: {}", line, label);
+            }
+        }
+        anyhow!(message)
+    })?;
+    Ok(module)
+}
+
+/// Checks that a render shader fits the pipeline Oscilloscope draws traces with, so a custom
+/// shader that doesn't is reported before it replaces the one that's working: it needs `main_vs`
+/// and `main_fs` entry points, can only take the `curr` and `next` instance attributes at
+/// locations 0 and 1, must output a color at location 0, and can only use uniform blocks at
+/// binding 0 of each group, no bigger than the buffer bound there. `uniform_sizes[group]` is
+/// that buffer's size in bytes.
+pub fn check_render_interface(module: &naga::Module, uniform_sizes: &[u64]) -> anyhow::Result<()> {
+    let entry_point = |name: &str, stage: naga::ShaderStage| {
+        module
+            .entry_points
+            .iter()
+            .find(|entry_point| entry_point.name == name && entry_point.stage == stage)
+            .ok_or_else(|| anyhow!("no {:?} entry point named {}", stage, name))
+    };
+    let vertex = entry_point("main_vs", naga::ShaderStage::Vertex)?;
+    let fragment = entry_point("main_fs", naga::ShaderStage::Fragment)?;
+
+    let mut inputs = Vec::new();
+    for argument in &vertex.function.arguments {
+        locations(module, argument.ty, argument.binding.as_ref(), &mut inputs);
+    }
+    for (location, ty) in inputs {
+        let is_vec3 = matches!(
+            module.types[ty].inner,
+            naga::TypeInner::Vector {
+                size: naga::VectorSize::Tri,
+                kind: naga::ScalarKind::Float,
+                ..
+            }
+        );
+        match location {
+            0 | 1 if !is_vec3 => bail!(
+                "main_vs input at location {} must be a vec3<f32>, like the samples it's given",
+                location
+            ),
+            0 | 1 => {}
+            _ => bail!(
+                "main_vs input at location {} isn't provided, only curr (0) and next (1) are",
+                location
+            ),
+        }
+    }
+
+    let mut outputs = Vec::new();
+    if let Some(result) = &fragment.function.result {
+        locations(module, result.ty, result.binding.as_ref(), &mut outputs);
+    }
+    if !outputs.iter().any(|&(location, _)| location == 0) {
+        bail!("main_fs must output a color at location 0");
+    }
+
+    for (_, global) in module.global_variables.iter() {
+        let binding = match &global.binding {
+            Some(binding) => binding,
+            None => continue,
+        };
+        let name = global.name.as_deref().unwrap_or("a global");
+        let bound_size = uniform_sizes
+            .get(binding.group as usize)
+            .filter(|_| binding.binding == 0);
+        match (global.class, bound_size) {
+            (naga::StorageClass::Uniform, Some(&bound_size)) => {
+                let size = module.types[global.ty].inner.span(&module.constants) as u64;
+                if size > bound_size {
+                    bail!(
+                        "{} at group {} is {} bytes, but only {} are bound there",
+                        name,
+                        binding.group,
+                        size,
+                        bound_size
+                    );
+                }
+            }
+            _ => bail!(
+                "{} at group {}, binding {} isn't bound, only uniforms at binding 0 of groups 0 \
+                 to {} are",
+                name,
+                binding.group,
+                binding.binding,
+                uniform_sizes.len().saturating_sub(1)
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// Collects the locations an entry point's argument or result is bound to, with their types,
+/// looking inside structs.
+fn locations(
+    module: &naga::Module,
+    ty: naga::Handle<naga::Type>,
+    binding: Option<&naga::Binding>,
+    locations: &mut Vec<(u32, naga::Handle<naga::Type>)>,
+) {
+    match binding {
+        Some(naga::Binding::Location { location, .. }) => locations.push((*location, ty)),
+        Some(naga::Binding::BuiltIn(_)) => {}
+        None => {
+            if let naga::TypeInner::Struct { members, .. } = &module.types[ty].inner {
+                for member in members {
+                    self::locations(module, member.ty, member.binding.as_ref(), locations);
+                }
+            }
+        }
+    }
+}
@@ -1,9 +1,11 @@
 // TODO: somehow get rid of these?
+// Frames are three channels wide (X, Y, Z), but vec3 arrays have a stride of 16 in storage
+// buffers, so the frames are addressed as flat arrays of scalars instead.
 struct Samples {
-    samples: [[stride(8)]] array<vec2<i32>>;
+    samples: [[stride(4)]] array<i32>;
 };
 struct Vertices {
-    vertices: [[stride(8)]] array<vec2<f32>>;
+    vertices: [[stride(4)]] array<f32>;
 };
 
 [[group(0), binding(0)]] var<storage, read> samples : Samples;
@@ -16,6 +18,8 @@ let i16_convert: f32 = 0.00003051850947599719;
 
 [[stage(compute), workgroup_size(64)]]
 fn main([[builtin(global_invocation_id)]] global_invocation_id: vec3<u32>) {
-  let index = global_invocation_id.x;
-  vertices.vertices[index] = vec2<f32>(samples.samples[index]) * i16_convert;
+  let index = global_invocation_id.x * 3u;
+  vertices.vertices[index] = f32(samples.samples[index]) * i16_convert;
+  vertices.vertices[index + 1u] = f32(samples.samples[index + 1u]) * i16_convert;
+  vertices.vertices[index + 2u] = f32(samples.samples[index + 2u]) * i16_convert;
 }
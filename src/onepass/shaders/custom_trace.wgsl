@@ -0,0 +1,111 @@
+// A starting point for custom trace shaders, loaded with --trace-shader=FILE.
+//
+// The beam is drawn as one quad per pair of neighbouring samples: main_vs runs for the 4 corners
+// of every segment, given the segment's two samples as instance attributes, and main_fs colors
+// the result. Blending is additive, so anything drawn adds light on top of the rest.
+//
+// The interface below is checked before a shader is used: keep the entry point names, the
+// inputs at locations 0 and 1, the color output at location 0 and the two bind groups. Members
+// can be left off the end of Uniforms, but not reordered.
+
+struct VertexInput {
+    // xy is the beam position in -1..1, z its intensity (or depth in the XYZ view)
+    [[location(0)]] curr: vec3<f32>;
+    [[location(1)]] next: vec3<f32>;
+    // 0..4, the corner of the segment's quad
+    [[builtin(vertex_index)]] v_id: u32;
+    // Which segment this is, a slot in the ring buffer of samples
+    [[builtin(instance_index)]] i_id: u32;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] color: vec4<f32>;
+    // x runs -1..1 across the beam, y 0..1 along the segment
+    [[location(1)]] beam: vec2<f32>;
+};
+
+struct Uniforms {
+    frame: u32;               // frames drawn so far
+    time: f32;                // seconds since the start
+    line_thickness: f32;      // half the beam's width, in clip space
+    count: f32;               // slots in the ring buffer
+    write_head: u32;          // slot after the newest sample
+    fade_curve: u32;          // F key: 0 none, 1 linear, 2 exponential, 3 power
+    fade_strength: f32;
+    blanking_threshold: f32;
+    hard_blanking: u32;       // B key
+    view_mode: u32;           // Tab key: 0 XY, 1 XYZ
+    fog_density: f32;
+    size_attenuation: f32;
+    primary_color: vec4<f32>; // C key, the color scheme's colors
+    secondary_color: vec4<f32>;
+    color_mode: u32;          // 0 solid, 1 by age, 2 by velocity
+    resolution: vec2<f32>;    // size of the target in pixels
+    levels: vec4<f32>;        // RMS of x, y and z over the last frame's samples, and their peak
+    params: array<vec4<f32>, 2>; // --shader-params, 8 floats in order
+};
+
+struct Camera {
+    view_proj: mat4x4<f32>;
+};
+
+[[group(0), binding(0)]] var<uniform> uniforms: Uniforms;
+[[group(1), binding(0)]] var<uniform> camera: Camera;
+
+// Age of a segment in [0, 1], where 0 is the newest sample and 1 the oldest.
+fn age(i_id: u32) -> f32 {
+    let count = u32(uniforms.count);
+    let newest = (uniforms.write_head + count - 1u) % count;
+    return f32((newest + count - i_id) % count) / uniforms.count;
+}
+
+[[stage(vertex)]]
+fn main_vs(in: VertexInput) -> VertexOutput {
+    // -1 or 1 across the beam, 0 or 1 along it
+    let across = -1.0 + 2.0 * f32(in.v_id % 2u);
+    let along = f32(in.v_id / 2u);
+
+    var curr = in.curr.xy;
+    var next = in.next.xy;
+    var intensity = clamp(min(in.curr.z, in.next.z), 0.0, 1.0);
+    // In the XYZ view, z is depth rather than intensity
+    if (uniforms.view_mode == 1u) {
+        let curr_clip = camera.view_proj * vec4<f32>(in.curr, 1.0);
+        let next_clip = camera.view_proj * vec4<f32>(in.next, 1.0);
+        curr = curr_clip.xy / curr_clip.w;
+        next = next_clip.xy / next_clip.w;
+        intensity = 1.0;
+    }
+
+    let between = next - curr;
+    // Long segments are jumps between shapes, and the newest joins onto the oldest
+    var w = step(length(between), 0.04) * intensity;
+    if (age(in.i_id) == 0.0) {
+        w = 0.0;
+    }
+
+    // The first parameter widens the beam, and it swells with the peak level
+    let width = uniforms.line_thickness * (1.0 + uniforms.params[0].x) * (1.0 + uniforms.levels.w);
+    let normal = normalize(vec2<f32>(-between.y, between.x)) * width;
+    let pos = mix(curr, next, along) + normal * across * w;
+
+    // Hue cycles with time, at a speed set by the second parameter
+    let hue = uniforms.time * uniforms.params[0].y + age(in.i_id);
+    let rainbow = 0.5 + 0.5 * cos(6.283 * (vec3<f32>(hue) + vec3<f32>(0.0, 0.33, 0.67)));
+    let color = mix(uniforms.primary_color.rgb, rainbow, clamp(uniforms.params[0].z, 0.0, 1.0));
+
+    let brightness = w * exp(-4.0 * age(in.i_id));
+    return VertexOutput(
+        vec4<f32>(pos, 0.0, 1.0),
+        vec4<f32>(color * brightness, 1.0),
+        vec2<f32>(across, along),
+    );
+}
+
+[[stage(fragment)]]
+fn main_fs(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    // Brightest along the middle of the beam, fading out to its edges
+    let falloff = 1.0 - in.beam.x * in.beam.x;
+    return vec4<f32>(in.color.rgb * falloff, 1.0);
+}
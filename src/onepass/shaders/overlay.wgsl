@@ -0,0 +1,20 @@
+struct VertexInput {
+    // Already in clip space
+    [[location(0)]] position: vec2<f32>;
+    [[location(1)]] color: vec4<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] color: vec4<f32>;
+};
+
+[[stage(vertex)]]
+fn main_vs(in: VertexInput) -> VertexOutput {
+    return VertexOutput(vec4<f32>(in.position, 0.0, 1.0), in.color);
+}
+
+[[stage(fragment)]]
+fn main_fs(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return in.color;
+}
@@ -4,9 +4,11 @@ struct VertexOutput {
 };
 
 struct VertexInput {
-    [[location(0)]] curr: vec2<f32>;
-    [[location(1)]] next: vec2<f32>;
+    // xy is the beam position, z its intensity
+    [[location(0)]] curr: vec3<f32>;
+    [[location(1)]] next: vec3<f32>;
     [[builtin(vertex_index)]] v_id: u32;
+    [[builtin(instance_index)]] i_id: u32;
 };
 
 struct Uniforms {
@@ -14,9 +16,87 @@ struct Uniforms {
     time: f32;
     line_thickness: f32;
     count: f32;
+    write_head: u32;
+    fade_curve: u32;
+    fade_strength: f32;
+    blanking_threshold: f32;
+    hard_blanking: u32;
+    view_mode: u32;
+    fog_density: f32;
+    size_attenuation: f32;
+    primary_color: vec4<f32>;
+    secondary_color: vec4<f32>;
+    color_mode: u32;
+    resolution: vec2<f32>;
+    levels: vec4<f32>;
+    params: array<vec4<f32>, 2>;
+};
+
+struct Camera {
+    view_proj: mat4x4<f32>;
 };
 
 [[group(0), binding(0)]] var<uniform> uniforms: Uniforms;
+[[group(1), binding(0)]] var<uniform> camera: Camera;
+
+// Must match the discriminants of state::ViewMode
+let MODE_XY: u32 = 0u;
+let MODE_XYZ: u32 = 1u;
+
+// Must match the discriminants of color::ColorMode
+let COLOR_SOLID: u32 = 0u;
+let COLOR_AGE: u32 = 1u;
+let COLOR_VELOCITY: u32 = 2u;
+
+// Must match the discriminants of state::FadeCurve
+let FADE_NONE: u32 = 0u;
+let FADE_LINEAR: u32 = 1u;
+let FADE_EXPONENTIAL: u32 = 2u;
+let FADE_POWER: u32 = 3u;
+
+// Age of an instance in [0, 1], where 0 is the newest sample and 1 the oldest.
+fn age(i_id: u32) -> f32 {
+    let count = u32(uniforms.count);
+    let newest = (uniforms.write_head + count - 1u) % count;
+    return f32((newest + count - i_id) % count) / uniforms.count;
+}
+
+fn fade(age: f32) -> f32 {
+    if (uniforms.fade_curve == FADE_LINEAR) {
+        return 1.0 - age;
+    }
+    if (uniforms.fade_curve == FADE_EXPONENTIAL) {
+        return exp(-uniforms.fade_strength * age);
+    }
+    if (uniforms.fade_curve == FADE_POWER) {
+        return pow(1.0 - age, uniforms.fade_strength);
+    }
+    return 1.0;
+}
+
+// Trace color for a segment, given its age and its length on screen.
+fn trace_color(age: f32, length: f32) -> vec3<f32> {
+    var t = 0.0;
+    if (uniforms.color_mode == COLOR_AGE) {
+        t = age;
+    }
+    if (uniforms.color_mode == COLOR_VELOCITY) {
+        // Segments get hidden at a length of 0.04, so that's as fast as a visible beam gets
+        t = clamp(length / 0.04, 0.0, 1.0);
+    }
+    return mix(uniforms.primary_color.rgb, uniforms.secondary_color.rgb, t);
+}
+
+// Brightness of the beam for a Z sample. In the XYZ view Z is depth, so the beam stays on.
+fn intensity(z: f32) -> f32 {
+    if (uniforms.view_mode == MODE_XYZ) {
+        return 1.0;
+    }
+    if (uniforms.hard_blanking != 0u) {
+        return step(uniforms.blanking_threshold, z);
+    }
+    return clamp(z, 0.0, 1.0);
+}
 
 [[stage(vertex)]]
 fn main_vs(in: VertexInput) -> VertexOutput {
@@ -24,19 +104,52 @@ fn main_vs(in: VertexInput) -> VertexOutput {
     x = -1.0 + 2.0 * x;
     var y = f32(in.v_id / 2u);
 
-    var between = in.next - in.curr;
+    var curr = in.curr.xy;
+    var next = in.next.xy;
+    // Distance of each end from the camera, used for depth cues in the XYZ view.
+    var curr_depth = 1.0;
+    var next_depth = 1.0;
+    if (uniforms.view_mode == MODE_XYZ) {
+        let curr_clip = camera.view_proj * vec4<f32>(in.curr, 1.0);
+        let next_clip = camera.view_proj * vec4<f32>(in.next, 1.0);
+        curr = curr_clip.xy / curr_clip.w;
+        next = next_clip.xy / next_clip.w;
+        curr_depth = curr_clip.w;
+        next_depth = next_clip.w;
+    }
+    let depth = mix(curr_depth, next_depth, f32(in.v_id / 2u));
+
+    var between = next - curr;
     var w = 1.0 - 10.0*length(between);
     w = w * step(0.6, w);
 
-    var color = vec3<f32>( 1.0, 1.0, 0.0 );
-    var pos = in.curr + vec2<f32>(x,y);
+    // Blanking either end hides the whole segment, so retrace lines disappear.
+    w = w * min(intensity(in.curr.z), intensity(in.next.z));
 
-    var norm = normalize(vec2<f32>(-between.y, between.x)) * uniforms.line_thickness * w;
+    // The newest sample's segment would join onto the oldest sample across the ring seam.
+    if (age(in.i_id) == 0.0) {
+        w = 0.0;
+    }
 
-    pos = (1.0-y) * in.curr + y * in.next;
+    var color = trace_color(age(in.i_id), length(between));
+    var pos = curr + vec2<f32>(x,y);
+
+    var thickness = uniforms.line_thickness;
+    var fog = 1.0;
+    if (uniforms.view_mode == MODE_XYZ) {
+        // new_camera places the eye 2 units from the origin, so lines there keep their thickness
+        thickness = thickness * mix(1.0, 2.0 / depth, uniforms.size_attenuation);
+        fog = exp(-uniforms.fog_density * max(depth - 2.0, 0.0));
+    }
+
+    var norm = normalize(vec2<f32>(-between.y, between.x)) * thickness * w;
+
+    pos = (1.0-y) * curr + y * next;
     pos = pos + norm * x;
 
     var z = mix(0.0, f32(in.v_id), uniforms.count);
+
+    w = w * fade(age(in.i_id)) * fog;
     color = mix(vec3<f32>(0.0, 0.0, 0.0), color, w);
 
     return VertexOutput(
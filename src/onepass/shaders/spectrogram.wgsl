@@ -0,0 +1,41 @@
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    // From (0, 0) at the bottom left of the screen to (1, 1) at the top right
+    [[location(0)]] uv: vec2<f32>;
+};
+
+struct Uniforms {
+    color: vec4<f32>;
+    // The column that's written next, which is also the oldest
+    head: u32;
+    // How many columns have been written, up to the width of the levels
+    columns: u32;
+    floor: f32;
+    padding: u32;
+};
+
+[[group(0), binding(0)]] var<uniform> uniforms: Uniforms;
+// The level in dB of each band, bottom to top, in a ring of columns
+[[group(0), binding(1)]] var levels: texture_2d<f32>;
+
+// A single triangle that covers the screen
+[[stage(vertex)]]
+fn main_vs([[builtin(vertex_index)]] index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return VertexOutput(vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0), uv);
+}
+
+[[stage(fragment)]]
+fn main_fs(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let size = textureDimensions(levels);
+    let column = min(i32(in.uv.x * f32(size.x)), size.x - 1);
+    // The newest column is on the right, and nothing's been written left of the oldest
+    if (size.x - 1 - column >= i32(uniforms.columns)) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+    let x = (i32(uniforms.head) + column) % size.x;
+    let y = min(i32(in.uv.y * f32(size.y)), size.y - 1);
+    let db = textureLoad(levels, vec2<i32>(x, y), 0).r;
+    let level = clamp((db - uniforms.floor) / -uniforms.floor, 0.0, 1.0);
+    return vec4<f32>(uniforms.color.rgb * level, 1.0);
+}
@@ -0,0 +1,514 @@
+//! The spectrum and spectrogram visualizers, which show the traces' frequencies rather than their
+//! shapes.
+
+use std::{borrow::Cow, f32::consts::TAU, num::NonZeroU32};
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+use super::{
+    overlay::{flat_pipeline, flat_vertices, render_flat, FlatVertex},
+    visualizer::{Param, SharedAudio, TraceAudio},
+    wgpu_resources::WgpuResources,
+    Shaderer,
+};
+
+/// How many samples each spectrum is taken over, which has to be a power of two.
+const FFT_SIZE: usize = 4096;
+/// The frequency at the left of the screen, in Hz. The right is half the sample rate.
+const LOWEST_FREQUENCY: f32 = 20.0;
+/// Grid lines are drawn at these frequencies, and every 20 dB.
+const GRID_FREQUENCIES: [f32; 3] = [100.0, 1000.0, 10000.0];
+const GRID_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.15];
+/// How many frames of history the spectrogram shows, and how many bands each is split into.
+const SPECTROGRAM_COLUMNS: usize = 256;
+const SPECTROGRAM_ROWS: usize = 128;
+
+/// An in-place radix-2 FFT, where `re` and `im` are a power of two long.
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut length = 2;
+    while length <= n {
+        let angle = -TAU / length as f32;
+        for start in (0..n).step_by(length) {
+            for k in 0..length / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + length / 2);
+                let (b_re, b_im) = (re[b] * cos - im[b] * sin, re[b] * sin + im[b] * cos);
+                re[b] = re[a] - b_re;
+                im[b] = im[a] - b_im;
+                re[a] += b_re;
+                im[a] += b_im;
+            }
+        }
+        length <<= 1;
+    }
+}
+
+/// The level of each frequency bin over `trace`'s latest samples, mixed to mono, in dB where 0
+/// is a full scale sine. Bin `k` is at `k * sample_rate / FFT_SIZE` Hz.
+fn spectrum(trace: &TraceAudio) -> Vec<f32> {
+    let mut re = vec![0.0; FFT_SIZE];
+    let mut im = vec![0.0; FFT_SIZE];
+    // Until there are enough samples, the start is left silent
+    let count = trace.samples.len().min(FFT_SIZE);
+    for (i, [x, y]) in (FFT_SIZE - count..).zip(trace.latest(count)) {
+        let hann = 0.5 - 0.5 * (TAU * i as f32 / (FFT_SIZE - 1) as f32).cos();
+        re[i] = 0.5 * (x + y) * hann;
+    }
+    fft(&mut re, &mut im);
+    // The Hann window halves the amplitude, and half of it is in the mirrored bins
+    let scale = 4.0 / FFT_SIZE as f32;
+    re.iter()
+        .zip(&im)
+        .take(FFT_SIZE / 2)
+        .map(|(re, im)| 20.0 * (scale * re.hypot(*im)).max(1e-10).log10())
+        .collect()
+}
+
+/// Where `frequency` goes across the screen, from -1 at the lowest to 1 at `nyquist`, on a log
+/// scale. Returns `None` if `nyquist` isn't above the lowest frequency, which leaves no room.
+fn frequency_x(frequency: f32, nyquist: f32) -> Option<f32> {
+    if nyquist <= LOWEST_FREQUENCY {
+        return None;
+    }
+    Some(2.0 * (frequency / LOWEST_FREQUENCY).ln() / (nyquist / LOWEST_FREQUENCY).ln() - 1.0)
+}
+
+/// How far a level in dB is between `floor` and 0, from 0 to 1.
+fn level(db: f32, floor: f32) -> f32 {
+    ((db - floor) / -floor).clamp(0.0, 1.0)
+}
+
+fn line(vertices: &mut Vec<FlatVertex>, from: [f32; 2], to: [f32; 2], color: [f32; 4]) {
+    vertices.push(FlatVertex {
+        position: from,
+        color,
+    });
+    vertices.push(FlatVertex {
+        position: to,
+        color,
+    });
+}
+
+fn floor_param(floor: f32) -> Param {
+    Param {
+        name: "floor",
+        description: "the quietest level shown, in dB",
+        value: floor,
+        min: -200.0,
+        max: -10.0,
+    }
+}
+
+/// Spectrum draws each visible trace's spectrum as a line, with frequency across and level up.
+pub struct Spectrum {
+    wgpu_resources: WgpuResources,
+    audio: SharedAudio,
+    pipeline: wgpu::RenderPipeline,
+    vertices: Option<(wgpu::Buffer, u32)>,
+    floor: f32,
+    /// How much of each frame's spectrum is the last one's, so it's easier to read.
+    smoothing: f32,
+    // Each trace's smoothed spectrum
+    smoothed: Vec<Vec<f32>>,
+}
+
+impl Shaderer for Spectrum {
+    fn new(wgpu_resources: WgpuResources, audio: SharedAudio) -> anyhow::Result<Self> {
+        Ok(Self {
+            pipeline: flat_pipeline(
+                &wgpu_resources,
+                "Spectrum",
+                wgpu::PrimitiveTopology::LineList,
+            ),
+            wgpu_resources,
+            audio,
+            vertices: None,
+            floor: -90.0,
+            smoothing: 0.6,
+            smoothed: Vec::new(),
+        })
+    }
+
+    fn update(&mut self) {
+        let audio = self.audio.borrow();
+        self.smoothed.resize_with(audio.traces.len(), Vec::new);
+
+        let mut vertices = Vec::new();
+        let mut db = 0.0;
+        while db > self.floor {
+            let y = 2.0 * level(db, self.floor) - 1.0;
+            line(&mut vertices, [-1.0, y], [1.0, y], GRID_COLOR);
+            db -= 20.0;
+        }
+        let nyquist = audio
+            .traces
+            .first()
+            .map_or(22050.0, |trace| trace.sample_rate as f32 / 2.0);
+        for frequency in GRID_FREQUENCIES {
+            if let Some(x) = frequency_x(frequency, nyquist) {
+                line(&mut vertices, [x, -1.0], [x, 1.0], GRID_COLOR);
+            }
+        }
+
+        for (trace, smoothed) in audio.traces.iter().zip(&mut self.smoothed) {
+            if trace.new_samples > 0 || smoothed.is_empty() {
+                let latest = spectrum(trace);
+                if smoothed.len() == latest.len() {
+                    for (smoothed, latest) in smoothed.iter_mut().zip(latest) {
+                        *smoothed = self.smoothing * *smoothed + (1.0 - self.smoothing) * latest;
+                    }
+                } else {
+                    *smoothed = latest;
+                }
+            }
+            if !trace.visible {
+                continue;
+            }
+
+            let nyquist = trace.sample_rate as f32 / 2.0;
+            let [r, g, b] = trace.color;
+            let points = smoothed
+                .iter()
+                .enumerate()
+                .map(|(k, &db)| (k as f32 * nyquist * 2.0 / FFT_SIZE as f32, db))
+                .filter(|&(frequency, _)| frequency >= LOWEST_FREQUENCY)
+                .filter_map(|(frequency, db)| {
+                    Some([
+                        frequency_x(frequency, nyquist)?,
+                        2.0 * level(db, self.floor) - 1.0,
+                    ])
+                })
+                .collect::<Vec<_>>();
+            for segment in points.windows(2) {
+                line(&mut vertices, segment[0], segment[1], [r, g, b, 1.0]);
+            }
+        }
+        self.vertices = flat_vertices(&self.wgpu_resources, "Spectrum Vertex Buffer", &vertices);
+    }
+
+    fn render(&self, view: &wgpu::TextureView) {
+        render_flat(&self.wgpu_resources, view, &self.pipeline, &self.vertices);
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        self.wgpu_resources.resize(width, height);
+    }
+
+    fn params(&self) -> Vec<Param> {
+        vec![
+            floor_param(self.floor),
+            Param {
+                name: "smoothing",
+                description: "how much each frame's spectrum keeps of the last, from 0 to 0.99",
+                value: self.smoothing,
+                min: 0.0,
+                max: 0.99,
+            },
+        ]
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> anyhow::Result<()> {
+        Param::check(&self.params(), name, value)?;
+        match name {
+            "floor" => self.floor = value,
+            _ => self.smoothing = value,
+        }
+        Ok(())
+    }
+}
+
+#[repr(C)]
+#[derive(Pod, Copy, Zeroable, Clone)]
+struct SpectrogramUniforms {
+    color: [f32; 4],
+    head: u32,
+    columns: u32,
+    floor: f32,
+    _padding: u32,
+}
+
+/// Spectrogram draws the first visible trace's spectrum over time, with the newest on the right
+/// and brighter for louder.
+pub struct Spectrogram {
+    wgpu_resources: WgpuResources,
+    audio: SharedAudio,
+    pipeline: wgpu::RenderPipeline,
+    // The level in dB of each band, bottom to top, in a ring of columns written one per frame
+    levels: wgpu::Texture,
+    uniforms: SpectrogramUniforms,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl Spectrogram {
+    /// Splits a spectrum into bands that are evenly spaced on a log scale, taking the loudest
+    /// bin in each, or the nearest bin for bands narrower than a bin.
+    fn bands(spectrum: &[f32], nyquist: f32) -> Vec<f32> {
+        let bin_width = 2.0 * nyquist / FFT_SIZE as f32;
+        let ratio = (nyquist / LOWEST_FREQUENCY).powf(1.0 / SPECTROGRAM_ROWS as f32);
+        (0..SPECTROGRAM_ROWS)
+            .map(|row| {
+                let low = LOWEST_FREQUENCY * ratio.powi(row as i32);
+                let first = (low / bin_width).round() as usize;
+                let last = ((low * ratio / bin_width) as usize).max(first);
+                spectrum[first.min(spectrum.len() - 1)..=last.min(spectrum.len() - 1)]
+                    .iter()
+                    .copied()
+                    .fold(f32::MIN, f32::max)
+            })
+            .collect()
+    }
+}
+
+impl Shaderer for Spectrogram {
+    fn new(wgpu_resources: WgpuResources, audio: SharedAudio) -> anyhow::Result<Self> {
+        let WgpuResources { device, config, .. } = &wgpu_resources;
+        let levels = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Spectrogram Levels"),
+            size: wgpu::Extent3d {
+                width: SPECTROGRAM_COLUMNS as u32,
+                height: SPECTROGRAM_ROWS as u32,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        });
+        let uniforms = SpectrogramUniforms {
+            color: [0.0; 4],
+            head: 0,
+            columns: 0,
+            floor: -90.0,
+            _padding: 0,
+        };
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Spectrogram Uniform Buffer"),
+            contents: bytemuck::bytes_of(&uniforms),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Spectrogram Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Spectrogram Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(
+                        &levels.create_view(&wgpu::TextureViewDescriptor::default()),
+                    ),
+                },
+            ],
+        });
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("Spectrogram Shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                "shaders/spectrogram.wgsl"
+            ))),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Spectrogram Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Spectrogram Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "main_vs",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "main_fs",
+                targets: &[config.format.into()],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+        Ok(Self {
+            wgpu_resources,
+            audio,
+            pipeline,
+            levels,
+            uniforms,
+            uniform_buffer,
+            bind_group,
+        })
+    }
+
+    fn update(&mut self) {
+        let audio = self.audio.borrow();
+        let queue = &self.wgpu_resources.queue;
+        match audio.traces.iter().find(|trace| trace.visible) {
+            Some(trace) => {
+                let [r, g, b] = trace.color;
+                self.uniforms.color = [r, g, b, 1.0];
+                if trace.new_samples > 0 {
+                    let bands =
+                        Spectrogram::bands(&spectrum(trace), trace.sample_rate as f32 / 2.0);
+                    // Only the newest column is written, over the oldest
+                    queue.write_texture(
+                        wgpu::ImageCopyTexture {
+                            texture: &self.levels,
+                            mip_level: 0,
+                            origin: wgpu::Origin3d {
+                                x: self.uniforms.head,
+                                y: 0,
+                                z: 0,
+                            },
+                            aspect: wgpu::TextureAspect::All,
+                        },
+                        bytemuck::cast_slice(&bands),
+                        wgpu::ImageDataLayout {
+                            offset: 0,
+                            bytes_per_row: NonZeroU32::new(4),
+                            rows_per_image: None,
+                        },
+                        wgpu::Extent3d {
+                            width: 1,
+                            height: SPECTROGRAM_ROWS as u32,
+                            depth_or_array_layers: 1,
+                        },
+                    );
+                    self.uniforms.head = (self.uniforms.head + 1) % SPECTROGRAM_COLUMNS as u32;
+                    self.uniforms.columns =
+                        (self.uniforms.columns + 1).min(SPECTROGRAM_COLUMNS as u32);
+                }
+            }
+            // Nothing to show, so it's left black until a trace is visible again
+            None => self.uniforms.color = [0.0, 0.0, 0.0, 1.0],
+        }
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&self.uniforms));
+    }
+
+    fn render(&self, view: &wgpu::TextureView) {
+        let WgpuResources { device, queue, .. } = &self.wgpu_resources;
+        let mut command_encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Spectrogram Command Encoder"),
+        });
+        {
+            let mut rpass = command_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Spectrogram Render Pass"),
+                color_attachments: &[wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
+                    },
+                }],
+                depth_stencil_attachment: None,
+            });
+            rpass.set_pipeline(&self.pipeline);
+            rpass.set_bind_group(0, &self.bind_group, &[]);
+            rpass.draw(0..3, 0..1);
+        }
+        queue.submit(Some(command_encoder.finish()));
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        self.wgpu_resources.resize(width, height);
+    }
+
+    fn params(&self) -> Vec<Param> {
+        vec![floor_param(self.uniforms.floor)]
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> anyhow::Result<()> {
+        Param::check(&self.params(), name, value)?;
+        self.uniforms.floor = value;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trace(sample: impl Fn(f32) -> f32) -> TraceAudio {
+        let mut trace = TraceAudio {
+            sample_rate: 48000,
+            ..TraceAudio::default()
+        };
+        for i in 0..FFT_SIZE {
+            let sample = sample(i as f32 / trace.sample_rate as f32);
+            trace.samples.push_back([sample, sample]);
+        }
+        trace
+    }
+
+    #[test]
+    fn full_scale_sine_is_0_db() {
+        // Exactly on bin 64, so it doesn't leak into the bins around it
+        let bin = 64;
+        let frequency = bin as f32 * 48000.0 / FFT_SIZE as f32;
+        let spectrum = spectrum(&trace(|time| (TAU * frequency * time).sin()));
+        assert!(spectrum[bin].abs() < 0.5, "{} dB", spectrum[bin]);
+        assert!(spectrum[bin * 4] < -60.0, "{} dB", spectrum[bin * 4]);
+    }
+
+    #[test]
+    fn silence_is_at_the_floor() {
+        let spectrum = spectrum(&trace(|_| 0.0));
+        assert_eq!(spectrum.len(), FFT_SIZE / 2);
+        assert!(spectrum.iter().all(|&db| level(db, -200.0) == 0.0));
+    }
+
+    #[test]
+    fn frequencies_span_the_screen() {
+        assert_eq!(frequency_x(LOWEST_FREQUENCY, 24000.0), Some(-1.0));
+        assert!((frequency_x(24000.0, 24000.0).unwrap() - 1.0).abs() < 1e-6);
+        // There's nothing above the lowest frequency to show at very low sample rates
+        assert_eq!(frequency_x(10.0, 10.0), None);
+        assert_eq!(frequency_x(10.0, LOWEST_FREQUENCY), None);
+    }
+}
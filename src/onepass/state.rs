@@ -1,19 +1,51 @@
-use std::{cmp::max, num::NonZeroU64, time::Instant};
+use std::{cmp::max, fmt, num::NonZeroU64, str::FromStr, time::Instant};
 
+use anyhow::{bail, Context};
 use bytemuck::{Pod, Zeroable};
 use itertools::Itertools;
 
-use crate::{ringbuffer::RingBuffer, sound::WavStreamer};
+use crate::{
+    dsp::Chain,
+    ringbuffer::RingBuffer,
+    sound::{Frame, Source, WavFormat, WavRecorder},
+};
 
-use super::wgpu_resources::{UniformBinder, WavStreamBinder, WgpuResources};
+use super::{
+    color::ColorScheme,
+    oscilloscope::Vertex,
+    wgpu_resources::{UniformBinder, WavStreamBinder, WgpuResources},
+};
 
 #[repr(C)]
 #[derive(Pod, Copy, Zeroable, Clone)]
 pub struct Uniforms {
     frame: u32,
     time: f32,
-    line_thickness: f32,
+    pub line_thickness: f32,
     count: f32,
+    // Index of the next instance to be written, ie. one past the newest sample.
+    write_head: u32,
+    fade_curve: u32,
+    pub fade_strength: f32,
+    // Z values below this are blanked when hard_blanking is set.
+    pub blanking_threshold: f32,
+    hard_blanking: u32,
+    view_mode: u32,
+    // Depth cues for the XYZ view: exponential fog over view depth, and how strongly line
+    // thickness shrinks with distance (0 keeps it constant, 1 is full perspective).
+    pub fog_density: f32,
+    pub size_attenuation: f32,
+    // Trace colors from the ColorScheme; the fourth component is unused padding.
+    primary_color: [f32; 4],
+    secondary_color: [f32; 4],
+    color_mode: u32,
+    _padding: u32,
+    // Size of the render target in pixels.
+    resolution: [f32; 2],
+    // RMS of X, Y and Z over the samples shown last frame, and the peak of X and Y.
+    levels: [f32; 4],
+    // Free parameters for custom trace shaders, packed four to a vec4.
+    params: [[f32; 4]; 2],
 }
 
 impl Default for Uniforms {
@@ -23,6 +55,116 @@ impl Default for Uniforms {
             time: Default::default(),
             line_thickness: 0.0075,
             count: SAMPLE_BUFFER_SIZE as f32,
+            write_head: 0,
+            fade_curve: FadeCurve::default() as u32,
+            fade_strength: 4.0,
+            blanking_threshold: 0.5,
+            hard_blanking: 0,
+            view_mode: ViewMode::default() as u32,
+            fog_density: 0.25,
+            size_attenuation: 1.0,
+            primary_color: [0.0; 4],
+            secondary_color: [0.0; 4],
+            color_mode: 0,
+            _padding: 0,
+            resolution: [0.0; 2],
+            levels: [0.0; 4],
+            params: [[0.0; 4]; 2],
+        }
+    }
+}
+
+/// FadeCurve maps a sample's age (0 for the newest, 1 for the oldest) to its brightness.
+/// The discriminants must match the `FADE_*` constants in `render.wgsl`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum FadeCurve {
+    /// Every sample is drawn at full brightness.
+    None = 0,
+    /// Brightness falls off as `1 - age`.
+    Linear = 1,
+    /// Brightness falls off as `exp(-fade_strength * age)`.
+    #[default]
+    Exponential = 2,
+    /// Brightness falls off as `(1 - age) ^ fade_strength`.
+    Power = 3,
+}
+
+/// ViewMode decides how a frame's channels are plotted.
+/// The discriminants must match the `MODE_*` constants in `render.wgsl`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ViewMode {
+    /// X and Y are plotted flat, and Z modulates intensity.
+    #[default]
+    Xy = 0,
+    /// X, Y and Z are plotted in space through the camera.
+    Xyz = 1,
+}
+
+impl ViewMode {
+    pub fn next(self) -> Self {
+        match self {
+            ViewMode::Xy => ViewMode::Xyz,
+            ViewMode::Xyz => ViewMode::Xy,
+        }
+    }
+}
+
+impl fmt::Display for ViewMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ViewMode::Xy => "xy",
+            ViewMode::Xyz => "xyz",
+        })
+    }
+}
+
+impl FromStr for ViewMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "xy" => Ok(ViewMode::Xy),
+            "xyz" => Ok(ViewMode::Xyz),
+            _ => anyhow::bail!("unknown view mode {:?}, expected xy or xyz", s),
+        }
+    }
+}
+
+impl FadeCurve {
+    pub fn next(self) -> Self {
+        match self {
+            FadeCurve::None => FadeCurve::Linear,
+            FadeCurve::Linear => FadeCurve::Exponential,
+            FadeCurve::Exponential => FadeCurve::Power,
+            FadeCurve::Power => FadeCurve::None,
+        }
+    }
+}
+
+impl fmt::Display for FadeCurve {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            FadeCurve::None => "none",
+            FadeCurve::Linear => "linear",
+            FadeCurve::Exponential => "exponential",
+            FadeCurve::Power => "power",
+        })
+    }
+}
+
+impl FromStr for FadeCurve {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(FadeCurve::None),
+            "linear" => Ok(FadeCurve::Linear),
+            "exponential" => Ok(FadeCurve::Exponential),
+            "power" => Ok(FadeCurve::Power),
+            _ => bail!(
+                "unknown fade curve {:?}, expected none, linear, exponential or power",
+                s
+            ),
         }
     }
 }
@@ -32,6 +174,12 @@ pub struct State {
     start_time: Instant,
     time: f32,
     prev_time: f32,
+    // When set, each frame advances time by exactly 1 / fixed_frame_rate instead of following
+    // the wall clock, eg. when exporting video.
+    fixed_frame_rate: Option<f32>,
+    // Fractional samples carried over between frames, so sample counts don't drift from time.
+    sample_remainder: f32,
+    finished: bool,
     pub uniforms: Uniforms,
 
     pub uniform_buffer: wgpu::Buffer,
@@ -47,9 +195,31 @@ pub struct State {
     pub wav_stream_bind_group_layout: wgpu::BindGroupLayout,
 
     offset: u64,
+    fade_curve: FadeCurve,
+    view_mode: ViewMode,
+    color_scheme: ColorScheme,
 
-    wav_streamer: WavStreamer,
+    source: Box<dyn Source>,
     rb: RingBuffer<(i32, i32)>,
+    // Processing between the source and the screen
+    chain: Chain,
+    recording: Option<Recording>,
+    // Scales X and Y on screen only, after processing and recording, and then moves them
+    gain: f32,
+    pan: [f32; 2],
+    // Scales the colors, so traces add up to more or less light
+    brightness: f32,
+    visible: bool,
+    // Frames taken from the source so far, counting from any seek
+    position: u64,
+}
+
+/// Recording is a WAV being written from a trace's samples as they're shown.
+struct Recording {
+    path: String,
+    recorder: WavRecorder,
+    // Samples left to record, if the recording has a set duration
+    remaining: Option<u64>,
 }
 
 // TODO: parameterize these
@@ -57,9 +227,11 @@ pub struct State {
 pub const SAMPLE_RENDER_COUNT: usize = 128000;
 pub const SAMPLE_BUFFER_SIZE: usize = 20000;
 pub const COMPUTE_BUFFER_FACTOR: usize = 1;
+/// How many free parameters custom trace shaders get in `uniforms.params`.
+pub const SHADER_PARAM_COUNT: usize = 8;
 
 impl State {
-    pub fn new(wgpu_resources: &WgpuResources, filename: &str) -> Self {
+    pub fn new(wgpu_resources: &WgpuResources, source: Box<dyn Source>) -> Self {
         let uniform_binder = UniformBinder::<Uniforms>::new(wgpu_resources);
 
         let uniform_buffer = uniform_binder.new_uniform_buffer();
@@ -67,13 +239,8 @@ impl State {
         let uniform_bind_group =
             uniform_binder.bind_group(&uniform_bind_group_layout, &uniform_buffer);
 
-        let wav_streamer = WavStreamer::new(filename);
-        let wav_stream_binder = WavStreamBinder::new(
-            wgpu_resources,
-            &wav_streamer.spec,
-            SAMPLE_BUFFER_SIZE,
-            COMPUTE_BUFFER_FACTOR,
-        );
+        let wav_stream_binder =
+            WavStreamBinder::new(wgpu_resources, SAMPLE_BUFFER_SIZE, COMPUTE_BUFFER_FACTOR);
         let wav_stream_bind_group_layout = wav_stream_binder.bind_group_layout();
         let (compute_buffer, instance_buffer) = wav_stream_binder.new_buffers();
         // TODO
@@ -85,12 +252,15 @@ impl State {
 
         let rb = RingBuffer::new(vec![(0, 0); SAMPLE_BUFFER_SIZE]);
 
-        Self {
+        let mut state = Self {
             frame: 0,
             uniforms: Uniforms::default(),
             start_time: Instant::now(),
             time: 0.0,
             prev_time: 0.0,
+            fixed_frame_rate: None,
+            sample_remainder: 0.0,
+            finished: false,
             uniform_buffer,
             uniform_bind_group_layout,
             uniform_bind_group,
@@ -104,42 +274,303 @@ impl State {
             wav_stream_bind_group_layout,
 
             offset: 0,
+            fade_curve: FadeCurve::default(),
+            view_mode: ViewMode::default(),
+            color_scheme: ColorScheme::default(),
 
-            wav_streamer,
+            source,
             rb,
+            chain: Chain::default(),
+            recording: None,
+            gain: 1.0,
+            pan: [0.0, 0.0],
+            brightness: 1.0,
+            visible: true,
+            position: 0,
+        };
+        state.set_color_scheme(ColorScheme::default());
+        state
+    }
+
+    pub fn set_color_scheme(&mut self, color_scheme: ColorScheme) {
+        let brightness = self.brightness;
+        let [r, g, b] = color_scheme.primary.map(|c| c * brightness);
+        self.uniforms.primary_color = [r, g, b, 1.0];
+        let [r, g, b] = color_scheme.secondary.map(|c| c * brightness);
+        self.uniforms.secondary_color = [r, g, b, 1.0];
+        self.uniforms.color_mode = color_scheme.mode as u32;
+        self.color_scheme = color_scheme;
+    }
+
+    pub fn color_scheme(&self) -> &ColorScheme {
+        &self.color_scheme
+    }
+
+    pub fn set_fade_curve(&mut self, fade_curve: FadeCurve) {
+        self.fade_curve = fade_curve;
+        self.uniforms.fade_curve = fade_curve as u32;
+    }
+
+    pub fn fade_curve(&self) -> FadeCurve {
+        self.fade_curve
+    }
+
+    pub fn set_view_mode(&mut self, view_mode: ViewMode) {
+        self.view_mode = view_mode;
+        self.uniforms.view_mode = view_mode as u32;
+    }
+
+    pub fn view_mode(&self) -> ViewMode {
+        self.view_mode
+    }
+
+    /// With hard blanking the Z channel switches the beam fully on or off at
+    /// `blanking_threshold`; otherwise it scales brightness linearly.
+    pub fn set_hard_blanking(&mut self, hard_blanking: bool) {
+        self.uniforms.hard_blanking = hard_blanking as u32;
+    }
+
+    pub fn hard_blanking(&self) -> bool {
+        self.uniforms.hard_blanking != 0
+    }
+
+    pub fn set_resolution(&mut self, width: u32, height: u32) {
+        self.uniforms.resolution = [width as f32, height as f32];
+    }
+
+    /// Sets one of the free parameters custom trace shaders read from `uniforms.params`.
+    pub fn set_shader_param(&mut self, index: usize, value: f32) -> anyhow::Result<()> {
+        if index >= SHADER_PARAM_COUNT {
+            bail!(
+                "no shader parameter {}, there are {}",
+                index,
+                SHADER_PARAM_COUNT
+            );
+        }
+        self.uniforms.params[index / 4][index % 4] = value;
+        Ok(())
+    }
+
+    pub fn shader_params(&self) -> Vec<f32> {
+        self.uniforms.params.iter().flatten().copied().collect()
+    }
+
+    pub fn set_fixed_frame_rate(&mut self, fixed_frame_rate: Option<f32>) {
+        self.fixed_frame_rate = fixed_frame_rate;
+    }
+
+    /// Scales the trace on screen, without changing what's recorded.
+    pub fn set_gain(&mut self, gain: f32) {
+        self.gain = gain;
+    }
+
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// Moves the trace on screen, after any gain, where the screen is 2 wide.
+    pub fn set_pan(&mut self, pan: [f32; 2]) {
+        self.pan = pan;
+    }
+
+    pub fn pan(&self) -> [f32; 2] {
+        self.pan
+    }
+
+    /// Scales the trace's colors, where 1 is the color scheme's own.
+    pub fn set_brightness(&mut self, brightness: f32) {
+        self.brightness = brightness;
+        self.set_color_scheme(self.color_scheme);
+    }
+
+    pub fn brightness(&self) -> f32 {
+        self.brightness
+    }
+
+    /// Hidden traces keep running, so they're in step when shown again.
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Jumps the source to `seconds` from its start, if it can seek.
+    pub fn seek(&mut self, seconds: f32) -> anyhow::Result<()> {
+        self.position = self.source.seek(seconds)?;
+        self.finished = false;
+        Ok(())
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    /// How far into the source the trace is, in seconds.
+    pub fn position(&self) -> f32 {
+        self.position as f32 / self.source.sample_rate() as f32
+    }
+
+    /// RMS of X, Y and Z over the samples shown last frame, and the peak of X and Y.
+    pub fn levels(&self) -> [f32; 4] {
+        self.uniforms.levels
+    }
+
+    /// Whether the source has run out of samples.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn set_chain(&mut self, chain: Chain) {
+        self.chain = chain;
+    }
+
+    pub fn chain(&self) -> &Chain {
+        &self.chain
+    }
+
+    pub fn chain_mut(&mut self) -> &mut Chain {
+        &mut self.chain
+    }
+
+    /// Starts writing the samples shown from now on to a WAV at `path`, until stopped or for
+    /// `duration` seconds. Any recording already going is finished first.
+    pub fn start_recording(
+        &mut self,
+        path: &str,
+        format: WavFormat,
+        duration: Option<f32>,
+    ) -> anyhow::Result<()> {
+        self.stop_recording()?;
+        let sample_rate = self.source.sample_rate();
+        self.recording = Some(Recording {
+            path: path.to_string(),
+            recorder: WavRecorder::create(path, sample_rate, self.source.channels(), format)?,
+            remaining: duration.map(|duration| (duration * sample_rate as f32).round() as u64),
+        });
+        Ok(())
+    }
+
+    /// Finishes the WAV being recorded, if there is one, returning its path.
+    pub fn stop_recording(&mut self) -> anyhow::Result<Option<String>> {
+        match self.recording.take() {
+            Some(Recording { path, recorder, .. }) => {
+                recorder
+                    .finish()
+                    .with_context(|| format!("writing {}", path))?;
+                Ok(Some(path))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Writes newly shown frames to the recording, finishing it once it has run for its
+    /// duration or the source runs out.
+    fn record(&mut self, frames: &[Frame]) {
+        let recording = match &mut self.recording {
+            Some(recording) => recording,
+            None => return,
+        };
+        let count = recording.remaining.map_or(frames.len(), |remaining| {
+            frames.len().min(remaining as usize)
+        });
+        if let Some(remaining) = &mut recording.remaining {
+            *remaining -= count as u64;
+        }
+        let written = frames[..count]
+            .iter()
+            .try_for_each(|&frame| recording.recorder.write(frame));
+
+        if let Err(e) = written {
+            log::error!("recording to {} failed: {}", recording.path, e);
+            self.recording = None;
+        } else if recording.remaining == Some(0) || self.finished {
+            match self.stop_recording() {
+                Ok(Some(path)) => log::info!("finished recording {}", path),
+                Ok(None) => {}
+                Err(e) => log::error!("{:?}", e),
+            }
         }
     }
 
     pub fn update_uniforms(&mut self) {
         self.frame += 1;
         self.prev_time = self.time;
-        self.time = Instant::now().duration_since(self.start_time).as_secs_f32();
+        self.time = match self.fixed_frame_rate {
+            Some(fps) => self.frame as f32 / fps,
+            None => Instant::now().duration_since(self.start_time).as_secs_f32(),
+        };
         self.uniforms.time = self.time;
         self.uniforms.frame = self.frame;
     }
 
-    pub fn update_instances(&mut self, queue: &wgpu::Queue) {
-        let hz = self.wav_streamer.spec.sample_rate as f32;
+    /// Restarts the clock from now, so the time spent paused isn't made up for with a burst of
+    /// samples on the next frame.
+    pub fn resume(&mut self) {
+        if self.fixed_frame_rate.is_none() {
+            self.time = Instant::now().duration_since(self.start_time).as_secs_f32();
+        }
+    }
+
+    /// Takes this frame's samples from the source and writes them to the instance buffer,
+    /// returning them after processing.
+    pub fn update_instances(&mut self, queue: &wgpu::Queue) -> Vec<Frame> {
+        let hz = self.source.sample_rate() as f32;
         let dt = self.time - self.prev_time;
-        let sample_count = (hz * dt) as usize;
+        let samples = hz * dt + self.sample_remainder;
+        let sample_count = samples as usize;
+        self.sample_remainder = samples.fract();
 
-        let data = self
-            .wav_streamer
+        // Sources without a Z channel keep the beam fully on, or lie flat in the XYZ view
+        let missing_z = match self.view_mode {
+            ViewMode::Xy => 1.0,
+            ViewMode::Xyz => 0.0,
+        };
+
+        let sample_rate = self.source.sample_rate();
+        let frames = self.source.by_ref().take(sample_count).collect_vec();
+        if frames.len() < sample_count {
+            self.finished = true;
+        }
+        self.position += frames.len() as u64;
+        let frames = frames
+            .into_iter()
+            .map(|frame| self.chain.process(frame, sample_rate))
+            .collect_vec();
+        self.record(&frames);
+        // Levels hold while paused, rather than dropping to silence
+        if !frames.is_empty() {
+            self.uniforms.levels = levels(&frames);
+        }
+
+        // Only the newest samples fit in the ring, however long the frame was
+        let data = frames[frames.len().saturating_sub(SAMPLE_BUFFER_SIZE)..]
             .iter()
-            .take(sample_count)
-            .map(|(x, y)| [x as f32 / i16::MAX as f32, y as f32 / i16::MAX as f32])
+            .map(|&(x, y, z)| {
+                Vertex([
+                    self.gain * x as f32 / i16::MAX as f32 + self.pan[0],
+                    self.gain * y as f32 / i16::MAX as f32 + self.pan[1],
+                    z.map_or(missing_z, |z| z as f32 / i16::MAX as f32),
+                ])
+            })
             .collect_vec();
 
         let curr_offset = self.offset;
-        let next_offset = (curr_offset + sample_count as u64) % SAMPLE_BUFFER_SIZE as u64;
+        let next_offset = (curr_offset + data.len() as u64) % SAMPLE_BUFFER_SIZE as u64;
         self.offset = next_offset;
+        self.uniforms.write_head = next_offset as u32;
 
-        if next_offset < curr_offset {
-            println!("====================");
+        if curr_offset as usize + data.len() > SAMPLE_BUFFER_SIZE {
+            log::debug!("instance buffer wrapped around");
             let cutoff = (SAMPLE_BUFFER_SIZE as u64 - curr_offset) as usize;
             queue.write_buffer(
                 &self.instance_buffer,
-                curr_offset * std::mem::size_of::<[f32; 2]>() as u64,
+                curr_offset * std::mem::size_of::<Vertex>() as u64,
                 bytemuck::cast_slice(&data[..cutoff]),
             );
             queue.write_buffer(
@@ -147,12 +578,29 @@ impl State {
                 0,
                 bytemuck::cast_slice(&data[cutoff..]),
             );
+            self.write_seam_instance(queue, &data[cutoff..]);
         } else {
             queue.write_buffer(
                 &self.instance_buffer,
-                curr_offset * std::mem::size_of::<[f32; 2]>() as u64,
+                curr_offset * std::mem::size_of::<Vertex>() as u64,
                 bytemuck::cast_slice(&data),
             );
+            if curr_offset == 0 {
+                self.write_seam_instance(queue, &data);
+            }
+        }
+        frames
+    }
+
+    /// The instance buffer holds one extra slot past the ring so that the last instance's `next`
+    /// attribute reads a copy of slot 0 instead of running off the end of the buffer.
+    fn write_seam_instance(&self, queue: &wgpu::Queue, head: &[Vertex]) {
+        if let Some(first) = head.first() {
+            queue.write_buffer(
+                &self.instance_buffer,
+                SAMPLE_BUFFER_SIZE as u64 * std::mem::size_of::<Vertex>() as u64,
+                bytemuck::bytes_of(first),
+            );
         }
     }
 
@@ -160,3 +608,18 @@ impl State {
         queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&self.uniforms));
     }
 }
+
+/// The RMS of X, Y and Z over `frames` and the peak of X and Y, scaled so full scale is 1.
+fn levels(frames: &[Frame]) -> [f32; 4] {
+    let mut squares = [0.0; 3];
+    let mut peak = 0.0f32;
+    for &(x, y, z) in frames {
+        let [x, y, z] = [x, y, z.unwrap_or(0)].map(|sample| sample as f32 / i16::MAX as f32);
+        for (square, sample) in squares.iter_mut().zip([x, y, z]) {
+            *square += sample * sample;
+        }
+        peak = peak.max(x.abs()).max(y.abs());
+    }
+    let [x, y, z] = squares.map(|square| (square / frames.len() as f32).sqrt());
+    [x, y, z, peak]
+}
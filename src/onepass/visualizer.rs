@@ -0,0 +1,231 @@
+//! Visualizers are the different ways the live window can draw the traces: the beam itself, or
+//! views made from the same audio, such as a spectrum. They're registered here by name so they
+//! can be picked with `--visualizer` and switched between while running.
+
+use std::{cell::RefCell, collections::VecDeque, fmt, rc::Rc, str::FromStr};
+
+use anyhow::bail;
+
+use crate::sound::Frame;
+
+use super::{
+    oscilloscope::Oscilloscope, spectrum::Spectrogram, spectrum::Spectrum, state::ViewMode,
+    waveform::Waveform, wgpu_resources::WgpuResources, Shaderer,
+};
+
+/// How many of each trace's latest samples are kept for visualizers to draw from.
+pub const HISTORY: usize = 8192;
+
+/// SharedAudio is how visualizers get at the audio, which the oscilloscope takes from the
+/// sources each frame.
+pub type SharedAudio = Rc<RefCell<Audio>>;
+
+/// Audio is every trace's latest samples, after processing.
+#[derive(Default)]
+pub struct Audio {
+    pub traces: Vec<TraceAudio>,
+}
+
+/// TraceAudio is one trace's latest samples, along with how the trace is shown.
+pub struct TraceAudio {
+    pub sample_rate: u32,
+    /// X and Y from -1 to 1, oldest first. There are at most `HISTORY`.
+    pub samples: VecDeque<[f32; 2]>,
+    /// How many of `samples` came in with the last frame.
+    pub new_samples: usize,
+    pub color: [f32; 3],
+    pub visible: bool,
+}
+
+impl Default for TraceAudio {
+    fn default() -> Self {
+        Self {
+            sample_rate: 44100,
+            samples: VecDeque::with_capacity(HISTORY),
+            new_samples: 0,
+            color: [1.0; 3],
+            visible: true,
+        }
+    }
+}
+
+impl TraceAudio {
+    pub fn push(&mut self, frames: &[Frame]) {
+        let scale = i16::MAX as f32;
+        let frames = &frames[frames.len().saturating_sub(HISTORY)..];
+        let excess = (self.samples.len() + frames.len()).saturating_sub(HISTORY);
+        self.samples.drain(..excess);
+        self.samples.extend(
+            frames
+                .iter()
+                .map(|&(x, y, _)| [x as f32 / scale, y as f32 / scale]),
+        );
+        self.new_samples = frames.len();
+    }
+
+    /// The latest `count` samples, or fewer if there aren't that many yet, oldest first.
+    pub fn latest(&self, count: usize) -> impl Iterator<Item = &[f32; 2]> + '_ {
+        self.samples
+            .iter()
+            .skip(self.samples.len().saturating_sub(count))
+    }
+}
+
+/// Param describes one of a visualizer's parameters, along with its current value.
+#[derive(Clone, Debug)]
+pub struct Param {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub value: f32,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Param {
+    /// Checks that `value` is in range for the parameter called `name` among `params`.
+    pub fn check(params: &[Param], name: &str, value: f32) -> anyhow::Result<()> {
+        let param = match params.iter().find(|param| param.name == name) {
+            Some(param) => param,
+            None => bail!(
+                "unknown parameter {:?}, expected one of {}",
+                name,
+                params
+                    .iter()
+                    .map(|param| param.name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        };
+        if !(param.min..=param.max).contains(&value) {
+            bail!(
+                "{} must be between {} and {}, got {}",
+                name,
+                param.min,
+                param.max,
+                value
+            );
+        }
+        Ok(())
+    }
+}
+
+type Constructor = fn(WgpuResources, SharedAudio) -> anyhow::Result<Box<dyn Shaderer>>;
+
+fn boxed<S: Shaderer + 'static>(
+    wgpu_resources: WgpuResources,
+    audio: SharedAudio,
+) -> anyhow::Result<Box<dyn Shaderer>> {
+    Ok(Box::new(S::new(wgpu_resources, audio)?))
+}
+
+/// Kind is how a visualizer is drawn.
+#[derive(Copy, Clone)]
+enum Kind {
+    /// By the oscilloscope's own beam, in one of its views.
+    Beam(ViewMode),
+    /// By a shaderer of its own, drawing from the shared audio.
+    Shaderer(Constructor),
+}
+
+/// Entry is a visualizer in the registry.
+pub struct Entry {
+    pub name: &'static str,
+    pub description: &'static str,
+    kind: Kind,
+}
+
+/// Every visualizer there is, in the order they're cycled through.
+pub const REGISTRY: &[Entry] = &[
+    Entry {
+        name: "xy",
+        description: "the beam, tracing X against Y",
+        kind: Kind::Beam(ViewMode::Xy),
+    },
+    Entry {
+        name: "yt",
+        description: "each channel against time, triggered on X",
+        kind: Kind::Shaderer(boxed::<Waveform>),
+    },
+    Entry {
+        name: "spectrum",
+        description: "each trace's spectrum, on a log frequency scale",
+        kind: Kind::Shaderer(boxed::<Spectrum>),
+    },
+    Entry {
+        name: "spectrogram",
+        description: "the first trace's spectrum over time, scrolling right to left",
+        kind: Kind::Shaderer(boxed::<Spectrogram>),
+    },
+    Entry {
+        name: "3d",
+        description: "the beam in XYZ, with a camera to orbit",
+        kind: Kind::Beam(ViewMode::Xyz),
+    },
+];
+
+/// Visualizer picks one of the registry's visualizers by name, eg. `spectrum`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Visualizer(usize);
+
+impl Visualizer {
+    pub fn next(self) -> Self {
+        Self((self.0 + 1) % REGISTRY.len())
+    }
+
+    /// The visualizer that's the beam in `view_mode`.
+    pub fn for_view(view_mode: ViewMode) -> Self {
+        let i = REGISTRY
+            .iter()
+            .position(|entry| matches!(entry.kind, Kind::Beam(view) if view == view_mode))
+            .expect("every view is registered");
+        Self(i)
+    }
+
+    pub fn entry(self) -> &'static Entry {
+        &REGISTRY[self.0]
+    }
+
+    /// Gets the visualizer ready to draw. The beam's views are drawn by `oscilloscope` itself, so
+    /// they switch its view and return `None`, and the others return a shaderer of their own on
+    /// its device.
+    pub fn create(
+        self,
+        oscilloscope: &mut Oscilloscope,
+    ) -> anyhow::Result<Option<Box<dyn Shaderer>>> {
+        match self.entry().kind {
+            Kind::Beam(view_mode) => {
+                oscilloscope.set_view_mode(view_mode);
+                Ok(None)
+            }
+            Kind::Shaderer(new) => {
+                let wgpu_resources = oscilloscope.wgpu_resources.share();
+                new(wgpu_resources, oscilloscope.audio()).map(Some)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Visualizer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.entry().name)
+    }
+}
+
+impl FromStr for Visualizer {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match REGISTRY.iter().position(|entry| entry.name == s) {
+            Some(i) => Ok(Self(i)),
+            None => bail!(
+                "unknown visualizer {:?}, expected {}",
+                s,
+                REGISTRY
+                    .iter()
+                    .map(|entry| entry.name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! The Y-T visualizer, which draws each channel against time like a scope that isn't in XY mode.
+
+use super::{
+    overlay::{flat_pipeline, flat_vertices, render_flat, FlatVertex},
+    visualizer::{Param, SharedAudio, TraceAudio, HISTORY},
+    wgpu_resources::WgpuResources,
+    Shaderer,
+};
+
+/// The line through the middle of each channel's lane.
+const GRID_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.15];
+
+/// Waveform stacks a lane for X and then Y of each visible trace, top to bottom.
+pub struct Waveform {
+    wgpu_resources: WgpuResources,
+    audio: SharedAudio,
+    pipeline: wgpu::RenderPipeline,
+    vertices: Option<(wgpu::Buffer, u32)>,
+    /// Seconds across the screen.
+    timebase: f32,
+    gain: f32,
+    /// Whether sweeps start where X rises through 0, so periodic signals stand still.
+    trigger: bool,
+}
+
+impl Waveform {
+    /// Where in `trace`'s samples a sweep of `window` samples starts.
+    fn sweep_start(&self, trace: &TraceAudio, window: usize) -> usize {
+        let latest = trace.samples.len().saturating_sub(window);
+        if !self.trigger {
+            return latest;
+        }
+        // Looking back at most another window, which finds a crossing in anything periodic
+        // that fits on screen
+        let earliest = latest.saturating_sub(window).max(1);
+        (earliest..=latest)
+            .rev()
+            .find(|&i| trace.samples[i - 1][0] < 0.0 && trace.samples[i][0] >= 0.0)
+            .unwrap_or(latest)
+    }
+}
+
+impl Shaderer for Waveform {
+    fn new(wgpu_resources: WgpuResources, audio: SharedAudio) -> anyhow::Result<Self> {
+        Ok(Self {
+            pipeline: flat_pipeline(
+                &wgpu_resources,
+                "Waveform",
+                wgpu::PrimitiveTopology::LineList,
+            ),
+            wgpu_resources,
+            audio,
+            vertices: None,
+            timebase: 0.02,
+            gain: 1.0,
+            trigger: true,
+        })
+    }
+
+    fn update(&mut self) {
+        let audio = self.audio.borrow();
+        let visible = audio
+            .traces
+            .iter()
+            .filter(|trace| trace.visible)
+            .collect::<Vec<_>>();
+        let lane_height = 2.0 / (2 * visible.len()).max(1) as f32;
+        let width = self.wgpu_resources.config.width as usize;
+
+        let mut vertices = Vec::new();
+        for (i, trace) in visible.into_iter().enumerate() {
+            let window =
+                ((self.timebase * trace.sample_rate as f32) as usize).clamp(2, HISTORY / 2);
+            let start = self.sweep_start(trace, window);
+            // About a point per pixel is plenty
+            let step = (window / width.max(1)).max(1);
+            let [r, g, b] = trace.color;
+            for channel in 0..2 {
+                let center = 1.0 - lane_height * ((2 * i + channel) as f32 + 0.5);
+                vertices.push(FlatVertex {
+                    position: [-1.0, center],
+                    color: GRID_COLOR,
+                });
+                vertices.push(FlatVertex {
+                    position: [1.0, center],
+                    color: GRID_COLOR,
+                });
+
+                let points = trace
+                    .samples
+                    .iter()
+                    .skip(start)
+                    .take(window)
+                    .enumerate()
+                    .step_by(step)
+                    .map(|(j, sample)| {
+                        let y = (self.gain * sample[channel]).clamp(-1.0, 1.0);
+                        [
+                            2.0 * j as f32 / (window - 1) as f32 - 1.0,
+                            center + y * lane_height / 2.0,
+                        ]
+                    })
+                    .collect::<Vec<_>>();
+                for segment in points.windows(2) {
+                    for &position in segment {
+                        vertices.push(FlatVertex {
+                            position,
+                            color: [r, g, b, 1.0],
+                        });
+                    }
+                }
+            }
+        }
+        self.vertices = flat_vertices(&self.wgpu_resources, "Waveform Vertex Buffer", &vertices);
+    }
+
+    fn render(&self, view: &wgpu::TextureView) {
+        render_flat(&self.wgpu_resources, view, &self.pipeline, &self.vertices);
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        self.wgpu_resources.resize(width, height);
+    }
+
+    fn params(&self) -> Vec<Param> {
+        vec![
+            Param {
+                name: "timebase",
+                description: "seconds across the screen",
+                value: self.timebase,
+                min: 0.001,
+                max: 0.09,
+            },
+            Param {
+                name: "gain",
+                description: "scales the channels in their lanes",
+                value: self.gain,
+                min: 0.0,
+                max: 100.0,
+            },
+            Param {
+                name: "trigger",
+                description: "1 to start sweeps where X rises through 0, 0 to free-run",
+                value: self.trigger as u8 as f32,
+                min: 0.0,
+                max: 1.0,
+            },
+        ]
+    }
+
+    fn set_param(&mut self, name: &str, value: f32) -> anyhow::Result<()> {
+        Param::check(&self.params(), name, value)?;
+        match name {
+            "timebase" => self.timebase = value,
+            "gain" => self.gain = value,
+            _ => self.trigger = value >= 0.5,
+        }
+        Ok(())
+    }
+}
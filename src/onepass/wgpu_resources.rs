@@ -1,73 +1,162 @@
-use std::{marker::PhantomData, mem, num::NonZeroU64};
+use std::{marker::PhantomData, mem, num::NonZeroU64, sync::Arc};
 
+use anyhow::Context;
 use wgpu::util::DeviceExt;
 use winit::window::Window;
 
-use super::oscilloscope::Vertex;
+use super::{gpu::GpuOptions, oscilloscope::Vertex};
 
 /// WgpuResources holds the information needed to set up shader pipeline and whatnot.
 pub struct WgpuResources {
-    pub surface: wgpu::Surface,
+    /// The window surface, or `None` when rendering headless into offscreen textures.
+    pub surface: Option<wgpu::Surface>,
+    /// The size and format of what's rendered into, whether or not there's a surface.
     pub config: wgpu::SurfaceConfiguration,
-    pub adapter: wgpu::Adapter,
-    pub device: wgpu::Device,
-    pub queue: wgpu::Queue,
+    /// The adapter the device was opened on, or `None` for a device the caller opened.
+    pub adapter: Option<wgpu::Adapter>,
+    /// Shared, so an app that embeds the oscilloscope can keep using its own device.
+    pub device: Arc<wgpu::Device>,
+    pub queue: Arc<wgpu::Queue>,
 }
 
 impl WgpuResources {
-    pub fn new(window: &Window) -> Self {
+    pub fn new(window: &Window, options: &GpuOptions) -> anyhow::Result<Self> {
         let size = window.inner_size();
-        let instance = wgpu::Instance::new(wgpu::Backends::all());
+        let instance = wgpu::Instance::new(options.backends.0);
         let surface = unsafe { instance.create_surface(&window) };
 
-        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::default(),
-            compatible_surface: Some(&surface),
-            force_fallback_adapter: false,
-        }))
-        .unwrap(); // TODO: handle this unwrap
-
-        let (device, queue) = pollster::block_on(adapter.request_device(
-            &wgpu::DeviceDescriptor {
-                label: Some("Device Descriptor"),
-                features: wgpu::Features::POLYGON_MODE_LINE,
-                limits: wgpu::Limits::default(),
-            },
-            None,
-        ))
-        .unwrap(); // TODO: handle this unwrap
+        let adapter = options.adapter(&instance, Some(&surface))?;
+        let (device, queue) = request_device(&adapter)?;
 
+        let format = surface.get_preferred_format(&adapter).with_context(|| {
+            format!(
+                "{} can't draw to the window's surface",
+                adapter.get_info().name
+            )
+        })?;
+        // Drivers that can't present this way fall back to vsync, with a warning in the log
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface.get_preferred_format(&adapter).unwrap(), // TODO: handle this unwrap
+            format,
             width: size.width,
             height: size.height,
-            present_mode: wgpu::PresentMode::Fifo,
+            present_mode: options.present_mode.into(),
         };
 
         surface.configure(&device, &config);
 
+        Ok(WgpuResources {
+            surface: Some(surface),
+            config,
+            adapter: Some(adapter),
+            device: Arc::new(device),
+            queue: Arc::new(queue),
+        })
+    }
+
+    /// Sets up a device without a window. `config` describes the offscreen render target.
+    pub fn new_headless(width: u32, height: u32, options: &GpuOptions) -> anyhow::Result<Self> {
+        let instance = wgpu::Instance::new(options.backends.0);
+
+        let adapter = options.adapter(&instance, None)?;
+        let (device, queue) = request_device(&adapter)?;
+
+        let mut wgpu_resources = WgpuResources::with_device(
+            Arc::new(device),
+            Arc::new(queue),
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            width,
+            height,
+        );
+        wgpu_resources.adapter = Some(adapter);
+        Ok(wgpu_resources)
+    }
+
+    /// Uses a device the caller already has, eg. their own app's, to render into their own
+    /// textures, which are `width` by `height` and `format`. Nothing is presented; see
+    /// `Oscilloscope::render_to` and `Oscilloscope::encode`.
+    pub fn with_device(
+        device: Arc<wgpu::Device>,
+        queue: Arc<wgpu::Queue>,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format,
+            width,
+            height,
+            present_mode: wgpu::PresentMode::Fifo,
+        };
+
         WgpuResources {
-            surface,
+            surface: None,
             config,
-            adapter,
+            adapter: None,
             device,
             queue,
         }
     }
-    pub fn frame(&self) -> (wgpu::SurfaceTexture) {
-        match self.surface.get_current_texture() {
-            Ok(frame) => frame,
-            Err(_) => {
-                self.surface.configure(&self.device, &self.config);
-                self.surface
-                    .get_current_texture()
-                    .expect("Failed to acquire next surface texture!")
+
+    /// Another WgpuResources on the same device, rendering into targets like this one's, so a
+    /// second renderer can draw into the same frames.
+    pub fn share(&self) -> Self {
+        WgpuResources::with_device(
+            self.device.clone(),
+            self.queue.clone(),
+            self.config.format,
+            self.config.width,
+            self.config.height,
+        )
+    }
+
+    /// Resizes the window's surface, or the offscreen target when headless. Sizes of 0, eg.
+    /// while the window is minimized, are ignored since a surface can't be that small.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        self.config.width = width;
+        self.config.height = height;
+        if let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.config);
+        }
+    }
+
+    /// The window's next frame to draw into. A surface that's been lost or gone out of date is
+    /// configured again first.
+    pub fn frame(&self) -> Result<wgpu::SurfaceTexture, wgpu::SurfaceError> {
+        let surface = self
+            .surface
+            .as_ref()
+            .expect("headless WgpuResources have no surface to draw frames from");
+        match surface.get_current_texture() {
+            Ok(frame) => Ok(frame),
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                surface.configure(&self.device, &self.config);
+                surface.get_current_texture()
             }
+            Err(e) => Err(e),
         }
     }
 }
 
+/// Opens a device on `adapter`. Nothing needs optional features, and the adapter's own limits
+/// are asked for rather than the defaults, so that GL and software adapters that fall short of
+/// the defaults can still be used.
+fn request_device(adapter: &wgpu::Adapter) -> anyhow::Result<(wgpu::Device, wgpu::Queue)> {
+    pollster::block_on(adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            label: Some("Device Descriptor"),
+            features: wgpu::Features::empty(),
+            limits: adapter.limits(),
+        },
+        None,
+    ))
+    .with_context(|| format!("opening a device on {}", adapter.get_info().name))
+}
+
 pub struct UniformBinder<'a, T> {
     uniform_type: PhantomData<T>,
     wgpu_resources: &'a WgpuResources,
@@ -129,7 +218,6 @@ impl<'a, T> UniformBinder<'a, T> {
 }
 
 pub struct WavStreamBinder<'a> {
-    wavspec: &'a hound::WavSpec,
     wgpu_resources: &'a WgpuResources,
     pub sample_buffer_size: usize,
     pub compute_buffer_factor: usize,
@@ -139,12 +227,10 @@ pub struct WavStreamBinder<'a> {
 impl<'a> WavStreamBinder<'a> {
     pub fn new(
         wgpu_resources: &'a WgpuResources,
-        wavspec: &'a hound::WavSpec,
         sample_buffer_size: usize,
         compute_buffer_factor: usize,
     ) -> Self {
         Self {
-            wavspec,
             wgpu_resources,
             sample_buffer_size,
             compute_buffer_factor,
@@ -211,7 +297,7 @@ impl<'a> WavStreamBinder<'a> {
                                     buffer: instance_buffer,
                                     offset: (i
                                         * self.compute_buffer_size
-                                        * std::mem::size_of::<[i32; 2]>())
+                                        * std::mem::size_of::<Vertex>())
                                         as u64,
                                     size,
                                 }),
@@ -224,7 +310,8 @@ impl<'a> WavStreamBinder<'a> {
 
     pub fn new_buffers(&self) -> (wgpu::Buffer, wgpu::Buffer) {
         // TODO: do not init buffer for efficiency maybe?
-        let data = vec![[0.0f32, 0.0]; self.sample_buffer_size];
+        // One extra slot mirrors slot 0 so every instance has a `next` sample to pair with.
+        let data = vec![Vertex([0.0, 0.0, 0.0]); self.sample_buffer_size + 1];
 
         let instance_buffer =
             self.wgpu_resources
@@ -243,7 +330,7 @@ impl<'a> WavStreamBinder<'a> {
             .create_buffer(&wgpu::BufferDescriptor {
                 label: Some("Uniform Buffer"),
                 mapped_at_creation: false,
-                size: (self.compute_buffer_size * std::mem::size_of::<[i32; 2]>())
+                size: (self.compute_buffer_size * std::mem::size_of::<[i32; 3]>())
                     as wgpu::BufferAddress, // TODO: parameterize this?
                 usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
             });
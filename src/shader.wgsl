@@ -1,34 +0,0 @@
-// Vertex shader
-
-struct VertexInput {
-    [[location(0)]] position: vec3<f32>;
-    [[location(1)]] color: vec4<f32>;
-};
-
-struct VertexOutput {
-    [[builtin(position)]] clip_position: vec4<f32>;
-    [[location(0)]] color: vec4<f32>;
-};
-
-[[stage(vertex)]]
-fn vs_main(
-    model: VertexInput,
-) -> VertexOutput {
-    var out: VertexOutput;
-
-    out.color = model.color;
-    out.clip_position = vec4<f32>(model.position.x, model.position.y, 0.0, 1.0);
-    return out;
-}
-
-// Fragment shader
-
-[[group(0), binding(0)]]
-var t_diffuse: texture_2d<f32>;
-[[group(0), binding(1)]]
-var s_diffuse: sampler;
-
-[[stage(fragment)]]
-fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
-    return in.color;
-}
\ No newline at end of file
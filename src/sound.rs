@@ -1,29 +1,246 @@
-use std::{fs::File, iter::Map, path::Path};
+use std::{fs::File, io::BufWriter, path::Path, str::FromStr};
 
-use hound::WavIntoSamples;
-use itertools::{Itertools, Tuples};
+use anyhow::{bail, Context};
 
-type ConvertFn = fn(Result<i32, hound::Error>) -> i32;
-type SampleIterator = Tuples<Map<hound::WavIntoSamples<File, i32>, ConvertFn>, (i32, i32)>;
+/// A single frame of samples: X, Y and an optional Z (intensity) channel.
+pub type Frame = (i32, i32, Option<i32>);
 
+/// Source is a stream of frames that feeds a trace, eg. a WAV file or a generator.
+pub trait Source: Iterator<Item = Frame> {
+    fn sample_rate(&self) -> u32;
+    /// How many channels the source's frames fill: 2 for XY, or 3 if they have a Z channel.
+    fn channels(&self) -> u16;
+
+    /// Jumps to `seconds` from the start, for sources that can, returning the frame it got to,
+    /// which is the end for anything past it.
+    fn seek(&mut self, _seconds: f32) -> anyhow::Result<u64> {
+        bail!("this source can't seek")
+    }
+}
+
+/// WavStreamer plays a WAV file. Samples of any format are scaled to 16 bits, like every other
+/// source's.
 pub struct WavStreamer {
     pub spec: hound::WavSpec,
-    samples: SampleIterator,
+    reader: hound::WavReader<File>,
+    path: String,
+    // Set when a sample can't be read, eg. in a truncated file, after which the stream is over
+    failed: bool,
 }
 
 impl WavStreamer {
-    pub fn new(filename: &str) -> Self {
-        let inp_file = File::open(Path::new(filename)).unwrap();
-        let wav_reader = hound::WavReader::new(inp_file).unwrap();
-        let spec = wav_reader.spec();
-        let samples = wav_reader
-            .into_samples::<i32>()
-            .map((|i| i.unwrap()) as ConvertFn)
-            .tuples();
-        Self { spec, samples }
-    }
-
-    pub fn iter(&mut self) -> &mut SampleIterator {
-        &mut self.samples
+    pub fn new(filename: &str) -> anyhow::Result<Self> {
+        let inp_file =
+            File::open(Path::new(filename)).with_context(|| format!("opening {}", filename))?;
+        let reader = hound::WavReader::new(inp_file)
+            .with_context(|| format!("reading {} as a WAV", filename))?;
+        let spec = reader.spec();
+        match (spec.sample_format, spec.bits_per_sample) {
+            (hound::SampleFormat::Int, 1..=32) | (hound::SampleFormat::Float, 32) => {}
+            (format, bits) => bail!(
+                "{} has {}-bit {:?} samples, which aren't supported",
+                filename,
+                bits,
+                format
+            ),
+        }
+        Ok(Self {
+            spec,
+            reader,
+            path: filename.to_string(),
+            failed: false,
+        })
+    }
+}
+
+impl Iterator for WavStreamer {
+    type Item = Frame;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let channels = self.spec.channels;
+        let bits = self.spec.bits_per_sample as i32;
+        let mut error = None;
+        let mut read = |sample: hound::Result<i32>| match sample {
+            Ok(sample) => Some(sample),
+            Err(e) => {
+                error = Some(e);
+                None
+            }
+        };
+        let frame = match self.spec.sample_format {
+            hound::SampleFormat::Int => FrameIterator {
+                samples: self.reader.samples::<i32>().map_while(|sample| {
+                    read(sample.map(|sample| match bits {
+                        16 => sample,
+                        bits if bits > 16 => sample >> (bits - 16),
+                        bits => sample << (16 - bits),
+                    }))
+                }),
+                channels,
+            }
+            .next(),
+            hound::SampleFormat::Float => FrameIterator {
+                samples: self.reader.samples::<f32>().map_while(|sample| {
+                    read(sample.map(|sample| (sample * i16::MAX as f32) as i32))
+                }),
+                channels,
+            }
+            .next(),
+        };
+        if let Some(e) = error {
+            log::warn!(
+                "{}: stopping at a sample that can't be read: {}",
+                self.path,
+                e
+            );
+            self.failed = true;
+        }
+        frame
+    }
+}
+
+impl Source for WavStreamer {
+    fn sample_rate(&self) -> u32 {
+        self.spec.sample_rate
+    }
+
+    fn channels(&self) -> u16 {
+        // Mono files are played as XY with Y copied from X
+        self.spec.channels.clamp(2, 3)
+    }
+
+    fn seek(&mut self, seconds: f32) -> anyhow::Result<u64> {
+        // Seeking past the end just ends the stream
+        let frame =
+            ((seconds.max(0.0) * self.spec.sample_rate as f32) as u32).min(self.reader.duration());
+        self.reader.seek(frame)?;
+        self.failed = false;
+        Ok(frame as u64)
+    }
+}
+
+/// FrameIterator groups interleaved samples into frames. The first two channels are X and Y,
+/// the third (if present) is Z, and any further channels are ignored.
+pub struct FrameIterator<I> {
+    samples: I,
+    channels: u16,
+}
+
+impl<I: Iterator<Item = i32>> Iterator for FrameIterator<I> {
+    type Item = Frame;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let x = self.samples.next()?;
+        let y = if self.channels > 1 {
+            self.samples.next()?
+        } else {
+            x
+        };
+        let z = if self.channels > 2 {
+            Some(self.samples.next()?)
+        } else {
+            None
+        };
+        for _ in 3..self.channels {
+            self.samples.next()?;
+        }
+        Some((x, y, z))
+    }
+}
+
+/// WavFormat is how samples are stored in a WAV being written.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum WavFormat {
+    Int8,
+    #[default]
+    Int16,
+    Int24,
+    Int32,
+    Float32,
+}
+
+impl WavFormat {
+    fn spec(self, sample_rate: u32, channels: u16) -> hound::WavSpec {
+        let (bits_per_sample, sample_format) = match self {
+            WavFormat::Int8 => (8, hound::SampleFormat::Int),
+            WavFormat::Int16 => (16, hound::SampleFormat::Int),
+            WavFormat::Int24 => (24, hound::SampleFormat::Int),
+            WavFormat::Int32 => (32, hound::SampleFormat::Int),
+            WavFormat::Float32 => (32, hound::SampleFormat::Float),
+        };
+        hound::WavSpec {
+            channels,
+            sample_rate,
+            bits_per_sample,
+            sample_format,
+        }
+    }
+}
+
+impl FromStr for WavFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "i8" => Ok(WavFormat::Int8),
+            "i16" => Ok(WavFormat::Int16),
+            "i24" => Ok(WavFormat::Int24),
+            "i32" => Ok(WavFormat::Int32),
+            "f32" => Ok(WavFormat::Float32),
+            _ => bail!(
+                "unknown WAV format {:?}, expected i8, i16, i24, i32 or f32",
+                s
+            ),
+        }
+    }
+}
+
+/// WavRecorder writes frames to a WAV, with X on the first channel, Y on the second and Z (if
+/// there are 3 channels) on the third, which the renderer can play straight back. Frames are
+/// 16-bit, so wider formats are scaled up from them rather than adding precision.
+pub struct WavRecorder {
+    writer: hound::WavWriter<BufWriter<File>>,
+    format: WavFormat,
+    channels: u16,
+}
+
+impl WavRecorder {
+    pub fn create(
+        path: &str,
+        sample_rate: u32,
+        channels: u16,
+        format: WavFormat,
+    ) -> anyhow::Result<Self> {
+        let writer = hound::WavWriter::create(path, format.spec(sample_rate, channels))
+            .with_context(|| format!("creating {}", path))?;
+        Ok(Self {
+            writer,
+            format,
+            channels,
+        })
+    }
+
+    /// Writes a frame. Frames without a Z channel are recorded with the beam fully on.
+    pub fn write(&mut self, (x, y, z): Frame) -> hound::Result<()> {
+        let z = z.unwrap_or(i16::MAX as i32);
+        for sample in [x, y, z].into_iter().take(self.channels as usize) {
+            let sample = sample.clamp(i16::MIN as i32, i16::MAX as i32);
+            match self.format {
+                WavFormat::Int8 => self.writer.write_sample((sample >> 8) as i8)?,
+                WavFormat::Int16 => self.writer.write_sample(sample as i16)?,
+                WavFormat::Int24 => self.writer.write_sample(sample << 8)?,
+                WavFormat::Int32 => self.writer.write_sample(sample << 16)?,
+                WavFormat::Float32 => self.writer.write_sample(sample as f32 / i16::MAX as f32)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Fills in the WAV's header, which it needs to be readable.
+    pub fn finish(self) -> hound::Result<()> {
+        self.writer.finalize()
     }
 }
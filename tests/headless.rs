@@ -0,0 +1,85 @@
+//! Drives a headless scope over `--control=stdin`, the way a test harness would.
+
+use std::{
+    f32::consts::TAU,
+    io::{BufRead, BufReader, Read, Write},
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+/// Writes a second of a circle, a sine on X and a cosine on Y, to a WAV in the temp directory.
+fn circle_wav() -> PathBuf {
+    let path = std::env::temp_dir().join(format!("oscilloscope-test-{}.wav", std::process::id()));
+    let spec = hound::WavSpec {
+        channels: 2,
+        sample_rate: 44100,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+    for i in 0..44100 {
+        let phase = TAU * 441.0 * i as f32 / 44100.0;
+        writer.write_sample((phase.sin() * 16000.0) as i16).unwrap();
+        writer.write_sample((phase.cos() * 16000.0) as i16).unwrap();
+    }
+    writer.finalize().unwrap();
+    path
+}
+
+#[test]
+fn commands_and_queries_over_stdin() {
+    let wav = circle_wav();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_oscilloscope"))
+        .arg("headless")
+        .arg("--control=stdin")
+        .arg("--size=64x64")
+        .arg(&wav)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut answer = || {
+        let mut line = String::new();
+        stdout.read_line(&mut line).unwrap();
+        line
+    };
+
+    // Ignored, since the write fails if it's already given up
+    let _ = writeln!(stdin, "set thickness 0.01");
+    let first = answer();
+    if first.is_empty() {
+        // It couldn't start, which is only fine if there's no GPU to run on
+        let mut stderr = String::new();
+        child
+            .stderr
+            .take()
+            .unwrap()
+            .read_to_string(&mut stderr)
+            .unwrap();
+        child.wait().unwrap();
+        std::fs::remove_file(&wav).unwrap();
+        assert!(stderr.contains("adapter"), "headless failed: {}", stderr);
+        eprintln!("skipping, there's no graphics adapter: {}", stderr.trim());
+        return;
+    }
+    assert_eq!(first.trim(), r#"{"ok": true}"#);
+
+    writeln!(stdin, "pause").unwrap();
+    assert_eq!(answer().trim(), r#"{"ok": true}"#);
+    writeln!(stdin, "status").unwrap();
+    let status = answer();
+    assert!(status.contains(r#""thickness": 0.01"#), "{}", status);
+    assert!(status.contains(r#""paused": true"#), "{}", status);
+
+    writeln!(stdin, "thickness").unwrap();
+    assert!(answer().starts_with(r#"{"error": "#));
+
+    // Closing stdin stops it
+    drop(stdin);
+    assert!(child.wait().unwrap().success());
+    std::fs::remove_file(&wav).unwrap();
+}